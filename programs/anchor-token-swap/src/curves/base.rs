@@ -1,7 +1,7 @@
 use {
     super::{
         ConstantPriceCurve, ConstantProductCurve, CurveCalculator, OffsetCurve, RoundDirection,
-        SwapWithoutFeesResult, TradeDirection,
+        StableCurve, SwapWithoutFeesResult, TradeDirection, WeightedCurve,
     },
     crate::Fees,
     anchor_lang::prelude::*,
@@ -11,7 +11,6 @@ use {
 /// "sensible" given a maximum of u128.
 /// Note that on Ethereum, Uniswap uses the geometric mean of all provided
 /// input amounts, and Balancer uses 100 * 10 ^ 18.
-
 pub const INITIAL_SWAP_POOL_AMOUNT: u128 = 1_000_000_000;
 
 pub struct SwapCurve {
@@ -26,6 +25,10 @@ impl SwapCurve {
                 Box::new(ConstantPriceCurve { token_b_price })
             }
             CurveType::Offset { token_b_offset } => Box::new(OffsetCurve { token_b_offset }),
+            CurveType::Stable { amp } => Box::new(StableCurve { amp }),
+            CurveType::Weighted { weight_a, weight_b } => {
+                Box::new(WeightedCurve { weight_a, weight_b })
+            }
         };
         SwapCurve {
             curve_type,
@@ -70,6 +73,51 @@ impl SwapCurve {
             owner_fee,
         })
     }
+    /// Calculate how much source token, including fees, is required to get
+    /// exactly the given amount of destination token.
+    pub fn swap_exact_out(
+        &self,
+        destination_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+        fees: &Fees,
+    ) -> Option<SwapResult> {
+        let SwapWithoutFeesResult {
+            source_amount_swapped,
+            destination_amount_swapped,
+        } = self.calculator.swap_without_fees_exact_out(
+            destination_amount,
+            swap_source_amount,
+            swap_destination_amount,
+            trade_direction,
+        )?;
+
+        // gross up the amount to credit the fees, which are debited from the
+        // source amount in `swap`. Since fees are rounded independently, with
+        // a minimum of 1 token each, the inverse can fall a bit short, so
+        // bump the source amount until all fees are covered.
+        let mut source_amount = fees.pre_trading_fee_amount(source_amount_swapped)?;
+        let (trade_fee, owner_fee) = loop {
+            let trade_fee = fees.trading_fee(source_amount)?;
+            let owner_fee = fees.owner_trading_fee(source_amount)?;
+            let total_fees = trade_fee.checked_add(owner_fee)?;
+            if source_amount.saturating_sub(total_fees) >= source_amount_swapped {
+                break (trade_fee, owner_fee);
+            }
+            source_amount = source_amount_swapped.checked_add(total_fees)?;
+        };
+
+        Some(SwapResult {
+            new_swap_source_amount: swap_source_amount.checked_add(source_amount)?,
+            new_swap_destination_amount: swap_destination_amount
+                .checked_sub(destination_amount_swapped)?,
+            source_amount_swapped: source_amount,
+            destination_amount_swapped,
+            trade_fee,
+            owner_fee,
+        })
+    }
     /// Get the amount of pool tokens for the deposited amount of token A or B
     pub fn deposit_single_token_type(
         &self,
@@ -99,6 +147,59 @@ impl SwapCurve {
             trade_direction,
         )
     }
+    /// Get the amount of token A or B, including fees, to deposit for the
+    /// given amount of pool tokens
+    pub fn deposit_single_token_type_exact_out(
+        &self,
+        pool_tokens: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+        fees: &Fees,
+    ) -> Option<u128> {
+        if pool_tokens == 0 {
+            return Some(0);
+        }
+        let source_amount = self.calculator.deposit_single_token_type_exact_out(
+            pool_tokens,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            pool_supply,
+            trade_direction,
+        )?;
+        // gross up the amount to credit the trading fee on *half* of it, which
+        // is debited in `deposit_single_token_type`, bumping it until all fees
+        // are covered as in `swap_exact_out`
+        let half_source_amount = source_amount.checked_add(1)?.checked_div(2)?; // round up
+        let mut gross_source_amount = source_amount
+            .checked_sub(half_source_amount)?
+            .checked_add(fees.pre_trading_fee_amount(half_source_amount)?)?;
+        loop {
+            let half_gross_source_amount = std::cmp::max(1, gross_source_amount.checked_div(2)?);
+            let trade_fee = fees.trading_fee(half_gross_source_amount)?;
+            let owner_fee = fees.owner_trading_fee(half_gross_source_amount)?;
+            let total_fees = trade_fee.checked_add(owner_fee)?;
+            if gross_source_amount.saturating_sub(total_fees) >= source_amount {
+                break;
+            }
+            gross_source_amount = source_amount.checked_add(total_fees)?;
+        }
+        // the square roots of `deposit_single_token_type` are approximations,
+        // so ask for one more token when the amount falls just short
+        let pool_tokens_minted = self.deposit_single_token_type(
+            gross_source_amount,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            pool_supply,
+            trade_direction,
+            fees,
+        )?;
+        if pool_tokens_minted < pool_tokens {
+            gross_source_amount = gross_source_amount.checked_add(1)?;
+        }
+        Some(gross_source_amount)
+    }
     /// Get the amount of pool tokens for the withdrawn amount of token A or B
     pub fn withdraw_single_token_type_exact_out(
         &self,
@@ -130,6 +231,35 @@ impl SwapCurve {
             RoundDirection::Ceiling,
         )
     }
+    /// Get the amount of token A or B withdrawn for the given amount of pool
+    /// tokens
+    pub fn withdraw_single_token_type_exact_in(
+        &self,
+        pool_tokens: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+        fees: &Fees,
+    ) -> Option<u128> {
+        if pool_tokens == 0 {
+            return Some(0);
+        }
+        let destination_amount = self.calculator.withdraw_single_token_type_exact_in(
+            pool_tokens,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            pool_supply,
+            trade_direction,
+        )?;
+        // Debit the trading fee incurred if *half* the destination amount is
+        // swapped for the other side, as done for single-sided deposits
+        let half_destination_amount = std::cmp::max(1, destination_amount.checked_div(2)?);
+        let trade_fee = fees.trading_fee(half_destination_amount)?;
+        let owner_fee = fees.owner_trading_fee(half_destination_amount)?;
+        let total_fees = trade_fee.checked_add(owner_fee)?;
+        destination_amount.checked_sub(total_fees)
+    }
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, InitSpace, Copy)]
@@ -141,6 +271,25 @@ pub enum CurveType {
     ConstantPrice { token_b_price: u64 },
     /// Offset curve, like Uniswap, but the token B side has a faked offset
     Offset { token_b_offset: u64 },
+    /// Curve.fi StableSwap curve for pegged pairs, amplified by `amp`
+    Stable { amp: u64 },
+    /// Balancer-style weighted curve, invariant = token_a_amount ^ weight_a *
+    /// token_b_amount ^ weight_b, with weights in percent
+    Weighted { weight_a: u64, weight_b: u64 },
+}
+
+impl CurveType {
+    /// Discriminant of the curve type, part of the address of the swap so
+    /// that every curve has its own canonical pool, whatever its parameters
+    pub fn discriminant(&self) -> u8 {
+        match self {
+            CurveType::ConstantProduct => 0,
+            CurveType::ConstantPrice { .. } => 1,
+            CurveType::Offset { .. } => 2,
+            CurveType::Stable { .. } => 3,
+            CurveType::Weighted { .. } => 4,
+        }
+    }
 }
 
 pub struct SwapResult {
@@ -157,3 +306,96 @@ pub struct SwapResult {
     /// Amount of source tokens going to owner
     pub owner_fee: u128,
 }
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::curves::tests::total_and_intermediate, proptest::prelude::*};
+
+    fn fees(trade_fee_numerator: u64, owner_trade_fee_numerator: u64) -> Fees {
+        Fees {
+            trade_fee_numerator,
+            trade_fee_denominator: 10_000,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator: 10_000,
+            owner_withdraw_fee_numerator: 0,
+            owner_withdraw_fee_denominator: 0,
+            host_fee_numerator: 0,
+            host_fee_denominator: 0,
+        }
+    }
+
+    #[test]
+    fn swap_exact_out_covers_minimum_fees() {
+        let swap_curve = SwapCurve::new(CurveType::ConstantProduct);
+        // 1 token out requires 2 tokens in before fees, and each fee takes
+        // at least 1 token
+        let result = swap_curve
+            .swap_exact_out(1, 1_000, 1_000, TradeDirection::AtoB, &fees(25, 5))
+            .unwrap();
+        assert_eq!(result.trade_fee, 1);
+        assert_eq!(result.owner_fee, 1);
+        assert_eq!(result.source_amount_swapped, 4);
+        assert_eq!(result.destination_amount_swapped, 1);
+        assert_eq!(result.new_swap_source_amount, 1_004);
+        assert_eq!(result.new_swap_destination_amount, 999);
+    }
+
+    #[test]
+    fn discriminant_matches_serialization() {
+        let curve_types = [
+            CurveType::ConstantProduct,
+            CurveType::ConstantPrice { token_b_price: 1 },
+            CurveType::Offset { token_b_offset: 2 },
+            CurveType::Stable { amp: 100 },
+            CurveType::Weighted {
+                weight_a: 80,
+                weight_b: 20,
+            },
+        ];
+        for curve_type in curve_types {
+            assert_eq!(
+                curve_type.discriminant(),
+                curve_type.try_to_vec().unwrap()[0]
+            );
+        }
+        // the parameters are not part of the address of the swap
+        assert_eq!(
+            CurveType::Offset { token_b_offset: 1 }.discriminant(),
+            CurveType::Offset { token_b_offset: 2 }.discriminant()
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn swap_exact_out_with_fees_covers_destination(
+            (swap_destination_amount, destination_amount) in total_and_intermediate(u32::MAX as u64),
+            swap_source_amount in 1..u32::MAX as u128,
+            trade_fee_numerator in 0..1_000u64,
+            owner_trade_fee_numerator in 0..1_000u64,
+        ) {
+            let swap_curve = SwapCurve::new(CurveType::ConstantProduct);
+            let fees = fees(trade_fee_numerator, owner_trade_fee_numerator);
+            let result = swap_curve
+                .swap_exact_out(
+                    destination_amount as u128,
+                    swap_source_amount,
+                    swap_destination_amount as u128,
+                    TradeDirection::AtoB,
+                    &fees,
+                )
+                .unwrap();
+            let forward_result = swap_curve
+                .swap(
+                    result.source_amount_swapped,
+                    swap_source_amount,
+                    swap_destination_amount as u128,
+                    TradeDirection::AtoB,
+                    &fees,
+                )
+                .unwrap();
+            prop_assert_eq!(forward_result.trade_fee, result.trade_fee);
+            prop_assert_eq!(forward_result.owner_fee, result.owner_fee);
+            prop_assert!(forward_result.destination_amount_swapped >= destination_amount as u128);
+        }
+    }
+}
@@ -18,6 +18,19 @@ pub trait CurveCalculatorTrait {
         trade_direction: TradeDirection,
     ) -> Option<SwapWithoutFeesResult>;
 
+    /// Calculate how much source token is required to get exactly the given
+    /// amount of destination token, the inverse of `swap_without_fees`.
+    ///
+    /// The source amount is rounded up, so that swapping it back through
+    /// `swap_without_fees` provides at least the destination amount.
+    fn swap_without_fees_exact_out(
+        &self,
+        destination_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<SwapWithoutFeesResult>;
+
     /// Get the supply for a new pool
     /// The default implementation is a Balancer-style fixed initial supply
     #[inline]
@@ -70,6 +83,20 @@ pub trait CurveCalculatorTrait {
         trade_direction: TradeDirection,
     ) -> Option<u128>;
 
+    /// Get the amount of token A or B to deposit for the given amount of pool
+    /// tokens, the inverse of `deposit_single_token_type`.
+    ///
+    /// The trading token amount is rounded up, so that depositing it through
+    /// `deposit_single_token_type` provides at least the given pool tokens.
+    fn deposit_single_token_type_exact_out(
+        &self,
+        pool_tokens: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128>;
+
     /// Get the amount of pool tokens for the withdrawn amount of token A or B.
     ///
     /// This is used for single-sided withdrawals and owner trade fee
@@ -90,6 +117,21 @@ pub trait CurveCalculatorTrait {
         round_direction: RoundDirection,
     ) -> Option<u128>;
 
+    /// Get the amount of token A or B withdrawn for the given amount of pool
+    /// tokens, the inverse of `withdraw_single_token_type_exact_out`.
+    ///
+    /// The trading token amount is rounded down, so that withdrawing it
+    /// through `withdraw_single_token_type_exact_out` never costs more than
+    /// the given pool tokens.
+    fn withdraw_single_token_type_exact_in(
+        &self,
+        pool_tokens: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128>;
+
     /// Some curves function best and prevent attacks if we prevent deposits
     /// after initialization.  For example, the offset curve in `offset.rs`,
     /// which fakes supply on one side of the swap, allows the swap creator
@@ -113,6 +155,13 @@ pub trait CurveCalculatorTrait {
         swap_token_a_amount: u128,
         swap_token_b_amount: u128,
     ) -> Option<PreciseNumber>;
+
+    /// Relative precision of `normalized_value`, for curves which can only
+    /// approximate it. Any decrease of the value within this precision may
+    /// come from the approximation alone, so checks of the value allow it.
+    fn normalized_value_precision(&self) -> Option<PreciseNumber> {
+        PreciseNumber::new(0)
+    }
 }
 
 #[derive(Clone, PartialEq, Copy)]
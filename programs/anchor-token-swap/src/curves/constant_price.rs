@@ -53,6 +53,32 @@ impl CurveCalculatorTrait for ConstantPriceCurve {
         })
     }
 
+    /// Inverse of the constant price swap, the source amount is rounded up
+    /// when buying token A with token B
+    fn swap_without_fees_exact_out(
+        &self,
+        destination_amount: u128,
+        _swap_source_amount: u128,
+        _swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<SwapWithoutFeesResult> {
+        let token_b_price = self.token_b_price as u128;
+
+        let source_amount_swapped = match trade_direction {
+            TradeDirection::BtoA => destination_amount
+                .checked_add(token_b_price)?
+                .checked_sub(1)?
+                .checked_div(token_b_price)?,
+            TradeDirection::AtoB => destination_amount.checked_mul(token_b_price)?,
+        };
+        let source_amount_swapped = map_zero_to_none(source_amount_swapped)?;
+        let destination_amount_swapped = map_zero_to_none(destination_amount)?;
+        Some(SwapWithoutFeesResult {
+            source_amount_swapped,
+            destination_amount_swapped,
+        })
+    }
+
     /// Get the amount of trading tokens for the given amount of pool tokens,
     /// provided the total trading tokens and supply of pool tokens.
     /// For the constant price curve, the total value of the pool is weighted
@@ -122,6 +148,26 @@ impl CurveCalculatorTrait for ConstantPriceCurve {
         )
     }
 
+    #[inline]
+    fn deposit_single_token_type_exact_out(
+        &self,
+        pool_tokens: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        pool_tokens_to_single_trading_tokens(
+            self.token_b_price,
+            pool_tokens,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            pool_supply,
+            trade_direction,
+            RoundDirection::Ceiling,
+        )
+    }
+
     #[inline]
     fn withdraw_single_token_type_exact_out(
         &self,
@@ -143,6 +189,26 @@ impl CurveCalculatorTrait for ConstantPriceCurve {
         )
     }
 
+    #[inline]
+    fn withdraw_single_token_type_exact_in(
+        &self,
+        pool_tokens: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        pool_tokens_to_single_trading_tokens(
+            self.token_b_price,
+            pool_tokens,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            pool_supply,
+            trade_direction,
+            RoundDirection::Floor,
+        )
+    }
+
     #[inline]
     fn validate(&self) -> Result<()> {
         if self.token_b_price == 0 {
@@ -232,3 +298,32 @@ pub fn trading_tokens_to_pool_tokens(
         ),
     }
 }
+
+/// Get the amount of token A or B worth the given amount of pool tokens, the
+/// inverse of `trading_tokens_to_pool_tokens`.
+#[inline]
+pub fn pool_tokens_to_single_trading_tokens(
+    token_b_price: u64,
+    pool_tokens: u128,
+    swap_token_a_amount: u128,
+    swap_token_b_amount: u128,
+    pool_supply: u128,
+    trade_direction: TradeDirection,
+    round_direction: RoundDirection,
+) -> Option<u128> {
+    let token_b_price = U256::from(token_b_price);
+    let total_value = U256::from(swap_token_b_amount)
+        .checked_mul(token_b_price)?
+        .checked_add(U256::from(swap_token_a_amount))?;
+    let given_value = U256::from(pool_tokens).checked_mul(total_value)?;
+    let pool_supply = U256::from(pool_supply);
+    let denominator = match trade_direction {
+        TradeDirection::AtoB => pool_supply,
+        TradeDirection::BtoA => pool_supply.checked_mul(token_b_price)?,
+    };
+    let trading_tokens = match round_direction {
+        RoundDirection::Floor => given_value.checked_div(denominator)?,
+        RoundDirection::Ceiling => given_value.checked_ceil_div(denominator)?.0,
+    };
+    u128::try_from(trading_tokens).ok()
+}
@@ -5,7 +5,7 @@ use {
     },
     crate::SwapError,
     anchor_lang::prelude::*,
-    spl_math::{checked_ceil_div::CheckedCeilDiv, precise_number::PreciseNumber},
+    spl_math::{checked_ceil_div::CheckedCeilDiv, precise_number::PreciseNumber, uint::U256},
 };
 #[derive(Clone, Default, PartialEq, Copy)]
 #[cfg_attr(feature = "debug", derive(Debug))]
@@ -36,6 +36,31 @@ pub fn swap(
     })
 }
 
+/// The inverse constant product swap calculation, factored out of its class
+/// for reuse.
+///
+/// The new source amount is rounded up, so the invariant never decreases, and
+/// the destination can never be fully drained.
+#[inline]
+pub fn swap_exact_out(
+    destination_amount: u128,
+    swap_source_amount: u128,
+    swap_destination_amount: u128,
+) -> Option<SwapWithoutFeesResult> {
+    let invariant = swap_source_amount.checked_mul(swap_destination_amount)?;
+    let new_swap_destination_amount =
+        map_zero_to_none(swap_destination_amount.checked_sub(destination_amount)?)?;
+    let (new_swap_source_amount, _) = invariant.checked_ceil_div(new_swap_destination_amount)?;
+    let source_amount_swapped =
+        map_zero_to_none(new_swap_source_amount.checked_sub(swap_source_amount)?)?;
+    let destination_amount_swapped = map_zero_to_none(destination_amount)?;
+
+    Some(SwapWithoutFeesResult {
+        source_amount_swapped,
+        destination_amount_swapped,
+    })
+}
+
 /// Calculates the total normalized value of the curve given the liquidity
 /// parameters.
 ///
@@ -85,6 +110,38 @@ pub fn deposit_single_token_type(
     }
 }
 
+/// Get the amount of token A or B to deposit for the given amount of pool
+/// tokens, inverting the Balancer formula of `deposit_single_token_type`:
+///
+///    `source_amount = swap_source_amount * ((1 + pool_tokens / pool_supply)
+/// ^ 2 - 1)`
+///
+/// which reduces to `swap_source_amount * pool_tokens * (2 * pool_supply +
+/// pool_tokens) / pool_supply ^ 2`, computed exactly and rounded up.
+pub fn deposit_single_token_type_exact_out(
+    pool_tokens: u128,
+    swap_token_a_amount: u128,
+    swap_token_b_amount: u128,
+    pool_supply: u128,
+    trade_direction: TradeDirection,
+) -> Option<u128> {
+    let swap_source_amount = match trade_direction {
+        TradeDirection::AtoB => swap_token_a_amount,
+        TradeDirection::BtoA => swap_token_b_amount,
+    };
+    let pool_supply = U256::from(pool_supply);
+    let pool_tokens = U256::from(pool_tokens);
+    let (source_amount, _) = U256::from(swap_source_amount)
+        .checked_mul(pool_tokens)?
+        .checked_mul(
+            pool_supply
+                .checked_mul(U256::from(2))?
+                .checked_add(pool_tokens)?,
+        )?
+        .checked_ceil_div(pool_supply.checked_mul(pool_supply)?)?;
+    u128::try_from(source_amount).ok()
+}
+
 /// Get the amount of pool tokens for the withdrawn amount of token A or B.
 ///
 /// The constant product implementation uses the Balancer formulas found at
@@ -118,6 +175,34 @@ pub fn withdraw_single_token_type_exact_out(
     }
 }
 
+/// Get the amount of token A or B withdrawn for the given amount of pool
+/// tokens, inverting the Balancer formula of
+/// `withdraw_single_token_type_exact_out`:
+///
+///    `source_amount = swap_source_amount * (1 - (1 - pool_tokens /
+/// pool_supply) ^ 2)`
+pub fn withdraw_single_token_type_exact_in(
+    pool_tokens: u128,
+    swap_token_a_amount: u128,
+    swap_token_b_amount: u128,
+    pool_supply: u128,
+    trade_direction: TradeDirection,
+) -> Option<u128> {
+    let swap_source_amount = match trade_direction {
+        TradeDirection::AtoB => swap_token_a_amount,
+        TradeDirection::BtoA => swap_token_b_amount,
+    };
+    let swap_source_amount = PreciseNumber::new(swap_source_amount)?;
+    let ratio = PreciseNumber::new(pool_tokens)?.checked_div(&PreciseNumber::new(pool_supply)?)?;
+    let one = PreciseNumber::new(1)?;
+    let base = one.checked_sub(&ratio)?;
+    let root = one.checked_sub(&base.checked_mul(&base)?)?;
+    swap_source_amount
+        .checked_mul(&root)?
+        .floor()?
+        .to_imprecise()
+}
+
 /// Get the amount of trading tokens for the given amount of pool tokens,
 /// provided the total trading tokens and supply of pool tokens.
 ///
@@ -179,6 +264,20 @@ impl CurveCalculatorTrait for ConstantProductCurve {
         swap(source_amount, swap_source_amount, swap_destination_amount)
     }
 
+    fn swap_without_fees_exact_out(
+        &self,
+        destination_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        _trade_direction: TradeDirection,
+    ) -> Option<SwapWithoutFeesResult> {
+        swap_exact_out(
+            destination_amount,
+            swap_source_amount,
+            swap_destination_amount,
+        )
+    }
+
     fn validate(&self) -> Result<()> {
         Ok(())
     }
@@ -201,6 +300,23 @@ impl CurveCalculatorTrait for ConstantProductCurve {
         )
     }
 
+    fn deposit_single_token_type_exact_out(
+        &self,
+        pool_tokens: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        deposit_single_token_type_exact_out(
+            pool_tokens,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            pool_supply,
+            trade_direction,
+        )
+    }
+
     fn withdraw_single_token_type_exact_out(
         &self,
         source_amount: u128,
@@ -220,6 +336,23 @@ impl CurveCalculatorTrait for ConstantProductCurve {
         )
     }
 
+    fn withdraw_single_token_type_exact_in(
+        &self,
+        pool_tokens: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        withdraw_single_token_type_exact_in(
+            pool_tokens,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            pool_supply,
+            trade_direction,
+        )
+    }
+
     fn normalized_value(
         &self,
         swap_token_a_amount: u128,
@@ -269,10 +402,12 @@ mod tests {
     use {
         super::*,
         crate::curves::tests::{
-            check_curve_value_from_swap, check_deposit_token_conversion,
-            check_pool_value_from_deposit, check_pool_value_from_withdraw,
+            check_curve_value_from_swap, check_deposit_single_exact_out,
+            check_deposit_token_conversion, check_pool_value_from_deposit,
+            check_pool_value_from_withdraw, check_swap_exact_out, check_withdraw_single_exact_in,
             check_withdraw_token_conversion, total_and_intermediate,
-            CONVERSION_BASIS_POINTS_GUARANTEE,
+            CONVERSION_BASIS_POINTS_GUARANTEE, EXACT_OUT_BASIS_POINTS_GUARANTEE,
+            SWAP_VALUE_EPSILON,
         },
         proptest::prelude::*,
     };
@@ -455,6 +590,111 @@ mod tests {
             );
         }
     }
+    #[test]
+    fn constant_product_swap_exact_out_rounding() {
+        let curve = ConstantProductCurve;
+
+        // the pool can never be drained
+        assert!(curve
+            .swap_without_fees_exact_out(30_000, 20_000, 30_000, TradeDirection::AtoB)
+            .is_none());
+
+        let tests: &[(u128, u128, u128, u128)] = &[
+            // 20_000 * 30_000 / 29_986 = 20_009.33
+            (14, 20_000, 30_000, 10),
+            // 20_000 * 30_000 / 29_985 = 20_010.005
+            (15, 20_000, 30_000, 11),
+            // 60_000 * 30_000 / 29_951 = 60_098.16
+            (49, 60_000, 30_000, 99),
+        ];
+        for (
+            destination_amount,
+            swap_source_amount,
+            swap_destination_amount,
+            expected_source_amount_swapped,
+        ) in tests
+        {
+            let result = curve
+                .swap_without_fees_exact_out(
+                    *destination_amount,
+                    *swap_source_amount,
+                    *swap_destination_amount,
+                    TradeDirection::AtoB,
+                )
+                .unwrap();
+            assert_eq!(
+                result.source_amount_swapped,
+                *expected_source_amount_swapped
+            );
+            // one less source token is not enough
+            let result = curve
+                .swap_without_fees(
+                    expected_source_amount_swapped - 1,
+                    *swap_source_amount,
+                    *swap_destination_amount,
+                    TradeDirection::AtoB,
+                )
+                .unwrap();
+            assert!(result.destination_amount_swapped < *destination_amount);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn deposit_single_exact_out_covers_pool_tokens(
+            (pool_token_supply, pool_token_amount) in total_and_intermediate(u64::MAX),
+            swap_token_a_amount in 1..u64::MAX,
+            swap_token_b_amount in 1..u64::MAX,
+        ) {
+            let curve = ConstantProductCurve {};
+            check_deposit_single_exact_out(
+                &curve,
+                pool_token_amount as u128,
+                pool_token_supply as u128,
+                swap_token_a_amount as u128,
+                swap_token_b_amount as u128,
+                TradeDirection::AtoB,
+                EXACT_OUT_BASIS_POINTS_GUARANTEE,
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn withdraw_single_exact_in_covers_pool_tokens(
+            (pool_token_supply, pool_token_amount) in total_and_intermediate(u64::MAX),
+            swap_token_a_amount in 1..u64::MAX,
+            swap_token_b_amount in 1..u64::MAX,
+        ) {
+            let curve = ConstantProductCurve {};
+            check_withdraw_single_exact_in(
+                &curve,
+                pool_token_amount as u128,
+                pool_token_supply as u128,
+                swap_token_a_amount as u128,
+                swap_token_b_amount as u128,
+                TradeDirection::AtoB,
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn swap_exact_out_covers_destination(
+            (swap_destination_amount, destination_token_amount) in total_and_intermediate(u64::MAX),
+            swap_source_amount in 1..u64::MAX,
+        ) {
+            let curve = ConstantProductCurve {};
+            check_swap_exact_out(
+                &curve,
+                destination_token_amount as u128,
+                swap_source_amount as u128,
+                swap_destination_amount as u128,
+                TradeDirection::AtoB,
+            );
+        }
+    }
+
     proptest! {
         #[test]
         fn curve_value_does_not_decrease_from_swap(
@@ -468,7 +708,8 @@ mod tests {
                 source_token_amount as u128,
                 swap_source_amount as u128,
                 swap_destination_amount as u128,
-                TradeDirection::AtoB
+                TradeDirection::AtoB,
+                SWAP_VALUE_EPSILON
             );
         }
     }
@@ -3,7 +3,12 @@ mod calculator;
 mod constant_price;
 mod constant_product;
 mod offset;
-pub use {base::*, calculator::*, constant_price::*, constant_product::*, offset::*};
+mod stable;
+mod weighted;
+pub use {
+    base::*, calculator::*, constant_price::*, constant_product::*, offset::*, stable::*,
+    weighted::*,
+};
 
 #[cfg(test)]
 mod tests;
@@ -1,6 +1,7 @@
 use {
     super::{
-        deposit_single_token_type, normalized_value, pool_tokens_to_trading_tokens, swap,
+        deposit_single_token_type, deposit_single_token_type_exact_out, normalized_value,
+        pool_tokens_to_trading_tokens, swap, swap_exact_out, withdraw_single_token_type_exact_in,
         withdraw_single_token_type_exact_out, CurveCalculator, CurveCalculatorTrait,
         RoundDirection, SwapWithoutFeesResult, TradeDirection, TradingTokenResult,
     },
@@ -48,6 +49,34 @@ impl CurveCalculatorTrait for OffsetCurve {
         swap(source_amount, swap_source_amount, swap_destination_amount)
     }
 
+    /// Inverse of the offset swap, with the same offset added to token B.
+    ///
+    /// Note that the offset is never available for trading, so the amount
+    /// must still be taken out of the real token B liquidity.
+    #[inline]
+    fn swap_without_fees_exact_out(
+        &self,
+        destination_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<SwapWithoutFeesResult> {
+        let token_b_offset = self.token_b_offset as u128;
+        let swap_source_amount = match trade_direction {
+            TradeDirection::AtoB => swap_source_amount,
+            TradeDirection::BtoA => swap_source_amount.checked_add(token_b_offset)?,
+        };
+        let swap_destination_amount = match trade_direction {
+            TradeDirection::AtoB => swap_destination_amount.checked_add(token_b_offset)?,
+            TradeDirection::BtoA => swap_destination_amount,
+        };
+        swap_exact_out(
+            destination_amount,
+            swap_source_amount,
+            swap_destination_amount,
+        )
+    }
+
     /// The conversion for the offset curve needs to take into account the
     /// offset
     #[inline]
@@ -91,6 +120,25 @@ impl CurveCalculatorTrait for OffsetCurve {
         )
     }
 
+    #[inline]
+    fn deposit_single_token_type_exact_out(
+        &self,
+        pool_tokens: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        let token_b_offset = self.token_b_offset as u128;
+        deposit_single_token_type_exact_out(
+            pool_tokens,
+            swap_token_a_amount,
+            swap_token_b_amount.checked_add(token_b_offset)?,
+            pool_supply,
+            trade_direction,
+        )
+    }
+
     #[inline]
     fn withdraw_single_token_type_exact_out(
         &self,
@@ -112,6 +160,25 @@ impl CurveCalculatorTrait for OffsetCurve {
         )
     }
 
+    #[inline]
+    fn withdraw_single_token_type_exact_in(
+        &self,
+        pool_tokens: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        let token_b_offset = self.token_b_offset as u128;
+        withdraw_single_token_type_exact_in(
+            pool_tokens,
+            swap_token_a_amount,
+            swap_token_b_amount.checked_add(token_b_offset)?,
+            pool_supply,
+            trade_direction,
+        )
+    }
+
     #[inline]
     fn validate(&self) -> Result<()> {
         if self.token_b_offset == 0 {
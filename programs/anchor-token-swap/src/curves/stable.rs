@@ -0,0 +1,691 @@
+use {
+    super::{
+        map_zero_to_none, pool_tokens_to_trading_tokens, CurveCalculator, CurveCalculatorTrait,
+        RoundDirection, SwapWithoutFeesResult, TradeDirection, TradingTokenResult,
+    },
+    crate::SwapError,
+    anchor_lang::prelude::*,
+    spl_math::{
+        checked_ceil_div::CheckedCeilDiv,
+        precise_number::{self, PreciseNumber},
+        uint::U256,
+    },
+};
+
+/// Number of coins in a pool, always 2 for this program
+const N_COINS: u8 = 2;
+/// `N_COINS ^ 2`, used when solving for the destination amount
+const N_COINS_SQUARED: u8 = 4;
+/// Maximum number of Newton iterations before giving up on convergence
+const ITERATIONS: u8 = 32;
+
+/// Minimum amplification coefficient
+pub const MIN_AMP: u64 = 1;
+/// Maximum amplification coefficient
+pub const MAX_AMP: u64 = 1_000_000;
+
+/// StableCurve struct implementing CurveCalculator, using the Curve
+/// (curve.fi) StableSwap invariant for pegged pairs
+#[derive(Clone, Default, PartialEq, Copy)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct StableCurve {
+    /// Amplification coefficient (A)
+    pub amp: u64,
+}
+
+/// Calculates A for deriving D
+///
+/// Per the Curve implementation, this A is not the same as the A from the
+/// whitepaper, it is actually `A * n ^ (n - 1)`, since `D ^ n / prod(x)`
+/// loses precision with a huge A value.
+#[inline]
+fn compute_a(amp: u64) -> Option<u64> {
+    amp.checked_mul(N_COINS as u64)
+}
+
+/// Returns `a` to the power of `b`
+#[inline]
+fn checked_u8_power(a: &U256, b: u8) -> Option<U256> {
+    let mut result = *a;
+    for _ in 1..b {
+        result = result.checked_mul(*a)?;
+    }
+    Some(result)
+}
+
+/// Returns `a` multiplied by `b`
+#[inline]
+fn checked_u8_mul(a: &U256, b: u8) -> Option<U256> {
+    let mut result = *a;
+    for _ in 1..b {
+        result = result.checked_add(*a)?;
+    }
+    Some(result)
+}
+
+/// Compute the StableSwap invariant (D) with Newton's method.
+///
+/// Equation:
+///
+///    `A * sum(x_i) * n ^ n + D = A * D * n ^ n + D ^ (n + 1) / (n ^ n *
+/// prod(x_i))`
+pub fn compute_d(leverage: u64, amount_a: u128, amount_b: u128) -> Option<u128> {
+    let amount_a_times_coins =
+        checked_u8_mul(&U256::from(amount_a), N_COINS)?.checked_add(U256::one())?;
+    let amount_b_times_coins =
+        checked_u8_mul(&U256::from(amount_b), N_COINS)?.checked_add(U256::one())?;
+    let sum_x = amount_a.checked_add(amount_b)?;
+    if sum_x == 0 {
+        return Some(0);
+    }
+    let mut d = U256::from(sum_x);
+    for _ in 0..ITERATIONS {
+        let d_product = d
+            .checked_mul(d)?
+            .checked_div(amount_a_times_coins)?
+            .checked_mul(d)?
+            .checked_div(amount_b_times_coins)?;
+        let d_previous = d;
+        d = calculate_step(&d, leverage, sum_x, &d_product)?;
+        // equality with a precision of 1
+        if d == d_previous {
+            break;
+        }
+    }
+    u128::try_from(d).ok()
+}
+
+/// A single Newton step towards D:
+///
+///    `d = (leverage * sum_x + d_product * n) * d / ((leverage - 1) * d + (n +
+/// 1) * d_product)`
+#[inline]
+fn calculate_step(initial_d: &U256, leverage: u64, sum_x: u128, d_product: &U256) -> Option<U256> {
+    let leverage_mul = U256::from(leverage).checked_mul(U256::from(sum_x))?;
+    let d_product_mul = checked_u8_mul(d_product, N_COINS)?;
+    let numerator = leverage_mul
+        .checked_add(d_product_mul)?
+        .checked_mul(*initial_d)?;
+
+    let leverage_sub = initial_d.checked_mul(U256::from(leverage.checked_sub(1)?))?;
+    let n_coins_sum = checked_u8_mul(d_product, N_COINS.checked_add(1)?)?;
+    let denominator = leverage_sub.checked_add(n_coins_sum)?;
+
+    numerator.checked_div(denominator)
+}
+
+/// Compute the new destination amount `y` given the new source amount `x`,
+/// keeping D constant.
+///
+/// Solves for y:
+///
+///    `y ^ 2 + y * (x + D / A') - D = D ^ (n + 1) / (n ^ (2 * n) * x * A')`
+///
+/// which is approximated with Newton's method, rounding up so that the pool
+/// never gives out more than the invariant allows.
+pub fn compute_new_destination_amount(
+    leverage: u64,
+    new_source_amount: u128,
+    d_val: u128,
+) -> Option<u128> {
+    let leverage = U256::from(leverage);
+    let new_source_amount = U256::from(new_source_amount);
+    let d_val = U256::from(d_val);
+
+    // c = D ^ (n + 1) / (n ^ (2 * n) * x * A')
+    let c = checked_u8_power(&d_val, N_COINS.checked_add(1)?)?
+        .checked_div(checked_u8_mul(&new_source_amount, N_COINS_SQUARED)?.checked_mul(leverage)?)?;
+    // b = x + D / A'
+    let b = new_source_amount.checked_add(d_val.checked_div(leverage)?)?;
+
+    let mut y = d_val;
+    for _ in 0..ITERATIONS {
+        let (y_new, _) = checked_u8_power(&y, 2)?
+            .checked_add(c)?
+            .checked_ceil_div(checked_u8_mul(&y, 2)?.checked_add(b)?.checked_sub(d_val)?)?;
+        if y_new == y {
+            break;
+        }
+        y = y_new;
+    }
+    u128::try_from(y).ok()
+}
+
+impl CurveCalculator for StableCurve {}
+impl CurveCalculatorTrait for StableCurve {
+    /// Stable swap keeps D constant, solving for the new destination amount
+    /// after adding the source amount
+    fn swap_without_fees(
+        &self,
+        source_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        _trade_direction: TradeDirection,
+    ) -> Option<SwapWithoutFeesResult> {
+        let leverage = compute_a(self.amp)?;
+        // the Newton approximation of D can land just below the real
+        // invariant, so bump it by 1 to never give out more than allowed
+        let d = compute_d(leverage, swap_source_amount, swap_destination_amount)?.checked_add(1)?;
+        let new_source_amount = swap_source_amount.checked_add(source_amount)?;
+        let new_destination_amount =
+            compute_new_destination_amount(leverage, new_source_amount, d)?;
+        let destination_amount_swapped =
+            map_zero_to_none(swap_destination_amount.checked_sub(new_destination_amount)?)?;
+        Some(SwapWithoutFeesResult {
+            source_amount_swapped: source_amount,
+            destination_amount_swapped,
+        })
+    }
+
+    /// The curve is symmetric, so the new source amount is found the same way
+    /// as the new destination amount of a regular swap, rounding it up
+    fn swap_without_fees_exact_out(
+        &self,
+        destination_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        _trade_direction: TradeDirection,
+    ) -> Option<SwapWithoutFeesResult> {
+        let leverage = compute_a(self.amp)?;
+        let d = compute_d(leverage, swap_source_amount, swap_destination_amount)?.checked_add(1)?;
+        let new_destination_amount =
+            map_zero_to_none(swap_destination_amount.checked_sub(destination_amount)?)?;
+        let new_source_amount =
+            compute_new_destination_amount(leverage, new_destination_amount, d)?;
+        let source_amount_swapped =
+            map_zero_to_none(new_source_amount.checked_sub(swap_source_amount)?)?;
+        let destination_amount_swapped = map_zero_to_none(destination_amount)?;
+        Some(SwapWithoutFeesResult {
+            source_amount_swapped,
+            destination_amount_swapped,
+        })
+    }
+
+    /// Re-implementation of Curve's `remove_liquidity`, which is a simple
+    /// ratio calculation, exactly like the constant product curve
+    #[inline]
+    fn pool_tokens_to_trading_tokens(
+        &self,
+        pool_tokens: u128,
+        pool_token_supply: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        round_direction: RoundDirection,
+    ) -> Option<TradingTokenResult> {
+        pool_tokens_to_trading_tokens(
+            pool_tokens,
+            pool_token_supply,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            round_direction,
+        )
+    }
+
+    /// Re-implementation of Curve's `calc_token_amount`, minting pool tokens
+    /// in proportion to the growth of D
+    fn deposit_single_token_type(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        if source_amount == 0 {
+            return Some(0);
+        }
+        let leverage = compute_a(self.amp)?;
+        let d0 = PreciseNumber::new(compute_d(
+            leverage,
+            swap_token_a_amount,
+            swap_token_b_amount,
+        )?)?;
+        let (deposit_token_amount, other_token_amount) = match trade_direction {
+            TradeDirection::AtoB => (swap_token_a_amount, swap_token_b_amount),
+            TradeDirection::BtoA => (swap_token_b_amount, swap_token_a_amount),
+        };
+        let updated_deposit_token_amount = deposit_token_amount.checked_add(source_amount)?;
+        let d1 = PreciseNumber::new(compute_d(
+            leverage,
+            updated_deposit_token_amount,
+            other_token_amount,
+        )?)?;
+        let diff = d1.checked_sub(&d0)?;
+        let pool_tokens = diff
+            .checked_mul(&PreciseNumber::new(pool_supply)?)?
+            .checked_div(&d0)?;
+        pool_tokens.floor()?.to_imprecise()
+    }
+
+    /// Inverse of `deposit_single_token_type`, growing D in proportion to the
+    /// minted pool tokens and solving for the new amount of the deposited
+    /// token, both rounded up in the pool's favor
+    fn deposit_single_token_type_exact_out(
+        &self,
+        pool_tokens: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        if pool_tokens == 0 {
+            return Some(0);
+        }
+        let leverage = compute_a(self.amp)?;
+        let d0 = compute_d(leverage, swap_token_a_amount, swap_token_b_amount)?;
+        let (deposit_token_amount, other_token_amount) = match trade_direction {
+            TradeDirection::AtoB => (swap_token_a_amount, swap_token_b_amount),
+            TradeDirection::BtoA => (swap_token_b_amount, swap_token_a_amount),
+        };
+        let (d1, _) = U256::from(d0)
+            .checked_mul(U256::from(pool_supply.checked_add(pool_tokens)?))?
+            .checked_ceil_div(U256::from(pool_supply))?;
+        let updated_deposit_token_amount =
+            compute_new_destination_amount(leverage, other_token_amount, u128::try_from(d1).ok()?)?;
+        updated_deposit_token_amount.checked_sub(deposit_token_amount)
+    }
+
+    /// Re-implementation of Curve's `calc_token_amount` for withdrawals,
+    /// burning pool tokens in proportion to the shrinkage of D
+    fn withdraw_single_token_type_exact_out(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+        round_direction: RoundDirection,
+    ) -> Option<u128> {
+        if source_amount == 0 {
+            return Some(0);
+        }
+        let leverage = compute_a(self.amp)?;
+        let d0 = PreciseNumber::new(compute_d(
+            leverage,
+            swap_token_a_amount,
+            swap_token_b_amount,
+        )?)?;
+        let (withdraw_token_amount, other_token_amount) = match trade_direction {
+            TradeDirection::AtoB => (swap_token_a_amount, swap_token_b_amount),
+            TradeDirection::BtoA => (swap_token_b_amount, swap_token_a_amount),
+        };
+        let updated_withdraw_token_amount = withdraw_token_amount.checked_sub(source_amount)?;
+        let d1 = PreciseNumber::new(compute_d(
+            leverage,
+            updated_withdraw_token_amount,
+            other_token_amount,
+        )?)?;
+        let diff = d0.checked_sub(&d1)?;
+        let pool_tokens = diff
+            .checked_mul(&PreciseNumber::new(pool_supply)?)?
+            .checked_div(&d0)?;
+        match round_direction {
+            RoundDirection::Floor => pool_tokens.floor()?.to_imprecise(),
+            RoundDirection::Ceiling => pool_tokens.ceiling()?.to_imprecise(),
+        }
+    }
+
+    /// Inverse of `withdraw_single_token_type_exact_out`, shrinking D in
+    /// proportion to the burned pool tokens and solving for the new amount of
+    /// the withdrawn token, both rounded up in the pool's favor
+    fn withdraw_single_token_type_exact_in(
+        &self,
+        pool_tokens: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        if pool_tokens == 0 {
+            return Some(0);
+        }
+        let leverage = compute_a(self.amp)?;
+        let d0 = compute_d(leverage, swap_token_a_amount, swap_token_b_amount)?;
+        let (withdraw_token_amount, other_token_amount) = match trade_direction {
+            TradeDirection::AtoB => (swap_token_a_amount, swap_token_b_amount),
+            TradeDirection::BtoA => (swap_token_b_amount, swap_token_a_amount),
+        };
+        let (d1, _) = U256::from(d0)
+            .checked_mul(U256::from(pool_supply.checked_sub(pool_tokens)?))?
+            .checked_ceil_div(U256::from(pool_supply))?;
+        let updated_withdraw_token_amount =
+            compute_new_destination_amount(leverage, other_token_amount, u128::try_from(d1).ok()?)?;
+        Some(withdraw_token_amount.saturating_sub(updated_withdraw_token_amount))
+    }
+
+    #[inline]
+    fn validate(&self) -> Result<()> {
+        if self.amp < MIN_AMP || self.amp > MAX_AMP {
+            err!(SwapError::InvalidCurve)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The normalized value of the stable curve is derived from the invariant
+    /// D, which already has the dimension `tokens ^ 1`, but sums both sides.
+    /// As with the constant price curve, we divide by 2 to normalize the
+    /// value between the two token types.
+    ///
+    /// D is homogeneous in the token amounts, so it is computed on amounts
+    /// scaled up by the `PreciseNumber` precision, keeping the fractional
+    /// part that the integer Newton iteration would otherwise truncate.
+    #[inline]
+    fn normalized_value(
+        &self,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+    ) -> Option<PreciseNumber> {
+        let leverage = compute_a(self.amp)?;
+        let d = compute_d(
+            leverage,
+            swap_token_a_amount.checked_mul(precise_number::ONE)?,
+            swap_token_b_amount.checked_mul(precise_number::ONE)?,
+        )?;
+        PreciseNumber {
+            value: U256::from(d),
+        }
+        .checked_div(&PreciseNumber::new(2)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::curves::{
+            tests::{
+                check_curve_value_from_swap, check_deposit_single_exact_out,
+                check_deposit_token_conversion, check_pool_value_from_deposit,
+                check_pool_value_from_withdraw, check_swap_exact_out,
+                check_withdraw_single_exact_in, check_withdraw_token_conversion,
+                total_and_intermediate, CONVERSION_BASIS_POINTS_GUARANTEE,
+                EXACT_OUT_BASIS_POINTS_GUARANTEE,
+            },
+            ConstantProductCurve, INITIAL_SWAP_POOL_AMOUNT,
+        },
+        proptest::{prelude::*, prop_compose},
+    };
+
+    /// Both the invariant D and the new destination amount are rounded in the
+    /// pool's favor, so a swap can add up to 2 normalized tokens of value.
+    const STABLE_SWAP_VALUE_EPSILON: u128 = 2;
+
+    prop_compose! {
+        /// The stable curve is meant for pegged pairs, so the token amounts
+        /// are kept within 2x of each other.
+        fn pegged_pool(max_value: u64)(token_a_amount in 1_000_000..max_value)
+                        (ratio_in_percent in 50..200u128, token_a_amount in Just(token_a_amount))
+                        -> (u128, u128) {
+            let token_a_amount = token_a_amount as u128;
+            let token_b_amount = std::cmp::max(1_000_000, token_a_amount * ratio_in_percent / 100);
+            (token_a_amount, std::cmp::min(token_b_amount, max_value as u128))
+        }
+    }
+
+    #[test]
+    fn initial_pool_amount() {
+        let amp = 1;
+        let calculator = StableCurve { amp };
+        assert_eq!(calculator.new_pool_supply(), INITIAL_SWAP_POOL_AMOUNT);
+    }
+
+    #[test]
+    fn validate_amp() {
+        assert!(StableCurve { amp: 0 }.validate().is_err());
+        assert!(StableCurve { amp: MIN_AMP }.validate().is_ok());
+        assert!(StableCurve { amp: MAX_AMP }.validate().is_ok());
+        assert!(StableCurve { amp: MAX_AMP + 1 }.validate().is_err());
+    }
+
+    #[test]
+    fn swap_near_peg_has_less_slippage_than_constant_product() {
+        let stable = StableCurve { amp: 100 };
+        let source_amount = 100_000;
+        let swap_source_amount = 1_000_000;
+        let swap_destination_amount = 1_000_000;
+        let stable_result = stable
+            .swap_without_fees(
+                source_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::AtoB,
+            )
+            .unwrap();
+        let constant_product_result = ConstantProductCurve
+            .swap_without_fees(
+                source_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::AtoB,
+            )
+            .unwrap();
+        assert!(
+            stable_result.destination_amount_swapped
+                > constant_product_result.destination_amount_swapped
+        );
+        assert!(stable_result.destination_amount_swapped <= source_amount);
+    }
+
+    proptest! {
+        #[test]
+        fn deposit_token_conversion(
+            (swap_source_amount, swap_destination_amount) in pegged_pool(u32::MAX as u64),
+            source_token_ratio_in_percent in 1..3u128,
+            pool_supply in INITIAL_SWAP_POOL_AMOUNT..u64::MAX as u128,
+            amp in 1..100u64,
+        ) {
+            // in the pool token conversion calcs, we simulate trading half of
+            // source_token_amount, so this needs to be at least 2
+            let source_token_amount =
+                std::cmp::max(2, swap_source_amount * source_token_ratio_in_percent / 100);
+            let curve = StableCurve { amp };
+            check_deposit_token_conversion(
+                &curve,
+                source_token_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::AtoB,
+                pool_supply,
+                CONVERSION_BASIS_POINTS_GUARANTEE,
+            );
+            check_deposit_token_conversion(
+                &curve,
+                source_token_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::BtoA,
+                pool_supply,
+                CONVERSION_BASIS_POINTS_GUARANTEE,
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn withdraw_token_conversion(
+            (pool_token_supply, pool_token_amount) in total_and_intermediate(u32::MAX as u64),
+            (swap_token_a_amount, swap_token_b_amount) in pegged_pool(u32::MAX as u64),
+            amp in 1..100u64,
+        ) {
+            let pool_token_amount = pool_token_amount as u128;
+            // Keep the pool pegged after the withdrawal and swap by
+            // withdrawing at most half of the supply
+            let pool_token_supply = pool_token_supply as u128 * 2;
+            // Make sure we will get at least one trading token out for each
+            // side, otherwise the calculation fails
+            prop_assume!(pool_token_amount * swap_token_a_amount / pool_token_supply >= 1);
+            prop_assume!(pool_token_amount * swap_token_b_amount / pool_token_supply >= 1);
+            let curve = StableCurve { amp };
+            check_withdraw_token_conversion(
+                &curve,
+                pool_token_amount,
+                pool_token_supply,
+                swap_token_a_amount,
+                swap_token_b_amount,
+                TradeDirection::AtoB,
+                CONVERSION_BASIS_POINTS_GUARANTEE
+            );
+            check_withdraw_token_conversion(
+                &curve,
+                pool_token_amount,
+                pool_token_supply,
+                swap_token_a_amount,
+                swap_token_b_amount,
+                TradeDirection::BtoA,
+                CONVERSION_BASIS_POINTS_GUARANTEE
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn deposit_single_exact_out_covers_pool_tokens(
+            (pool_token_supply, pool_token_amount) in total_and_intermediate(u32::MAX as u64),
+            (swap_token_a_amount, swap_token_b_amount) in pegged_pool(u32::MAX as u64),
+            amp in 1..100u64,
+        ) {
+            // Keep the pool pegged after the deposit by depositing at most
+            // half of the supply
+            let pool_token_supply = pool_token_supply as u128 * 2;
+            let curve = StableCurve { amp };
+            check_deposit_single_exact_out(
+                &curve,
+                pool_token_amount as u128,
+                pool_token_supply,
+                swap_token_a_amount,
+                swap_token_b_amount,
+                TradeDirection::AtoB,
+                EXACT_OUT_BASIS_POINTS_GUARANTEE,
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn withdraw_single_exact_in_covers_pool_tokens(
+            (pool_token_supply, pool_token_amount) in total_and_intermediate(u32::MAX as u64),
+            (swap_token_a_amount, swap_token_b_amount) in pegged_pool(u32::MAX as u64),
+            amp in 1..100u64,
+        ) {
+            // Keep the pool pegged after the withdrawal by withdrawing at
+            // most half of the supply
+            let pool_token_supply = pool_token_supply as u128 * 2;
+            let curve = StableCurve { amp };
+            check_withdraw_single_exact_in(
+                &curve,
+                pool_token_amount as u128,
+                pool_token_supply,
+                swap_token_a_amount,
+                swap_token_b_amount,
+                TradeDirection::AtoB,
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn swap_exact_out_covers_destination(
+            (swap_source_amount, swap_destination_amount) in pegged_pool(u64::MAX),
+            destination_token_ratio_in_percent in 1..50u128,
+            amp in 1..100u64,
+        ) {
+            let destination_token_amount =
+                std::cmp::max(1, swap_destination_amount * destination_token_ratio_in_percent / 100);
+            let curve = StableCurve { amp };
+            check_swap_exact_out(
+                &curve,
+                destination_token_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::AtoB,
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn curve_value_does_not_decrease_from_swap(
+            (swap_source_amount, swap_destination_amount) in pegged_pool(u64::MAX),
+            source_token_ratio_in_percent in 1..50u128,
+            amp in 1..100u64,
+        ) {
+            let source_token_amount =
+                std::cmp::max(1, swap_source_amount * source_token_ratio_in_percent / 100);
+            let curve = StableCurve { amp };
+            // Make sure the swap yields at least one destination token,
+            // otherwise the calculation fails
+            prop_assume!(curve
+                .swap_without_fees(
+                    source_token_amount,
+                    swap_source_amount,
+                    swap_destination_amount,
+                    TradeDirection::AtoB,
+                )
+                .is_some());
+            check_curve_value_from_swap(
+                &curve,
+                source_token_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::AtoB,
+                STABLE_SWAP_VALUE_EPSILON
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn curve_value_does_not_decrease_from_deposit(
+            pool_token_amount in 1..u64::MAX,
+            pool_token_supply in 1..u64::MAX,
+            swap_token_a_amount in 1..u64::MAX,
+            swap_token_b_amount in 1..u64::MAX,
+            amp in 1..100u64,
+        ) {
+            let pool_token_amount = pool_token_amount as u128;
+            let pool_token_supply = pool_token_supply as u128;
+            let swap_token_a_amount = swap_token_a_amount as u128;
+            let swap_token_b_amount = swap_token_b_amount as u128;
+            // Make sure we will get at least one trading token out for each
+            // side, otherwise the calculation fails
+            prop_assume!(pool_token_amount * swap_token_a_amount / pool_token_supply >= 1);
+            prop_assume!(pool_token_amount * swap_token_b_amount / pool_token_supply >= 1);
+            let curve = StableCurve { amp };
+            check_pool_value_from_deposit(
+                &curve,
+                pool_token_amount,
+                pool_token_supply,
+                swap_token_a_amount,
+                swap_token_b_amount,
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn curve_value_does_not_decrease_from_withdraw(
+            (pool_token_supply, pool_token_amount) in total_and_intermediate(u64::MAX),
+            swap_token_a_amount in 1..u64::MAX,
+            swap_token_b_amount in 1..u64::MAX,
+            amp in 1..100u64,
+        ) {
+            let pool_token_amount = pool_token_amount as u128;
+            let pool_token_supply = pool_token_supply as u128;
+            let swap_token_a_amount = swap_token_a_amount as u128;
+            let swap_token_b_amount = swap_token_b_amount as u128;
+            // Make sure we will get at least one trading token out for each
+            // side, otherwise the calculation fails
+            prop_assume!(pool_token_amount * swap_token_a_amount / pool_token_supply >= 1);
+            prop_assume!(pool_token_amount * swap_token_b_amount / pool_token_supply >= 1);
+            let curve = StableCurve { amp };
+            check_pool_value_from_withdraw(
+                &curve,
+                pool_token_amount,
+                pool_token_supply,
+                swap_token_a_amount,
+                swap_token_b_amount,
+            );
+        }
+    }
+}
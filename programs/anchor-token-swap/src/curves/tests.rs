@@ -8,6 +8,14 @@ use {
 /// comparing a one-sided deposit to a swap + deposit.
 pub const CONVERSION_BASIS_POINTS_GUARANTEE: u128 = 50;
 
+/// The epsilon for the exact output calculations, comparing them to the
+/// regular calculation they invert.
+pub const EXACT_OUT_BASIS_POINTS_GUARANTEE: u128 = 1;
+
+/// The maximum increase in normalized value from a swap for most curves,
+/// in normalized tokens. Extremely close!
+pub const SWAP_VALUE_EPSILON: u128 = 1;
+
 /// Test function to check that depositing token A is the same as swapping
 /// half for token B and depositing both.
 /// Since calculations use unsigned integers, there will be truncation at
@@ -87,11 +95,7 @@ pub fn check_deposit_token_conversion(
         1,
         pool_tokens_total_separate * epsilon_in_basis_points / 10000,
     );
-    let difference = if pool_tokens_from_one_side >= pool_tokens_total_separate {
-        pool_tokens_from_one_side - pool_tokens_total_separate
-    } else {
-        pool_tokens_total_separate - pool_tokens_from_one_side
-    };
+    let difference = pool_tokens_from_one_side.abs_diff(pool_tokens_total_separate);
     assert!(
         difference <= epsilon,
         "difference expected to be less than {}, actually {}",
@@ -172,11 +176,7 @@ pub fn check_withdraw_token_conversion(
 
     // slippage due to rounding or truncation errors
     let epsilon = std::cmp::max(1, pool_token_amount * epsilon_in_basis_points / 10000);
-    let difference = if pool_token_amount >= pool_token_amount_from_single_side_withdraw {
-        pool_token_amount - pool_token_amount_from_single_side_withdraw
-    } else {
-        pool_token_amount_from_single_side_withdraw - pool_token_amount
-    };
+    let difference = pool_token_amount.abs_diff(pool_token_amount_from_single_side_withdraw);
     assert!(
         difference <= epsilon,
         "difference expected to be less than {}, actually {}",
@@ -291,13 +291,15 @@ pub fn check_pool_value_from_withdraw(
 /// either direction if too much is given to the swapper.
 ///
 /// This test guarantees that the relative change in value will be at most
-/// 1 normalized token, and that the value will never decrease from a trade.
+/// some epsilon of normalized tokens provided by the curve, and that the value
+/// will never decrease from a trade.
 pub fn check_curve_value_from_swap(
     curve: &dyn CurveCalculator,
     source_token_amount: u128,
     swap_source_amount: u128,
     swap_destination_amount: u128,
     trade_direction: TradeDirection,
+    epsilon: u128,
 ) {
     let results = curve
         .swap_without_fees(
@@ -332,7 +334,6 @@ pub fn check_curve_value_from_swap(
         .unwrap();
     assert!(new_value.greater_than_or_equal(&previous_value));
 
-    let epsilon = 1; // Extremely close!
     let difference = new_value
         .checked_sub(&previous_value)
         .unwrap()
@@ -341,6 +342,129 @@ pub fn check_curve_value_from_swap(
     assert!(difference <= epsilon);
 }
 
+/// Test function checking that the inverse swap calculation always asks for
+/// enough source token to provide the destination amount through a regular
+/// swap.
+///
+/// Since the source amount is rounded up, the pool can only gain value from
+/// an exact output swap.
+pub fn check_swap_exact_out(
+    curve: &dyn CurveCalculator,
+    destination_token_amount: u128,
+    swap_source_amount: u128,
+    swap_destination_amount: u128,
+    trade_direction: TradeDirection,
+) {
+    let results = curve
+        .swap_without_fees_exact_out(
+            destination_token_amount,
+            swap_source_amount,
+            swap_destination_amount,
+            trade_direction,
+        )
+        .unwrap();
+    assert_eq!(results.destination_amount_swapped, destination_token_amount);
+
+    let forward_results = curve
+        .swap_without_fees(
+            results.source_amount_swapped,
+            swap_source_amount,
+            swap_destination_amount,
+            trade_direction,
+        )
+        .unwrap();
+    assert!(forward_results.destination_amount_swapped >= destination_token_amount);
+}
+
+/// Test function checking that the trading tokens withdrawn for an exact
+/// amount of pool tokens never cost more than those pool tokens through
+/// the exact output withdrawal.
+///
+/// Since the trading token amount is rounded down, the pool can only gain
+/// value from an exact input withdrawal.
+pub fn check_withdraw_single_exact_in(
+    curve: &dyn CurveCalculator,
+    pool_token_amount: u128,
+    pool_token_supply: u128,
+    swap_token_a_amount: u128,
+    swap_token_b_amount: u128,
+    trade_direction: TradeDirection,
+) {
+    let trading_token_amount = curve
+        .withdraw_single_token_type_exact_in(
+            pool_token_amount,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            pool_token_supply,
+            trade_direction,
+        )
+        .unwrap();
+    if trading_token_amount == 0 {
+        return;
+    }
+    let pool_token_cost = curve
+        .withdraw_single_token_type_exact_out(
+            trading_token_amount,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            pool_token_supply,
+            trade_direction,
+            RoundDirection::Ceiling,
+        )
+        .unwrap();
+    assert!(
+        pool_token_cost <= pool_token_amount,
+        "withdrawing {} costs {} pool tokens, more than {}",
+        trading_token_amount,
+        pool_token_cost,
+        pool_token_amount
+    );
+}
+
+/// Test function checking that the trading tokens deposited for an exact
+/// amount of pool tokens provide those pool tokens through the regular
+/// single-sided deposit.
+///
+/// The trading token amount is rounded up, but the regular deposit goes
+/// through a square root or a fractional power of limited precision, so it
+/// may fall short by the given epsilon, with a minimum of 1 pool token.
+pub fn check_deposit_single_exact_out(
+    curve: &dyn CurveCalculator,
+    pool_token_amount: u128,
+    pool_token_supply: u128,
+    swap_token_a_amount: u128,
+    swap_token_b_amount: u128,
+    trade_direction: TradeDirection,
+    epsilon_in_basis_points: u128,
+) {
+    let trading_token_amount = curve
+        .deposit_single_token_type_exact_out(
+            pool_token_amount,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            pool_token_supply,
+            trade_direction,
+        )
+        .unwrap();
+    let pool_tokens_minted = curve
+        .deposit_single_token_type(
+            trading_token_amount,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            pool_token_supply,
+            trade_direction,
+        )
+        .unwrap();
+    let epsilon = std::cmp::max(1, pool_token_amount * epsilon_in_basis_points / 10000);
+    assert!(
+        pool_tokens_minted + epsilon >= pool_token_amount,
+        "depositing {} mints {} pool tokens, less than {}",
+        trading_token_amount,
+        pool_tokens_minted,
+        pool_token_amount
+    );
+}
+
 prop_compose! {
     pub fn total_and_intermediate(max_value: u64)(total in 1..max_value)
                     (intermediate in 1..total, total in Just(total))
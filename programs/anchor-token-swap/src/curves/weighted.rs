@@ -0,0 +1,901 @@
+use {
+    super::{
+        map_zero_to_none, pool_tokens_to_trading_tokens, CurveCalculator, CurveCalculatorTrait,
+        RoundDirection, SwapWithoutFeesResult, TradeDirection, TradingTokenResult,
+    },
+    crate::SwapError,
+    anchor_lang::prelude::*,
+    spl_math::{precise_number::PreciseNumber, uint::U256},
+};
+
+/// Weights are expressed in percent, so `weight_a + weight_b` must add up to
+/// this value
+pub const WEIGHT_DENOMINATOR: u64 = 100;
+/// Smallest weight allowed on either side, as in Balancer
+pub const MIN_WEIGHT: u64 = 2;
+/// Number of bits used to approximate the fractional part of an exponent
+const FRACTION_BITS: u8 = 40;
+/// Scale of the fixed-point numbers used for fractional powers, 10^24.
+/// Repeated square roots quickly tend towards 1, where the 12 decimals of a
+/// precise number leave too few significant digits.
+const POW_SCALE: u128 = 1_000_000_000_000_000_000_000_000;
+/// Inverse of the relative precision of the normalized value
+const NORMALIZED_VALUE_PRECISION: u128 = 10_000_000_000;
+
+/// WeightedCurve struct implementing CurveCalculator, a Balancer-style curve
+/// with invariant `token_a ^ weight_a * token_b ^ weight_b`
+#[derive(Clone, Default, PartialEq, Copy)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct WeightedCurve {
+    /// Weight of token A, in percent
+    pub weight_a: u64,
+    /// Weight of token B, in percent
+    pub weight_b: u64,
+}
+
+impl WeightedCurve {
+    /// Weights of the (source, destination) sides for the given direction
+    #[inline]
+    fn weights(&self, trade_direction: TradeDirection) -> (u128, u128) {
+        match trade_direction {
+            TradeDirection::AtoB => (self.weight_a as u128, self.weight_b as u128),
+            TradeDirection::BtoA => (self.weight_b as u128, self.weight_a as u128),
+        }
+    }
+}
+
+/// Divide, rounding in the given direction
+fn checked_div(
+    numerator: U256,
+    denominator: U256,
+    round_direction: &RoundDirection,
+) -> Option<U256> {
+    let quotient = numerator.checked_div(denominator)?;
+    match round_direction {
+        RoundDirection::Floor => Some(quotient),
+        RoundDirection::Ceiling if numerator.checked_rem(denominator)?.is_zero() => Some(quotient),
+        RoundDirection::Ceiling => quotient.checked_add(U256::one()),
+    }
+}
+
+/// Multiply two fixed-point numbers, rounding in the given direction
+fn checked_mul(left: U256, right: U256, round_direction: &RoundDirection) -> Option<U256> {
+    checked_div(
+        left.checked_mul(right)?,
+        U256::from(POW_SCALE),
+        round_direction,
+    )
+}
+
+/// Square root of a fixed-point number, rounding in the given direction
+fn checked_sqrt(value: U256, round_direction: &RoundDirection) -> Option<U256> {
+    let square = value.checked_mul(U256::from(POW_SCALE))?;
+    let root = square.integer_sqrt();
+    match round_direction {
+        RoundDirection::Ceiling if root.checked_mul(root)? < square => {
+            root.checked_add(U256::one())
+        }
+        _ => Some(root),
+    }
+}
+
+/// Fixed-point ratio of two amounts, rounding in the given direction
+fn checked_ratio(
+    numerator: u128,
+    denominator: u128,
+    round_direction: &RoundDirection,
+) -> Option<U256> {
+    checked_div(
+        U256::from(numerator).checked_mul(U256::from(POW_SCALE))?,
+        U256::from(denominator),
+        round_direction,
+    )
+}
+
+/// Amount times a fixed-point number, rounding in the given direction
+fn checked_scale(amount: u128, value: U256, round_direction: &RoundDirection) -> Option<u128> {
+    let amount = checked_div(
+        U256::from(amount).checked_mul(value)?,
+        U256::from(POW_SCALE),
+        round_direction,
+    )?;
+    u128::try_from(amount).ok()
+}
+
+/// Raise a fixed-point number to a fractional power, `base ^ (numerator /
+/// denominator)`, rounding in the given direction.
+///
+/// The whole part of the exponent goes through repeated squaring, and the
+/// fractional part is built bit by bit from repeated square roots, ie.
+/// `base ^ (1 / 2 ^ k)`. The bits past `FRACTION_BITS` are bounded by a
+/// linear approximation of the last root. Every operation is rounded in the
+/// given direction, so the result is a bound of the exact power.
+fn checked_pow_fraction(
+    base: U256,
+    numerator: u128,
+    denominator: u128,
+    round_direction: &RoundDirection,
+) -> Option<U256> {
+    let one = U256::from(POW_SCALE);
+    let mut whole = numerator.checked_div(denominator)?;
+    let mut remainder = numerator.checked_rem(denominator)?;
+    let mut result = one;
+    let mut square = base;
+    while whole > 0 {
+        if whole % 2 == 1 {
+            result = checked_mul(result, square, round_direction)?;
+        }
+        whole /= 2;
+        if whole > 0 {
+            square = checked_mul(square, square, round_direction)?;
+        }
+    }
+    let mut root = base;
+    for _ in 0..FRACTION_BITS {
+        if remainder == 0 {
+            break;
+        }
+        root = checked_sqrt(root, round_direction)?;
+        remainder = remainder.checked_mul(2)?;
+        if remainder >= denominator {
+            remainder = remainder.checked_sub(denominator)?;
+            result = checked_mul(result, root, round_direction)?;
+        }
+    }
+    if remainder > 0 {
+        // the rest of the exponent is `t = remainder / denominator` of the
+        // last root: `root ^ t` lies below the chord `1 + t * (root - 1)` and
+        // above the tangent `1 + t * ln(root)`, where `ln(root) >= 1 - 1 / root`
+        let (difference, root_above_one) = if root >= one {
+            (root.checked_sub(one)?, true)
+        } else {
+            (one.checked_sub(root)?, false)
+        };
+        let difference = difference.checked_mul(U256::from(remainder))?;
+        let factor = match (round_direction, root_above_one) {
+            (RoundDirection::Ceiling, true) => one.checked_add(checked_div(
+                difference,
+                U256::from(denominator),
+                &RoundDirection::Ceiling,
+            )?)?,
+            (RoundDirection::Ceiling, false) => one.checked_sub(checked_div(
+                difference,
+                U256::from(denominator),
+                &RoundDirection::Floor,
+            )?)?,
+            (RoundDirection::Floor, true) => one.checked_add(checked_div(
+                difference.checked_mul(one)?,
+                U256::from(denominator).checked_mul(root)?,
+                &RoundDirection::Floor,
+            )?)?,
+            (RoundDirection::Floor, false) => one.checked_sub(checked_div(
+                difference.checked_mul(one)?,
+                U256::from(denominator).checked_mul(root)?,
+                &RoundDirection::Ceiling,
+            )?)?,
+        };
+        result = checked_mul(result, factor, round_direction)?;
+    }
+    Some(result)
+}
+
+impl CurveCalculator for WeightedCurve {}
+impl CurveCalculatorTrait for WeightedCurve {
+    /// Balancer "out given in":
+    ///
+    ///    `amount_out = swap_destination * (1 - (swap_source / (swap_source +
+    /// amount_in)) ^ (weight_source / weight_destination))`
+    ///
+    /// The power is rounded up, so the amount out never exceeds the exact one.
+    fn swap_without_fees(
+        &self,
+        source_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<SwapWithoutFeesResult> {
+        let (weight_source, weight_destination) = self.weights(trade_direction);
+        let new_swap_source_amount = swap_source_amount.checked_add(source_amount)?;
+        let ratio = checked_ratio(
+            swap_source_amount,
+            new_swap_source_amount,
+            &RoundDirection::Ceiling,
+        )?;
+        let power = checked_pow_fraction(
+            ratio,
+            weight_source,
+            weight_destination,
+            &RoundDirection::Ceiling,
+        )?;
+        // the real new amount is always positive, so never drain the pool
+        let new_swap_destination_amount = std::cmp::max(
+            1,
+            checked_scale(swap_destination_amount, power, &RoundDirection::Ceiling)?,
+        );
+        let destination_amount_swapped =
+            map_zero_to_none(swap_destination_amount.checked_sub(new_swap_destination_amount)?)?;
+        Some(SwapWithoutFeesResult {
+            source_amount_swapped: source_amount,
+            destination_amount_swapped,
+        })
+    }
+
+    /// Balancer "in given out":
+    ///
+    ///    `amount_in = swap_source * ((swap_destination / (swap_destination -
+    /// amount_out)) ^ (weight_destination / weight_source) - 1)`
+    ///
+    /// The power is rounded up, and one more token is asked for since
+    /// `swap_without_fees` rounds the new destination amount up.
+    fn swap_without_fees_exact_out(
+        &self,
+        destination_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<SwapWithoutFeesResult> {
+        let (weight_source, weight_destination) = self.weights(trade_direction);
+        let new_swap_destination_amount =
+            map_zero_to_none(swap_destination_amount.checked_sub(destination_amount)?)?;
+        let ratio = checked_ratio(
+            swap_destination_amount,
+            new_swap_destination_amount,
+            &RoundDirection::Ceiling,
+        )?;
+        let power = checked_pow_fraction(
+            ratio,
+            weight_destination,
+            weight_source,
+            &RoundDirection::Ceiling,
+        )?;
+        let new_swap_source_amount =
+            checked_scale(swap_source_amount, power, &RoundDirection::Ceiling)?.checked_add(1)?;
+        let source_amount_swapped = new_swap_source_amount.checked_sub(swap_source_amount)?;
+        let destination_amount_swapped = map_zero_to_none(destination_amount)?;
+        Some(SwapWithoutFeesResult {
+            source_amount_swapped,
+            destination_amount_swapped,
+        })
+    }
+
+    /// Withdrawing all token types is a simple ratio calculation, exactly like
+    /// the constant product curve, since it does not move the spot price
+    #[inline]
+    fn pool_tokens_to_trading_tokens(
+        &self,
+        pool_tokens: u128,
+        pool_token_supply: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        round_direction: RoundDirection,
+    ) -> Option<TradingTokenResult> {
+        pool_tokens_to_trading_tokens(
+            pool_tokens,
+            pool_token_supply,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            round_direction,
+        )
+    }
+
+    /// Balancer single-asset deposit, "pool out given single in":
+    ///
+    ///    `pool_tokens = pool_supply * ((1 + source_amount / swap_source) ^
+    /// weight_source - 1)`
+    fn deposit_single_token_type(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        let (weight_source, _) = self.weights(trade_direction);
+        let swap_source_amount = match trade_direction {
+            TradeDirection::AtoB => swap_token_a_amount,
+            TradeDirection::BtoA => swap_token_b_amount,
+        };
+        let base = checked_ratio(
+            swap_source_amount.checked_add(source_amount)?,
+            swap_source_amount,
+            &RoundDirection::Floor,
+        )?;
+        let root = checked_pow_fraction(
+            base,
+            weight_source,
+            WEIGHT_DENOMINATOR as u128,
+            &RoundDirection::Floor,
+        )?
+        .checked_sub(U256::from(POW_SCALE))?;
+        checked_scale(pool_supply, root, &RoundDirection::Floor)
+    }
+
+    /// Balancer single-asset deposit, "single in given pool out":
+    ///
+    ///    `source_amount = swap_source * ((1 + pool_tokens / pool_supply) ^ (1
+    /// / weight_source) - 1)`
+    fn deposit_single_token_type_exact_out(
+        &self,
+        pool_tokens: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        let (weight_source, _) = self.weights(trade_direction);
+        let swap_source_amount = match trade_direction {
+            TradeDirection::AtoB => swap_token_a_amount,
+            TradeDirection::BtoA => swap_token_b_amount,
+        };
+        let base = checked_ratio(
+            pool_supply.checked_add(pool_tokens)?,
+            pool_supply,
+            &RoundDirection::Ceiling,
+        )?;
+        let root = checked_pow_fraction(
+            base,
+            WEIGHT_DENOMINATOR as u128,
+            weight_source,
+            &RoundDirection::Ceiling,
+        )?
+        .checked_sub(U256::from(POW_SCALE))?;
+        // a light side can need more than fits in a u128
+        checked_scale(swap_source_amount, root, &RoundDirection::Ceiling)
+    }
+
+    /// Balancer single-asset withdrawal, "pool in given single out":
+    ///
+    ///    `pool_tokens = pool_supply * (1 - (1 - source_amount /
+    /// swap_source) ^ weight_source)`
+    fn withdraw_single_token_type_exact_out(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+        round_direction: RoundDirection,
+    ) -> Option<u128> {
+        let (weight_source, _) = self.weights(trade_direction);
+        let swap_source_amount = match trade_direction {
+            TradeDirection::AtoB => swap_token_a_amount,
+            TradeDirection::BtoA => swap_token_b_amount,
+        };
+        // the power is subtracted, so round it the other way
+        let power_round_direction = match round_direction {
+            RoundDirection::Floor => RoundDirection::Ceiling,
+            RoundDirection::Ceiling => RoundDirection::Floor,
+        };
+        let base = checked_ratio(
+            swap_source_amount.checked_sub(source_amount)?,
+            swap_source_amount,
+            &power_round_direction,
+        )?;
+        let root = U256::from(POW_SCALE).checked_sub(checked_pow_fraction(
+            base,
+            weight_source,
+            WEIGHT_DENOMINATOR as u128,
+            &power_round_direction,
+        )?)?;
+        checked_scale(pool_supply, root, &round_direction)
+    }
+
+    /// Balancer single-asset withdrawal, "single out given pool in":
+    ///
+    ///    `source_amount = swap_source * (1 - (1 - pool_tokens / pool_supply)
+    /// ^ (1 / weight_source))`
+    ///
+    /// One more token is held back, since `withdraw_single_token_type_exact_out`
+    /// rounds the pool tokens for it up.
+    fn withdraw_single_token_type_exact_in(
+        &self,
+        pool_tokens: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        let (weight_source, _) = self.weights(trade_direction);
+        let swap_source_amount = match trade_direction {
+            TradeDirection::AtoB => swap_token_a_amount,
+            TradeDirection::BtoA => swap_token_b_amount,
+        };
+        let base = checked_ratio(
+            pool_supply.checked_sub(pool_tokens)?,
+            pool_supply,
+            &RoundDirection::Ceiling,
+        )?;
+        let root = U256::from(POW_SCALE).checked_sub(checked_pow_fraction(
+            base,
+            WEIGHT_DENOMINATOR as u128,
+            weight_source,
+            &RoundDirection::Ceiling,
+        )?)?;
+        checked_scale(swap_source_amount, root, &RoundDirection::Floor)
+            .map(|amount| amount.saturating_sub(1))
+    }
+
+    #[inline]
+    fn validate(&self) -> Result<()> {
+        if self.weight_a < MIN_WEIGHT
+            || self.weight_b < MIN_WEIGHT
+            || self.weight_a.checked_add(self.weight_b) != Some(WEIGHT_DENOMINATOR)
+        {
+            err!(SwapError::InvalidCurve)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The normalized value of the weighted curve is the weighted geometric
+    /// mean of the token amounts, ie. the invariant
+    /// `token_a ^ weight_a * token_b ^ weight_b` with weights summing to 1.
+    /// With equal weights, this is the same as the constant product curve.
+    #[inline]
+    fn normalized_value(
+        &self,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+    ) -> Option<PreciseNumber> {
+        let value = |amount: u128, weight: u64| {
+            let power = checked_pow_fraction(
+                U256::from(amount).checked_mul(U256::from(POW_SCALE))?,
+                weight as u128,
+                WEIGHT_DENOMINATOR as u128,
+                &RoundDirection::Floor,
+            )?;
+            // back to the 12 decimals of a precise number, plenty for amounts
+            // raised to a positive power
+            Some(PreciseNumber {
+                value: power.checked_div(
+                    U256::from(POW_SCALE).checked_div(PreciseNumber::new(1)?.value)?,
+                )?,
+            })
+        };
+        let token_a_value = value(swap_token_a_amount, self.weight_a)?;
+        let token_b_value = value(swap_token_b_amount, self.weight_b)?;
+        token_a_value.checked_mul(&token_b_value)
+    }
+
+    /// Each side of `normalized_value` is at least 1 and truncated to 12
+    /// decimals, before their product is rounded to 12 decimals as well,
+    /// leaving a relative error well below 10^-10
+    fn normalized_value_precision(&self) -> Option<PreciseNumber> {
+        PreciseNumber::new(1)?.checked_div(&PreciseNumber::new(NORMALIZED_VALUE_PRECISION)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::curves::{
+            tests::{
+                check_curve_value_from_swap, check_deposit_single_exact_out,
+                check_deposit_token_conversion, check_pool_value_from_deposit,
+                check_pool_value_from_withdraw, check_swap_exact_out,
+                check_withdraw_single_exact_in, check_withdraw_token_conversion,
+                total_and_intermediate, CONVERSION_BASIS_POINTS_GUARANTEE,
+                EXACT_OUT_BASIS_POINTS_GUARANTEE, SWAP_VALUE_EPSILON,
+            },
+            ConstantProductCurve, INITIAL_SWAP_POOL_AMOUNT,
+        },
+        proptest::prelude::*,
+    };
+
+    #[test]
+    fn initial_pool_amount() {
+        let calculator = WeightedCurve {
+            weight_a: 80,
+            weight_b: 20,
+        };
+        assert_eq!(calculator.new_pool_supply(), INITIAL_SWAP_POOL_AMOUNT);
+    }
+
+    #[test]
+    fn validate_weights() {
+        let check = |weight_a, weight_b| WeightedCurve { weight_a, weight_b }.validate();
+        assert!(check(80, 20).is_ok());
+        assert!(check(50, 50).is_ok());
+        assert!(check(MIN_WEIGHT, WEIGHT_DENOMINATOR - MIN_WEIGHT).is_ok());
+        assert!(check(0, 100).is_err());
+        assert!(check(1, 99).is_err());
+        assert!(check(80, 30).is_err());
+        assert!(check(u64::MAX, 2).is_err());
+    }
+
+    #[test]
+    fn pow_fraction() {
+        let check = |base: u128, numerator: u128, denominator: u128, expected: u128| {
+            let base = U256::from(base) * U256::from(POW_SCALE);
+            let expected = U256::from(expected) * U256::from(POW_SCALE);
+            let floor =
+                checked_pow_fraction(base, numerator, denominator, &RoundDirection::Floor).unwrap();
+            let ceiling =
+                checked_pow_fraction(base, numerator, denominator, &RoundDirection::Ceiling)
+                    .unwrap();
+            assert!(floor <= expected);
+            assert!(ceiling >= expected);
+            assert!(ceiling - floor <= expected / U256::from(10_000_000_000u64));
+        };
+        check(16, 1, 2, 4);
+        check(16, 1, 4, 2);
+        check(16, 3, 4, 8);
+        check(8, 5, 3, 32);
+        check(1_000_000, 1, 3, 100);
+        check(7, 0, 3, 1);
+        check(3, 5, 1, 243);
+    }
+
+    #[test]
+    fn swap_rounds_in_pool_favor() {
+        let curve = WeightedCurve {
+            weight_a: 69,
+            weight_b: 31,
+        };
+        // the exact amount out is 65494904.97...
+        let result = curve
+            .swap_without_fees(
+                10_370_078,
+                1_185_666_393,
+                3_411_890_147,
+                TradeDirection::AtoB,
+            )
+            .unwrap();
+        assert_eq!(result.destination_amount_swapped, 65_494_904);
+    }
+
+    #[test]
+    fn equal_weights_match_constant_product() {
+        let curve = WeightedCurve {
+            weight_a: 50,
+            weight_b: 50,
+        };
+        let tests: &[(u128, u128, u128)] = &[
+            (10, 4_000_000, 70_000_000_000),
+            (1_000, 1_000_000, 1_000_000),
+            (100, 60_000, 30_000),
+            (12_345, 987_654, 123_456_789),
+        ];
+        for (source_amount, swap_source_amount, swap_destination_amount) in tests.iter() {
+            let weighted = curve
+                .swap_without_fees(
+                    *source_amount,
+                    *swap_source_amount,
+                    *swap_destination_amount,
+                    TradeDirection::AtoB,
+                )
+                .unwrap();
+            let constant_product = ConstantProductCurve
+                .swap_without_fees(
+                    *source_amount,
+                    *swap_source_amount,
+                    *swap_destination_amount,
+                    TradeDirection::AtoB,
+                )
+                .unwrap();
+            assert!(
+                weighted.destination_amount_swapped <= constant_product.destination_amount_swapped
+            );
+            assert!(
+                weighted.destination_amount_swapped + 1
+                    >= constant_product.destination_amount_swapped
+            );
+        }
+    }
+
+    #[test]
+    fn heavier_side_moves_less() {
+        let curve = WeightedCurve {
+            weight_a: 80,
+            weight_b: 20,
+        };
+        // selling the heavy token A gives less B than the equal-weight curve,
+        // since the spot price is `(token_b / weight_b) / (token_a / weight_a)`
+        let result = curve
+            .swap_without_fees(1_000, 1_000_000, 1_000_000, TradeDirection::AtoB)
+            .unwrap();
+        assert!(result.destination_amount_swapped > 3_900);
+        assert!(result.destination_amount_swapped < 4_000);
+        let result = curve
+            .swap_without_fees(1_000, 1_000_000, 1_000_000, TradeDirection::BtoA)
+            .unwrap();
+        assert!(result.destination_amount_swapped > 240);
+        assert!(result.destination_amount_swapped < 250);
+    }
+
+    #[test]
+    fn deposit_exact_out_overflow() {
+        let curve = WeightedCurve {
+            weight_a: 2,
+            weight_b: 98,
+        };
+        // 11 ^ 50 times the reserve does not fit in a u128
+        assert_eq!(
+            curve.deposit_single_token_type_exact_out(
+                10_000_000_000,
+                1_000_000_000,
+                1_000_000_000,
+                1_000_000_000,
+                TradeDirection::AtoB,
+            ),
+            None
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn deposit_token_conversion(
+            swap_source_amount in 1_000_000..u32::MAX as u128,
+            swap_destination_amount in 1_000_000..u32::MAX as u128,
+            source_token_ratio_in_basis_points in 1..100u128,
+            pool_supply in INITIAL_SWAP_POOL_AMOUNT..u64::MAX as u128,
+            weight_a in MIN_WEIGHT..=WEIGHT_DENOMINATOR - MIN_WEIGHT,
+        ) {
+            // The conversion check simulates swapping half of the source
+            // amount, which is only the equivalent trade for equal weights, so
+            // keep the deposit small enough for the difference to vanish.
+            // It also needs to be at least 2.
+            let source_token_amount = std::cmp::max(
+                2,
+                swap_source_amount * source_token_ratio_in_basis_points / 10_000,
+            );
+            let curve = WeightedCurve { weight_a, weight_b: WEIGHT_DENOMINATOR - weight_a };
+            // The swap floors its destination amount, losing up to one
+            // destination token, worth `weight_destination /
+            // swap_destination_amount` of the pool. Swapping from a light side
+            // into a heavy one only gets a few tokens, so that token can be
+            // worth more than the guarantee.
+            let epsilon_in_basis_points = |weight_source: u64, weight_destination: u64| {
+                CONVERSION_BASIS_POINTS_GUARANTEE
+                    + 10_000 * weight_destination as u128 * swap_source_amount
+                        / (swap_destination_amount * weight_source as u128 * source_token_amount)
+                    + 1
+            };
+            check_deposit_token_conversion(
+                &curve,
+                source_token_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::AtoB,
+                pool_supply,
+                epsilon_in_basis_points(curve.weight_a, curve.weight_b),
+            );
+            check_deposit_token_conversion(
+                &curve,
+                source_token_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::BtoA,
+                pool_supply,
+                epsilon_in_basis_points(curve.weight_b, curve.weight_a),
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn withdraw_token_conversion(
+            (pool_token_supply, pool_token_amount) in total_and_intermediate(u32::MAX as u64),
+            swap_token_a_amount in 1_000_000..u32::MAX as u128,
+            swap_token_b_amount in 1_000_000..u32::MAX as u128,
+            weight_a in MIN_WEIGHT..=WEIGHT_DENOMINATOR - MIN_WEIGHT,
+        ) {
+            // As with deposits, the withdrawal must be small for the swap of
+            // one side to be equivalent, so withdraw at most 1% of the supply
+            let pool_token_amount = pool_token_amount as u128;
+            let pool_token_supply = pool_token_supply as u128 * 100;
+            // Make sure we will get at least one trading token out for each
+            // side, otherwise the calculation fails
+            prop_assume!(pool_token_amount * swap_token_a_amount / pool_token_supply >= 1);
+            prop_assume!(pool_token_amount * swap_token_b_amount / pool_token_supply >= 1);
+            let curve = WeightedCurve { weight_a, weight_b: WEIGHT_DENOMINATOR - weight_a };
+            check_withdraw_token_conversion(
+                &curve,
+                pool_token_amount,
+                pool_token_supply,
+                swap_token_a_amount,
+                swap_token_b_amount,
+                TradeDirection::AtoB,
+                CONVERSION_BASIS_POINTS_GUARANTEE
+            );
+            check_withdraw_token_conversion(
+                &curve,
+                pool_token_amount,
+                pool_token_supply,
+                swap_token_a_amount,
+                swap_token_b_amount,
+                TradeDirection::BtoA,
+                CONVERSION_BASIS_POINTS_GUARANTEE
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn deposit_single_exact_out_covers_pool_tokens(
+            (pool_token_supply, pool_token_amount) in total_and_intermediate(u32::MAX as u64),
+            swap_token_a_amount in 1..u32::MAX as u128,
+            swap_token_b_amount in 1..u32::MAX as u128,
+            weight_a in MIN_WEIGHT..WEIGHT_DENOMINATOR - MIN_WEIGHT + 1,
+        ) {
+            let curve = WeightedCurve {
+                weight_a,
+                weight_b: WEIGHT_DENOMINATOR - weight_a,
+            };
+            check_deposit_single_exact_out(
+                &curve,
+                pool_token_amount as u128,
+                pool_token_supply as u128,
+                swap_token_a_amount,
+                swap_token_b_amount,
+                TradeDirection::AtoB,
+                EXACT_OUT_BASIS_POINTS_GUARANTEE,
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn withdraw_single_exact_in_covers_pool_tokens(
+            (pool_token_supply, pool_token_amount) in total_and_intermediate(u32::MAX as u64),
+            swap_token_a_amount in 1..u32::MAX as u128,
+            swap_token_b_amount in 1..u32::MAX as u128,
+            weight_a in MIN_WEIGHT..WEIGHT_DENOMINATOR - MIN_WEIGHT + 1,
+        ) {
+            let curve = WeightedCurve {
+                weight_a,
+                weight_b: WEIGHT_DENOMINATOR - weight_a,
+            };
+            check_withdraw_single_exact_in(
+                &curve,
+                pool_token_amount as u128,
+                pool_token_supply as u128,
+                swap_token_a_amount,
+                swap_token_b_amount,
+                TradeDirection::AtoB,
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn swap_exact_out_covers_destination(
+            (swap_destination_amount, destination_token_amount) in total_and_intermediate(u32::MAX as u64),
+            swap_source_amount in 1..u32::MAX as u128,
+            weight_a in MIN_WEIGHT..WEIGHT_DENOMINATOR - MIN_WEIGHT + 1,
+        ) {
+            let curve = WeightedCurve {
+                weight_a,
+                weight_b: WEIGHT_DENOMINATOR - weight_a,
+            };
+            let destination_token_amount = destination_token_amount as u128;
+            let swap_destination_amount = swap_destination_amount as u128;
+            // Emptying most of the destination side can require more source
+            // token than fits in a precise number, or so much that the ratio
+            // used by the regular swap runs out of precision
+            let results = curve.swap_without_fees_exact_out(
+                destination_token_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::AtoB,
+            );
+            prop_assume!(results.is_some());
+            prop_assume!(results.unwrap().source_amount_swapped <= swap_source_amount * 1_000);
+            check_swap_exact_out(
+                &curve,
+                destination_token_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::AtoB,
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn curve_value_does_not_decrease_from_swap(
+            source_token_amount in 1..u32::MAX,
+            swap_source_amount in 1..u32::MAX,
+            swap_destination_amount in 1..u32::MAX,
+            weight_a in MIN_WEIGHT..=WEIGHT_DENOMINATOR - MIN_WEIGHT,
+        ) {
+            let curve = WeightedCurve { weight_a, weight_b: WEIGHT_DENOMINATOR - weight_a };
+            let source_token_amount = source_token_amount as u128;
+            let swap_source_amount = swap_source_amount as u128;
+            let swap_destination_amount = swap_destination_amount as u128;
+            // Make sure the swap yields at least one destination token,
+            // otherwise the calculation fails, and that the pool is not
+            // drained, where the value of a single token explodes
+            let results = curve.swap_without_fees(
+                source_token_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::AtoB,
+            );
+            prop_assume!(results.is_some());
+            let new_swap_destination_amount =
+                swap_destination_amount - results.unwrap().destination_amount_swapped;
+            prop_assume!(new_swap_destination_amount >= 1_000);
+            // Unlike the constant product curve, the whole source amount is
+            // always taken and the destination amount is floored, leaving up
+            // to one extra token B in the pool, worth about
+            // `weight_b * value / token_b` normalized tokens
+            let new_value = curve
+                .normalized_value(swap_source_amount + source_token_amount, new_swap_destination_amount)
+                .unwrap()
+                .to_imprecise()
+                .unwrap();
+            let epsilon = std::cmp::max(
+                SWAP_VALUE_EPSILON,
+                2 * new_value * curve.weight_b as u128
+                    / WEIGHT_DENOMINATOR as u128
+                    / new_swap_destination_amount
+                    + 1,
+            );
+            check_curve_value_from_swap(
+                &curve,
+                source_token_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::AtoB,
+                epsilon,
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn curve_value_does_not_decrease_from_deposit(
+            pool_token_amount in 1..u64::MAX,
+            pool_token_supply in 1..u64::MAX,
+            swap_token_a_amount in 1..u64::MAX,
+            swap_token_b_amount in 1..u64::MAX,
+            weight_a in MIN_WEIGHT..=WEIGHT_DENOMINATOR - MIN_WEIGHT,
+        ) {
+            let pool_token_amount = pool_token_amount as u128;
+            let pool_token_supply = pool_token_supply as u128;
+            let swap_token_a_amount = swap_token_a_amount as u128;
+            let swap_token_b_amount = swap_token_b_amount as u128;
+            // Make sure we will get at least one trading token out for each
+            // side, otherwise the calculation fails
+            prop_assume!(pool_token_amount * swap_token_a_amount / pool_token_supply >= 1);
+            prop_assume!(pool_token_amount * swap_token_b_amount / pool_token_supply >= 1);
+            let curve = WeightedCurve { weight_a, weight_b: WEIGHT_DENOMINATOR - weight_a };
+            check_pool_value_from_deposit(
+                &curve,
+                pool_token_amount,
+                pool_token_supply,
+                swap_token_a_amount,
+                swap_token_b_amount,
+            );
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn curve_value_does_not_decrease_from_withdraw(
+            (pool_token_supply, pool_token_amount) in total_and_intermediate(u32::MAX as u64),
+            swap_token_a_amount in 1..u32::MAX,
+            swap_token_b_amount in 1..u32::MAX,
+            weight_a in MIN_WEIGHT..=WEIGHT_DENOMINATOR - MIN_WEIGHT,
+        ) {
+            let pool_token_amount = pool_token_amount as u128;
+            let pool_token_supply = pool_token_supply as u128;
+            let swap_token_a_amount = swap_token_a_amount as u128;
+            let swap_token_b_amount = swap_token_b_amount as u128;
+            // Make sure we will get at least one trading token out for each
+            // side, otherwise the calculation fails
+            prop_assume!(pool_token_amount * swap_token_a_amount / pool_token_supply >= 1);
+            prop_assume!(pool_token_amount * swap_token_b_amount / pool_token_supply >= 1);
+            let curve = WeightedCurve { weight_a, weight_b: WEIGHT_DENOMINATOR - weight_a };
+            check_pool_value_from_withdraw(
+                &curve,
+                pool_token_amount,
+                pool_token_supply,
+                swap_token_a_amount,
+                swap_token_b_amount,
+            );
+        }
+    }
+}
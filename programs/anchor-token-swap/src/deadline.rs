@@ -0,0 +1,46 @@
+use {crate::SwapError, anchor_lang::prelude::*};
+
+/// Point in time after which a swap, deposit or withdrawal must not execute,
+/// so that a transaction landing late does not trade at a stale price
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub enum Deadline {
+    /// Last unix timestamp at which the instruction may execute
+    UnixTimestamp(i64),
+    /// Last slot at which the instruction may execute
+    Slot(u64),
+}
+
+impl Deadline {
+    pub fn is_expired(&self, clock: &Clock) -> bool {
+        match *self {
+            Deadline::UnixTimestamp(timestamp) => clock.unix_timestamp > timestamp,
+            Deadline::Slot(slot) => clock.slot > slot,
+        }
+    }
+}
+
+pub fn validate_deadline(deadline: &Option<Deadline>) -> Result<()> {
+    if let Some(deadline) = deadline {
+        require!(!deadline.is_expired(&Clock::get()?), SwapError::Expired);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deadline_expires_after_its_timestamp_or_slot() {
+        let clock = Clock {
+            slot: 100,
+            unix_timestamp: 1_000,
+            ..Default::default()
+        };
+        assert!(!Deadline::UnixTimestamp(1_000).is_expired(&clock));
+        assert!(Deadline::UnixTimestamp(999).is_expired(&clock));
+        assert!(!Deadline::Slot(100).is_expired(&clock));
+        assert!(Deadline::Slot(99).is_expired(&clock));
+    }
+}
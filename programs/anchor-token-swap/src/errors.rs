@@ -109,4 +109,64 @@ pub enum SwapError {
     /// The source and swap source accounts are the same
     #[msg("Cannot transfer between the same account")]
     SameAccountTransfer,
+
+    // 30.
+    /// The signer is not the admin of the swap
+    #[msg("The signer is not the admin of the swap")]
+    InvalidAdmin,
+    /// The operation is not allowed by the current status of the pool
+    #[msg("The operation is not allowed by the current status of the pool")]
+    OperationPaused,
+    /// The accounts of a routed swap do not form a valid route
+    #[msg("The accounts of a routed swap do not form a valid route")]
+    InvalidRoute,
+    /// A flash loan of the swap is outstanding
+    #[msg("A flash loan of the swap is outstanding")]
+    FlashLoanInProgress,
+    /// The flash loan is not repaid later in the same transaction
+    #[msg("The flash loan is not repaid later in the same transaction")]
+    FlashLoanNotRepaid,
+
+    // 35.
+    /// The swap has no outstanding flash loan to repay
+    #[msg("The swap has no outstanding flash loan to repay")]
+    NoFlashLoan,
+    /// The mint of token A must sort before the mint of token B
+    #[msg("The mint of token A must sort before the mint of token B")]
+    UnsortedMints,
+    /// The signer is not the upgrade authority of the program
+    #[msg("The signer is not the upgrade authority of the program")]
+    InvalidUpgradeAuthority,
+    /// The locked liquidity account is required when the pool supply is zero
+    #[msg("The locked liquidity account is required when the pool supply is zero")]
+    MissingLockedLiquidity,
+    /// The deadline of the transaction has passed
+    #[msg("The deadline of the transaction has passed")]
+    Expired,
+
+    // 40.
+    /// The pool still holds liquidity besides the locked minimum
+    #[msg("The pool still holds liquidity besides the locked minimum")]
+    PoolNotDrained,
+    /// The swap received less than the amount used by the curve
+    #[msg("The swap received less than the amount used by the curve")]
+    InsufficientTransferAmount,
+    /// The value of a pool token decreased
+    #[msg("The value of a pool token decreased")]
+    PoolValueDecreased,
+    /// A token account is required unless its mint is wrapped SOL
+    #[msg("A token account is required unless its mint is wrapped SOL")]
+    TokenAccountRequired,
+    /// The temporary wrapped SOL account is required to wrap or unwrap SOL
+    #[msg("The temporary wrapped SOL account is required to wrap or unwrap SOL")]
+    MissingNativeSolAccount,
+    /// An observation was already recorded at the current timestamp
+    #[msg("An observation was already recorded at the current timestamp")]
+    ObservationTooRecent,
+    /// The transfer hook accounts of a mint are missing or out of order
+    #[msg("The transfer hook accounts of a mint are missing or out of order")]
+    InvalidTransferHookAccounts,
+    /// The deposited amount is worth less than the pool tokens minted
+    #[msg("The deposited amount is worth less than the pool tokens minted")]
+    InsufficientDepositAmount,
 }
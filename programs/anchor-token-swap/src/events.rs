@@ -0,0 +1,232 @@
+use {
+    crate::{curves::CurveType, Fees, PoolStatus, SwapConstraints},
+    anchor_lang::prelude::*,
+};
+
+/// Emit an event with `emit_cpi!` when the `event-cpi` feature is enabled,
+/// so indexers can read it from the instruction data instead of the
+/// truncatable program logs, or with `emit!` otherwise.
+///
+/// The accounts struct of the instruction must be annotated with
+/// `#[cfg_attr(feature = "event-cpi", event_cpi)]`.
+macro_rules! emit_event {
+    ($ctx:expr, $event:expr) => {{
+        #[cfg(feature = "event-cpi")]
+        {
+            let ctx = &$ctx;
+            anchor_lang::prelude::emit_cpi!($event);
+        }
+        #[cfg(not(feature = "event-cpi"))]
+        {
+            let _ = &$ctx;
+            anchor_lang::prelude::emit!($event);
+        }
+    }};
+}
+pub(crate) use emit_event;
+
+/// Emitted when a new swap is initialized
+#[event]
+pub struct PoolInitialized {
+    /// Swap account
+    pub token_swap: Pubkey,
+    /// Admin of the swap
+    pub admin: Pubkey,
+    /// Pool token mint
+    pub pool_mint: Pubkey,
+    /// Mint of token A
+    pub token_a_mint: Pubkey,
+    /// Mint of token B
+    pub token_b_mint: Pubkey,
+    /// Curve of the swap
+    pub curve_type: CurveType,
+    /// Fees of the swap
+    pub fees: Fees,
+    /// Trade fee in basis points, part of the address of the swap
+    pub fee_tier: u16,
+    /// Initial pool tokens minted to the creator
+    pub pool_tokens_minted: u64,
+    /// Initial amount of token A held by the swap
+    pub reserve_a: u64,
+    /// Initial amount of token B held by the swap
+    pub reserve_b: u64,
+}
+
+/// Emitted on every swap
+#[event]
+pub struct Swapped {
+    /// Swap account
+    pub token_swap: Pubkey,
+    /// Authority which transferred the source tokens
+    pub user: Pubkey,
+    /// Mint of the source token
+    pub source_mint: Pubkey,
+    /// Mint of the destination token
+    pub destination_mint: Pubkey,
+    /// Amount of source token transferred by the user, before any transfer fee
+    pub amount_in: u64,
+    /// Amount of destination token transferred by the swap, before any
+    /// transfer fee
+    pub amount_out: u64,
+    /// Trade fee in source token, kept in the swap for liquidity providers
+    pub trade_fee: u64,
+    /// Owner trade fee in source token, converted into pool tokens for the
+    /// pool fee and host fee accounts
+    pub owner_fee: u64,
+    /// Pool tokens minted to the host fee account
+    pub host_fee: u64,
+    /// Amount of token A held by the swap after the trade
+    pub reserve_a: u64,
+    /// Amount of token B held by the swap after the trade
+    pub reserve_b: u64,
+}
+
+/// Emitted on every deposit, for single-sided deposits one of the token
+/// amounts is 0
+#[event]
+pub struct Deposited {
+    /// Swap account
+    pub token_swap: Pubkey,
+    /// Authority which transferred the deposited tokens
+    pub user: Pubkey,
+    /// Amount of token A deposited, before any transfer fee
+    pub token_a_amount: u64,
+    /// Amount of token B deposited, before any transfer fee
+    pub token_b_amount: u64,
+    /// Pool tokens minted to the user
+    pub pool_tokens_minted: u64,
+    /// Amount of token A held by the swap after the deposit
+    pub reserve_a: u64,
+    /// Amount of token B held by the swap after the deposit
+    pub reserve_b: u64,
+}
+
+/// Emitted on every withdrawal, for single-sided withdrawals one of the token
+/// amounts is 0
+#[event]
+pub struct Withdrawn {
+    /// Swap account
+    pub token_swap: Pubkey,
+    /// Authority which provided the pool tokens
+    pub user: Pubkey,
+    /// Amount of token A withdrawn, before any transfer fee
+    pub token_a_amount: u64,
+    /// Amount of token B withdrawn, before any transfer fee
+    pub token_b_amount: u64,
+    /// Pool tokens burned from the user
+    pub pool_tokens_burned: u64,
+    /// Pool tokens sent to the pool fee account
+    pub withdraw_fee: u64,
+    /// Amount of token A held by the swap after the withdrawal
+    pub reserve_a: u64,
+    /// Amount of token B held by the swap after the withdrawal
+    pub reserve_b: u64,
+}
+
+/// Emitted when the admin of a swap replaces its fees
+#[event]
+pub struct FeesUpdated {
+    /// Swap account whose fees were updated
+    pub token_swap: Pubkey,
+    /// Fees before the update
+    pub old_fees: Fees,
+    /// Fees after the update
+    pub new_fees: Fees,
+}
+
+/// Emitted when the admin of a swap hands the role over
+#[event]
+pub struct AdminUpdated {
+    /// Swap account whose admin was updated
+    pub token_swap: Pubkey,
+    /// Admin before the update
+    pub old_admin: Pubkey,
+    /// Admin after the update
+    pub new_admin: Pubkey,
+}
+
+/// Emitted when the upgrade authority sets the config of the program
+#[event]
+pub struct ConfigUpdated {
+    /// Constraints on the swaps created with the program
+    pub constraints: Option<SwapConstraints>,
+}
+
+/// Emitted when the admin of a swap changes its status
+#[event]
+pub struct PoolStatusUpdated {
+    /// Swap account whose status was updated
+    pub token_swap: Pubkey,
+    /// Status before the update
+    pub old_status: PoolStatus,
+    /// Status after the update
+    pub new_status: PoolStatus,
+}
+
+/// Emitted when a flash loan is lent out of a swap
+#[event]
+pub struct FlashBorrowed {
+    /// Swap account
+    pub token_swap: Pubkey,
+    /// Authority which took the loan
+    pub borrower: Pubkey,
+    /// Mint of the borrowed token
+    pub mint: Pubkey,
+    /// Amount lent out
+    pub amount: u64,
+    /// Fee owed on top of the amount
+    pub fee: u64,
+}
+
+/// Emitted when a flash loan is repaid
+#[event]
+pub struct FlashRepaid {
+    /// Swap account
+    pub token_swap: Pubkey,
+    /// Authority which repaid the loan
+    pub user: Pubkey,
+    /// Mint of the borrowed token
+    pub mint: Pubkey,
+    /// Amount transferred back to the swap, including the fee and any
+    /// transfer fee
+    pub amount_repaid: u64,
+    /// Fee credited to liquidity providers
+    pub fee: u64,
+    /// Reserve of the borrowed token after the repayment
+    pub reserve: u64,
+}
+
+/// Emitted when the admin of a drained swap closes it
+#[event]
+pub struct PoolClosed {
+    /// Swap account, closed
+    pub token_swap: Pubkey,
+    /// Admin of the swap, which received the remaining tokens and the rent
+    pub admin: Pubkey,
+    /// Amount of token A swept to the admin, before any transfer fee
+    pub token_a_amount: u64,
+    /// Amount of token B swept to the admin, before any transfer fee
+    pub token_b_amount: u64,
+}
+
+/// Emitted when the reserves of a swap are set to its token balances
+#[event]
+pub struct ReservesSynced {
+    /// Swap account
+    pub token_swap: Pubkey,
+    /// Reserve of token A after the sync
+    pub reserve_a: u64,
+    /// Reserve of token B after the sync
+    pub reserve_b: u64,
+}
+
+/// Emitted when the tokens held by a swap above its reserves are skimmed
+#[event]
+pub struct Skimmed {
+    /// Swap account
+    pub token_swap: Pubkey,
+    /// Amount of token A skimmed, before any transfer fee
+    pub token_a_amount: u64,
+    /// Amount of token B skimmed, before any transfer fee
+    pub token_b_amount: u64,
+}
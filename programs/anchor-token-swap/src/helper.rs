@@ -1,7 +1,473 @@
-use {crate::SwapError, anchor_lang::prelude::*};
+use {
+    crate::SwapError,
+    anchor_lang::{prelude::*, solana_program::program_pack::Pack, system_program},
+    anchor_spl::{
+        token::spl_token,
+        token_2022::spl_token_2022::{
+            self,
+            extension::{transfer_fee::TransferFeeConfig, transfer_hook, StateWithExtensions},
+            onchain,
+        },
+        token_interface::{
+            get_mint_extension_data, Mint, TokenAccount, TokenInterface, TransferChecked,
+        },
+    },
+    spl_tlv_account_resolution::state::ExtraAccountMetaList,
+    spl_transfer_hook_interface::{
+        get_extra_account_metas_address, instruction::ExecuteInstruction,
+    },
+    spl_type_length_value::state::TlvStateBorrowed,
+};
+
+/// Seed of the temporary wrapped SOL account of a user transfer authority,
+/// standing in for a wrapped SOL token account omitted by the user
+pub const NATIVE_SOL_SEED: &[u8] = b"native-sol";
 
 pub fn to_u64(amount: u128) -> Result<u64> {
     amount
         .try_into()
         .map_err(|_| SwapError::ConversionFailure.into())
 }
+
+/// Fee withheld by the Token-2022 transfer fee extension of the mint when
+/// transferring `amount` during the current epoch, 0 for mints without it
+pub fn transfer_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    match get_mint_extension_data::<TransferFeeConfig>(&mint.to_account_info()) {
+        Ok(config) => config
+            .calculate_epoch_fee(Clock::get()?.epoch, amount)
+            .ok_or_else(|| SwapError::FeeCalculationFailure.into()),
+        Err(_) => Ok(0),
+    }
+}
+
+/// Fee to add to `amount` so that `amount` is received after the Token-2022
+/// transfer fee of the mint during the current epoch, 0 for mints without it
+pub fn inverse_transfer_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    match get_mint_extension_data::<TransferFeeConfig>(&mint.to_account_info()) {
+        Ok(config) => config
+            .calculate_inverse_epoch_fee(Clock::get()?.epoch, amount)
+            .ok_or_else(|| SwapError::FeeCalculationFailure.into()),
+        Err(_) => Ok(0),
+    }
+}
+
+/// Split the accounts required by the transfer hook of the mint off the front
+/// of `remaining_accounts`: the hook program, the extra account metas account
+/// of the mint, then the extra accounts it lists. Mints without a transfer
+/// hook take no account, so that every transfer only gets the accounts of its
+/// own mint. Instructions take the accounts of token A before the ones of
+/// token B, or of the source mint before the ones of the destination mint.
+pub fn split_hook_accounts<'a, 'info>(
+    mint: &AccountInfo<'info>,
+    remaining_accounts: &mut &'a [AccountInfo<'info>],
+) -> Result<&'a [AccountInfo<'info>]> {
+    let Some(hook_program_id) = transfer_hook_program_id(mint)? else {
+        return Ok(&[]);
+    };
+    let [hook_program, extra_metas, ..] = remaining_accounts else {
+        return err!(SwapError::InvalidTransferHookAccounts);
+    };
+    require_keys_eq!(
+        hook_program.key(),
+        hook_program_id,
+        SwapError::InvalidTransferHookAccounts
+    );
+    require_keys_eq!(
+        extra_metas.key(),
+        get_extra_account_metas_address(mint.key, &hook_program_id),
+        SwapError::InvalidTransferHookAccounts
+    );
+    // a hook without extra accounts may not have created the account
+    let extra_accounts = if extra_metas.data_is_empty() {
+        0
+    } else {
+        let data = extra_metas.try_borrow_data()?;
+        let tlv_state = TlvStateBorrowed::unpack(&data)?;
+        ExtraAccountMetaList::unpack_with_tlv_state::<ExecuteInstruction>(&tlv_state)?
+            .data()
+            .len()
+    };
+    let len = extra_accounts + 2;
+    require_gte!(
+        remaining_accounts.len(),
+        len,
+        SwapError::InvalidTransferHookAccounts
+    );
+    let (hook_accounts, rest) = remaining_accounts.split_at(len);
+    *remaining_accounts = rest;
+    Ok(hook_accounts)
+}
+
+/// Program of the transfer hook of a Token-2022 mint, if any
+fn transfer_hook_program_id(mint: &AccountInfo) -> Result<Option<Pubkey>> {
+    if mint.owner != &spl_token_2022::ID {
+        return Ok(None);
+    }
+    let data = mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    Ok(transfer_hook::get_program_id(&mint))
+}
+
+/// `transfer_checked` resolving the extra accounts required by the transfer
+/// hook of a Token-2022 mint from the remaining accounts of the context,
+/// which the plain CPI does not pass to the hook program. The remaining
+/// accounts are the ones split off for the mint by `split_hook_accounts`.
+pub fn transfer_checked_with_hook<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, TransferChecked<'info>>,
+    amount: u64,
+    decimals: u8,
+) -> Result<()> {
+    onchain::invoke_transfer_checked(
+        ctx.program.key,
+        ctx.accounts.from,
+        ctx.accounts.mint,
+        ctx.accounts.to,
+        ctx.accounts.authority,
+        &ctx.remaining_accounts,
+        amount,
+        decimals,
+        ctx.signer_seeds,
+    )
+    .map_err(Into::into)
+}
+
+/// Whether the mint is the wrapped SOL mint of spl-token or Token-2022
+pub fn is_native_mint(mint: &Pubkey) -> bool {
+    *mint == spl_token::native_mint::ID || *mint == spl_token_2022::native_mint::ID
+}
+
+/// Token account of the user in one leg of an instruction, either given by
+/// the user or, for wrapped SOL, the temporary account of the user transfer
+/// authority, created around the instruction and closed back to lamports
+pub enum UserTokenAccount<'info> {
+    Token(AccountInfo<'info>),
+    NativeSol {
+        account: AccountInfo<'info>,
+        bump: u8,
+        wallet: AccountInfo<'info>,
+        mint: AccountInfo<'info>,
+        token_program: AccountInfo<'info>,
+        system_program: AccountInfo<'info>,
+    },
+}
+
+impl<'info> UserTokenAccount<'info> {
+    /// Use the token account of the user if given, otherwise the temporary
+    /// wrapped SOL account of `wallet`, which must then be passed as well
+    pub fn new(
+        token_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+        native_sol_account: Option<&UncheckedAccount<'info>>,
+        native_sol_bump: Option<u8>,
+        wallet: &Signer<'info>,
+        mint: &InterfaceAccount<'info, Mint>,
+        token_program: &Interface<'info, TokenInterface>,
+        system_program: &Program<'info, System>,
+    ) -> Result<Self> {
+        if let Some(token_account) = token_account {
+            return Ok(Self::Token(token_account.to_account_info()));
+        }
+        require!(is_native_mint(&mint.key()), SwapError::TokenAccountRequired);
+        let (account, bump) = native_sol_account
+            .zip(native_sol_bump)
+            .ok_or(SwapError::MissingNativeSolAccount)?;
+        Ok(Self::NativeSol {
+            account: account.to_account_info(),
+            bump,
+            wallet: wallet.to_account_info(),
+            mint: mint.to_account_info(),
+            token_program: token_program.to_account_info(),
+            system_program: system_program.to_account_info(),
+        })
+    }
+
+    pub fn key(&self) -> Pubkey {
+        match self {
+            Self::Token(account) | Self::NativeSol { account, .. } => account.key(),
+        }
+    }
+
+    pub fn to_account_info(&self) -> AccountInfo<'info> {
+        match self {
+            Self::Token(account) | Self::NativeSol { account, .. } => account.clone(),
+        }
+    }
+
+    /// Create the temporary wrapped SOL account, owned by the wallet and
+    /// holding `amount` SOL taken from it. Nothing to do for a token account
+    /// of the user.
+    pub fn wrap(&self, amount: u64) -> Result<()> {
+        let Self::NativeSol {
+            account,
+            bump,
+            wallet,
+            mint,
+            token_program,
+            system_program,
+        } = self
+        else {
+            return Ok(());
+        };
+        let space = spl_token_2022::state::Account::LEN;
+        let lamports = Rent::get()?
+            .minimum_balance(space)
+            .checked_add(amount)
+            .ok_or(SwapError::CalculationFailure)?;
+        let signer_seeds: &[&[&[u8]]] = &[&[NATIVE_SOL_SEED, wallet.key.as_ref(), &[*bump]]];
+        // lamports sent to the address beforehand must not block the wallet,
+        // they are wrapped along with the amount
+        let top_up = lamports.saturating_sub(account.lamports());
+        if top_up > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    system_program.clone(),
+                    system_program::Transfer {
+                        from: wallet.clone(),
+                        to: account.clone(),
+                    },
+                ),
+                top_up,
+            )?;
+        }
+        system_program::allocate(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                system_program::Allocate {
+                    account_to_allocate: account.clone(),
+                },
+                signer_seeds,
+            ),
+            space as u64,
+        )?;
+        system_program::assign(
+            CpiContext::new_with_signer(
+                system_program.clone(),
+                system_program::Assign {
+                    account_to_assign: account.clone(),
+                },
+                signer_seeds,
+            ),
+            token_program.key,
+        )?;
+        anchor_spl::token_interface::initialize_account3(CpiContext::new(
+            token_program.clone(),
+            anchor_spl::token_interface::InitializeAccount3 {
+                account: account.clone(),
+                mint: mint.clone(),
+                authority: wallet.clone(),
+            },
+        ))?;
+        anchor_spl::token_interface::sync_native(CpiContext::new(
+            token_program.clone(),
+            anchor_spl::token_interface::SyncNative {
+                account: account.clone(),
+            },
+        ))
+    }
+
+    /// Close the temporary wrapped SOL account, returning what it holds and
+    /// its rent to the wallet as lamports. Nothing to do for a token account
+    /// of the user.
+    pub fn unwrap(&self) -> Result<()> {
+        let Self::NativeSol {
+            account,
+            wallet,
+            token_program,
+            ..
+        } = self
+        else {
+            return Ok(());
+        };
+        anchor_spl::token_interface::close_account(CpiContext::new(
+            token_program.clone(),
+            anchor_spl::token_interface::CloseAccount {
+                account: account.clone(),
+                destination: wallet.clone(),
+                authority: wallet.clone(),
+            },
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        anchor_spl::token_2022::spl_token_2022::{
+            extension::{
+                transfer_hook::TransferHook, BaseStateWithExtensionsMut, ExtensionType,
+                StateWithExtensionsMut,
+            },
+            state::Mint as MintState,
+        },
+        spl_tlv_account_resolution::account::ExtraAccountMeta,
+    };
+
+    /// Account backing an `AccountInfo` of a test
+    struct TestAccount {
+        key: Pubkey,
+        owner: Pubkey,
+        lamports: u64,
+        data: Vec<u8>,
+    }
+
+    impl TestAccount {
+        fn new(owner: Pubkey, data: Vec<u8>) -> Self {
+            Self {
+                key: Pubkey::new_unique(),
+                owner,
+                lamports: 1,
+                data,
+            }
+        }
+
+        fn info(&mut self) -> AccountInfo<'_> {
+            AccountInfo::new(
+                &self.key,
+                false,
+                false,
+                &mut self.lamports,
+                &mut self.data,
+                &self.owner,
+                false,
+                0,
+            )
+        }
+    }
+
+    /// Token-2022 mint, with a transfer hook if `hook_program` is set
+    fn mint(hook_program: Option<Pubkey>) -> TestAccount {
+        let extensions = hook_program
+            .map(|_| vec![ExtensionType::TransferHook])
+            .unwrap_or_default();
+        let mut data =
+            vec![0; ExtensionType::try_calculate_account_len::<MintState>(&extensions).unwrap()];
+        let mut state =
+            StateWithExtensionsMut::<MintState>::unpack_uninitialized(&mut data).unwrap();
+        if let Some(hook_program) = hook_program {
+            state
+                .init_extension::<TransferHook>(true)
+                .unwrap()
+                .program_id = Some(hook_program).try_into().unwrap();
+        }
+        state.base = MintState {
+            decimals: 2,
+            is_initialized: true,
+            ..Default::default()
+        };
+        state.pack_base();
+        if hook_program.is_some() {
+            state.init_account_type().unwrap();
+        }
+        TestAccount::new(spl_token_2022::ID, data)
+    }
+
+    /// Hook program, extra account metas of the mint and the listed extra
+    /// accounts
+    fn hook_accounts(mint: &TestAccount, hook_program: Pubkey, extras: usize) -> Vec<TestAccount> {
+        let extra_accounts = (0..extras)
+            .map(|_| TestAccount::new(Pubkey::new_unique(), vec![]))
+            .collect::<Vec<_>>();
+        let metas = extra_accounts
+            .iter()
+            .map(|account| ExtraAccountMeta::new_with_pubkey(&account.key, false, false).unwrap())
+            .collect::<Vec<_>>();
+        let mut data = vec![0; ExtraAccountMetaList::size_of(extras).unwrap()];
+        ExtraAccountMetaList::init::<ExecuteInstruction>(&mut data, &metas).unwrap();
+        let mut extra_metas = TestAccount::new(hook_program, data);
+        extra_metas.key = get_extra_account_metas_address(&mint.key, &hook_program);
+        let mut program = TestAccount::new(Pubkey::default(), vec![]);
+        program.key = hook_program;
+        [program, extra_metas]
+            .into_iter()
+            .chain(extra_accounts)
+            .collect()
+    }
+
+    #[test]
+    fn split_hook_accounts_of_each_mint() {
+        let (hook_a, hook_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut mint_a = mint(Some(hook_a));
+        let mut mint_b = mint(Some(hook_b));
+        let mut accounts = hook_accounts(&mint_a, hook_a, 2);
+        accounts.extend(hook_accounts(&mint_b, hook_b, 1));
+        let keys = accounts
+            .iter()
+            .map(|account| account.key)
+            .collect::<Vec<_>>();
+        let infos = accounts
+            .iter_mut()
+            .map(TestAccount::info)
+            .collect::<Vec<_>>();
+
+        let mut remaining_accounts = &infos[..];
+        let accounts_a = split_hook_accounts(&mint_a.info(), &mut remaining_accounts).unwrap();
+        let accounts_b = split_hook_accounts(&mint_b.info(), &mut remaining_accounts).unwrap();
+        assert_eq!(
+            accounts_a.iter().map(|info| *info.key).collect::<Vec<_>>(),
+            keys[..4]
+        );
+        assert_eq!(
+            accounts_b.iter().map(|info| *info.key).collect::<Vec<_>>(),
+            keys[4..]
+        );
+        assert!(remaining_accounts.is_empty());
+    }
+
+    #[test]
+    fn split_hook_accounts_of_mint_without_hook() {
+        let hook = Pubkey::new_unique();
+        let mut plain_mint = mint(None);
+        let mut hooked_mint = mint(Some(hook));
+        let mut accounts = hook_accounts(&hooked_mint, hook, 1);
+        let infos = accounts
+            .iter_mut()
+            .map(TestAccount::info)
+            .collect::<Vec<_>>();
+
+        // the mint without a hook leaves the accounts of the other one
+        let mut remaining_accounts = &infos[..];
+        let plain_accounts =
+            split_hook_accounts(&plain_mint.info(), &mut remaining_accounts).unwrap();
+        assert!(plain_accounts.is_empty());
+        assert_eq!(remaining_accounts.len(), 3);
+        let hooked_accounts =
+            split_hook_accounts(&hooked_mint.info(), &mut remaining_accounts).unwrap();
+        assert_eq!(hooked_accounts.len(), 3);
+    }
+
+    #[test]
+    fn split_hook_accounts_of_another_mint() {
+        let hook = Pubkey::new_unique();
+        let mut mint_a = mint(Some(hook));
+        let mint_b = mint(Some(hook));
+        let mut accounts_b = hook_accounts(&mint_b, hook, 0);
+        let mut accounts_a = hook_accounts(&mint_a, hook, 2);
+        let infos_b = accounts_b
+            .iter_mut()
+            .map(TestAccount::info)
+            .collect::<Vec<_>>();
+        let infos_a = accounts_a
+            .iter_mut()
+            .map(TestAccount::info)
+            .collect::<Vec<_>>();
+        let mint_a_info = mint_a.info();
+
+        // the extra account metas of mint B do not belong to mint A
+        let mut remaining_accounts = &infos_b[..];
+        assert_eq!(
+            split_hook_accounts(&mint_a_info, &mut remaining_accounts).unwrap_err(),
+            SwapError::InvalidTransferHookAccounts.into()
+        );
+        // nor does a missing or short list of extra accounts
+        let mut remaining_accounts = &infos_a[..3];
+        assert_eq!(
+            split_hook_accounts(&mint_a_info, &mut remaining_accounts).unwrap_err(),
+            SwapError::InvalidTransferHookAccounts.into()
+        );
+        let mut remaining_accounts = &infos_a[..0];
+        assert_eq!(
+            split_hook_accounts(&mint_a_info, &mut remaining_accounts).unwrap_err(),
+            SwapError::InvalidTransferHookAccounts.into()
+        );
+    }
+}
@@ -0,0 +1,233 @@
+use {
+    crate::{
+        events::{emit_event, PoolClosed},
+        helper::{split_hook_accounts, transfer_checked_with_hook},
+        instructions::{LOCKED_LIQUIDITY_SEED, OBSERVATIONS_SEED},
+        PoolEntry, SwapError, SwapV2,
+    },
+    anchor_lang::prelude::*,
+    anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+/// Close a drained swap, sweeping what is left in its token accounts to the
+/// admin, burning the locked liquidity and closing every account of the swap
+/// but its pool mint, which is reused when the swap is created again. The
+/// rent of every closed account returns to the admin.
+pub fn close_pool_handler<'info>(ctx: Context<'_, '_, '_, 'info, ClosePool<'info>>) -> Result<()> {
+    // only the liquidity locked on initialization may remain, the tokens
+    // backing it have no other owner than the pool. A migrated swap has no
+    // locked liquidity, so all of its pool tokens must have been withdrawn.
+    let locked_amount = ctx
+        .accounts
+        .locked_liquidity
+        .as_ref()
+        .map_or(0, |locked_liquidity| locked_liquidity.amount);
+    require_eq!(
+        ctx.accounts.pool_mint.supply,
+        locked_amount,
+        SwapError::PoolNotDrained
+    );
+    require!(
+        ctx.accounts.pool_entry.is_some() == ctx.accounts.token_swap.pool_entry_index.is_some(),
+        SwapError::IncorrectSwapAccount
+    );
+
+    let token_swap_key = ctx.accounts.token_swap.key();
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        token_swap_key.as_ref(),
+        &[ctx.accounts.token_swap.bump_seed],
+    ]];
+    let token_a_amount = ctx.accounts.swap_token_a.amount;
+    let token_b_amount = ctx.accounts.swap_token_b.amount;
+    let mut remaining_accounts = ctx.remaining_accounts;
+    for (token_program, swap_token, destination, mint, amount) in [
+        (
+            &ctx.accounts.token_a_program,
+            &ctx.accounts.swap_token_a,
+            &ctx.accounts.destination_a,
+            &ctx.accounts.token_a_mint,
+            token_a_amount,
+        ),
+        (
+            &ctx.accounts.token_b_program,
+            &ctx.accounts.swap_token_b,
+            &ctx.accounts.destination_b,
+            &ctx.accounts.token_b_mint,
+            token_b_amount,
+        ),
+    ] {
+        let hook_accounts = split_hook_accounts(&mint.to_account_info(), &mut remaining_accounts)?;
+        if amount > 0 {
+            transfer_checked_with_hook(
+                CpiContext::new_with_signer(
+                    token_program.to_account_info(),
+                    anchor_spl::token_interface::TransferChecked {
+                        from: swap_token.to_account_info(),
+                        to: destination.to_account_info(),
+                        authority: ctx.accounts.authority.to_account_info(),
+                        mint: mint.to_account_info(),
+                    },
+                    signer_seeds,
+                )
+                .with_remaining_accounts(hook_accounts.to_vec()),
+                amount,
+                mint.decimals,
+            )?;
+        }
+        anchor_spl::token_interface::close_account(CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            anchor_spl::token_interface::CloseAccount {
+                account: swap_token.to_account_info(),
+                destination: ctx.accounts.admin.to_account_info(),
+                authority: ctx.accounts.authority.to_account_info(),
+            },
+            signer_seeds,
+        ))?;
+    }
+
+    if let Some(locked_liquidity) = &ctx.accounts.locked_liquidity {
+        if locked_amount > 0 {
+            anchor_spl::token_interface::burn(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_pool_program.to_account_info(),
+                    anchor_spl::token_interface::Burn {
+                        mint: ctx.accounts.pool_mint.to_account_info(),
+                        from: locked_liquidity.to_account_info(),
+                        authority: ctx.accounts.authority.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                locked_amount,
+            )?;
+        }
+        anchor_spl::token_interface::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_pool_program.to_account_info(),
+            anchor_spl::token_interface::CloseAccount {
+                account: locked_liquidity.to_account_info(),
+                destination: ctx.accounts.admin.to_account_info(),
+                authority: ctx.accounts.authority.to_account_info(),
+            },
+            signer_seeds,
+        ))?;
+    }
+    // the observations are optional, only created by `initialize_observations`
+    let observations = ctx.accounts.observations.to_account_info();
+    if observations.owner == &crate::ID {
+        let admin = ctx.accounts.admin.to_account_info();
+        let lamports = admin
+            .lamports()
+            .checked_add(observations.lamports())
+            .ok_or(SwapError::CalculationFailure)?;
+        **admin.lamports.borrow_mut() = lamports;
+        **observations.lamports.borrow_mut() = 0;
+        observations.assign(&System::id());
+        observations.realloc(0, false)?;
+    }
+
+    emit_event!(
+        ctx,
+        PoolClosed {
+            token_swap: token_swap_key,
+            admin: ctx.accounts.admin.key(),
+            token_a_amount,
+            token_b_amount,
+        }
+    );
+    Ok(())
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ClosePool<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        close = admin,
+        has_one = admin @ SwapError::InvalidAdmin,
+        constraint = !token_swap.to_account_info().data_is_empty() @ SwapError::IncorrectSwapAccount,
+        constraint = token_swap.flash_loan.is_none() @ SwapError::FlashLoanInProgress,
+    )]
+    pub token_swap: Account<'info, SwapV2>,
+    #[account(
+        seeds = [token_swap.key().as_ref()],
+        bump = token_swap.bump_seed,
+    )]
+    pub authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        token::mint = token_a_mint,
+        token::token_program = token_a_program,
+        constraint = swap_token_a.key() == token_swap.token_a @ SwapError::IncorrectSwapAccount,
+    )]
+    pub swap_token_a: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = token_b_mint,
+        token::token_program = token_b_program,
+        constraint = swap_token_b.key() == token_swap.token_b @ SwapError::IncorrectSwapAccount,
+    )]
+    pub swap_token_b: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = pool_mint.key() == token_swap.pool_mint @ SwapError::IncorrectPoolMint,
+    )]
+    pub pool_mint: InterfaceAccount<'info, Mint>,
+    /// Required unless the swap was migrated
+    #[account(
+        mut,
+        seeds = [LOCKED_LIQUIDITY_SEED, token_swap.key().as_ref()],
+        bump,
+        token::mint = pool_mint,
+        token::token_program = token_pool_program,
+    )]
+    pub locked_liquidity: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Registry entry of the swap, required unless the swap was migrated
+    #[account(
+        mut,
+        close = admin,
+        constraint = pool_entry.token_swap == token_swap.key() @ SwapError::IncorrectSwapAccount,
+    )]
+    pub pool_entry: Option<Account<'info, PoolEntry>>,
+    /// CHECK: observations of the swap, closed if they were initialized
+    #[account(
+        mut,
+        seeds = [OBSERVATIONS_SEED, token_swap.key().as_ref()],
+        bump,
+    )]
+    pub observations: UncheckedAccount<'info>,
+    #[account(
+        constraint = token_a_mint.key() == token_swap.token_a_mint @ SwapError::IncorrectSwapAccount,
+    )]
+    pub token_a_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        constraint = token_b_mint.key() == token_swap.token_b_mint @ SwapError::IncorrectSwapAccount,
+    )]
+    pub token_b_mint: InterfaceAccount<'info, Mint>,
+    /// Receives what is left of token A in the swap
+    #[account(
+        mut,
+        token::mint = token_a_mint,
+        token::token_program = token_a_program,
+    )]
+    pub destination_a: InterfaceAccount<'info, TokenAccount>,
+    /// Receives what is left of token B in the swap
+    #[account(
+        mut,
+        token::mint = token_b_mint,
+        token::token_program = token_b_program,
+    )]
+    pub destination_b: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        constraint = token_a_program.key() == token_swap.token_a_program @ SwapError::IncorrectTokenProgramId,
+    )]
+    pub token_a_program: Interface<'info, TokenInterface>,
+    #[account(
+        constraint = token_b_program.key() == token_swap.token_b_program @ SwapError::IncorrectTokenProgramId,
+    )]
+    pub token_b_program: Interface<'info, TokenInterface>,
+    #[account(
+        constraint = token_pool_program.key() == token_swap.pool_token_program @ SwapError::IncorrectTokenProgramId,
+    )]
+    pub token_pool_program: Interface<'info, TokenInterface>,
+}
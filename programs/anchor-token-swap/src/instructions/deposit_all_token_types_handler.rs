@@ -1,20 +1,35 @@
+#[cfg(feature = "invariant-check")]
+use crate::invariant::PoolValue;
 use {
     crate::{
         curves::{RoundDirection, SwapCurve},
-        helper::to_u64,
-        SwapError, SwapV1,
+        events::{emit_event, Deposited},
+        helper::{
+            split_hook_accounts, to_u64, transfer_checked_with_hook, UserTokenAccount,
+            NATIVE_SOL_SEED,
+        },
+        instructions::{LOCKED_LIQUIDITY_SEED, MINIMUM_LIQUIDITY},
+        SwapError, SwapV2,
     },
     anchor_lang::prelude::*,
     anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface},
 };
 
-pub fn deposit_all_token_types_handler(
-    ctx: Context<DepositAllTokenTypes>,
+pub fn deposit_all_token_types_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAllTokenTypes<'info>>,
     pool_token_amount: u64,
     token_a_slippage_limit: u64,
     token_b_slippage_limit: u64,
 ) -> Result<()> {
-    let token_swap = &ctx.accounts.swap_v1;
+    ctx.accounts.token_swap.update_cumulative_prices()?;
+    let balances_before = (ctx.accounts.token_a.amount, ctx.accounts.token_b.amount);
+    #[cfg(feature = "invariant-check")]
+    let pool_value = PoolValue::new(
+        &ctx.accounts.token_swap,
+        balances_before,
+        ctx.accounts.pool_mint.supply,
+    )?;
+    let token_swap = &ctx.accounts.token_swap;
     let swap_curve = SwapCurve::new(token_swap.curve_type);
     let calculator = swap_curve.calculator;
     if !calculator.allows_deposits() {
@@ -23,17 +38,23 @@ pub fn deposit_all_token_types_handler(
 
     let current_pool_supply = ctx.accounts.pool_mint.supply as u128;
 
-    let (pool_token_amount, pool_mint_supply) = if current_pool_supply > 0 {
-        (u128::from(pool_token_amount), current_pool_supply)
+    // an empty pool starts over from the initial supply, part of which is
+    // locked forever
+    let (pool_token_amount, pool_mint_supply, locked_amount) = if current_pool_supply > 0 {
+        (u128::from(pool_token_amount), current_pool_supply, 0)
     } else {
-        (calculator.new_pool_supply(), calculator.new_pool_supply())
+        (
+            calculator.new_pool_supply(),
+            calculator.new_pool_supply(),
+            MINIMUM_LIQUIDITY,
+        )
     };
     let results = calculator
         .pool_tokens_to_trading_tokens(
             pool_token_amount,
             pool_mint_supply,
-            u128::from(ctx.accounts.token_a.amount),
-            u128::from(ctx.accounts.token_b.amount),
+            u128::from(ctx.accounts.token_swap.reserve_a),
+            u128::from(ctx.accounts.token_swap.reserve_b),
             RoundDirection::Ceiling,
         )
         .ok_or(SwapError::ZeroTradingTokens)?;
@@ -53,35 +74,93 @@ pub fn deposit_all_token_types_handler(
 
     require_neq!(token_a_amount, 0, SwapError::ZeroTradingTokens);
     require_neq!(token_b_amount, 0, SwapError::ZeroTradingTokens);
+    let pool_tokens_minted = to_u64(pool_token_amount)?
+        .checked_sub(locked_amount)
+        .ok_or(SwapError::CalculationFailure)?;
 
-    anchor_spl::token_interface::transfer_checked(
+    let source_a = UserTokenAccount::new(
+        ctx.accounts.source_a.as_ref(),
+        ctx.accounts.native_sol_account.as_ref(),
+        ctx.bumps.native_sol_account,
+        &ctx.accounts.user_transfer_authority,
+        &ctx.accounts.token_a_mint,
+        &ctx.accounts.token_a_program,
+        &ctx.accounts.system_program,
+    )?;
+    let source_b = UserTokenAccount::new(
+        ctx.accounts.source_b.as_ref(),
+        ctx.accounts.native_sol_account.as_ref(),
+        ctx.bumps.native_sol_account,
+        &ctx.accounts.user_transfer_authority,
+        &ctx.accounts.token_b_mint,
+        &ctx.accounts.token_b_program,
+        &ctx.accounts.system_program,
+    )?;
+    source_a.wrap(token_a_amount)?;
+    source_b.wrap(token_b_amount)?;
+    let mut remaining_accounts = ctx.remaining_accounts;
+    let token_a_hook_accounts = split_hook_accounts(
+        &ctx.accounts.token_a_mint.to_account_info(),
+        &mut remaining_accounts,
+    )?;
+    let token_b_hook_accounts = split_hook_accounts(
+        &ctx.accounts.token_b_mint.to_account_info(),
+        &mut remaining_accounts,
+    )?;
+    transfer_checked_with_hook(
         CpiContext::new(
             ctx.accounts.token_a_program.to_account_info(),
             anchor_spl::token_interface::TransferChecked {
-                from: ctx.accounts.source_a.to_account_info(),
+                from: source_a.to_account_info(),
                 to: ctx.accounts.token_a.to_account_info(),
                 authority: ctx.accounts.user_transfer_authority.to_account_info(),
                 mint: ctx.accounts.token_a_mint.to_account_info(),
             },
-        ),
+        )
+        .with_remaining_accounts(token_a_hook_accounts.to_vec()),
         token_a_amount,
         ctx.accounts.token_a_mint.decimals,
     )?;
 
-    anchor_spl::token_interface::transfer_checked(
+    transfer_checked_with_hook(
         CpiContext::new(
             ctx.accounts.token_b_program.to_account_info(),
             anchor_spl::token_interface::TransferChecked {
-                from: ctx.accounts.source_b.to_account_info(),
+                from: source_b.to_account_info(),
                 to: ctx.accounts.token_b.to_account_info(),
                 authority: ctx.accounts.user_transfer_authority.to_account_info(),
                 mint: ctx.accounts.token_b_mint.to_account_info(),
             },
-        ),
+        )
+        .with_remaining_accounts(token_b_hook_accounts.to_vec()),
         token_b_amount,
         ctx.accounts.token_b_mint.decimals,
     )?;
+    source_a.unwrap()?;
+    source_b.unwrap()?;
 
+    if locked_amount > 0 {
+        let locked_liquidity = ctx
+            .accounts
+            .locked_liquidity
+            .as_ref()
+            .ok_or(SwapError::MissingLockedLiquidity)?;
+        anchor_spl::token_interface::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_pool_program.to_account_info(),
+                anchor_spl::token_interface::MintTo {
+                    mint: ctx.accounts.pool_mint.to_account_info(),
+                    to: locked_liquidity.to_account_info(),
+                    authority: ctx.accounts.authority.to_account_info(),
+                },
+                &[&[
+                    &ctx.accounts.token_swap.key().to_bytes(),
+                    &[ctx.accounts.token_swap.bump_seed],
+                ]],
+            ),
+            locked_amount,
+        )?;
+    }
     anchor_spl::token_interface::mint_to(
         CpiContext::new_with_signer(
             ctx.accounts.token_pool_program.to_account_info(),
@@ -91,68 +170,104 @@ pub fn deposit_all_token_types_handler(
                 authority: ctx.accounts.authority.to_account_info(),
             },
             &[&[
-                &ctx.accounts.swap_v1.key().to_bytes(),
-                &[ctx.bumps.authority],
+                &ctx.accounts.token_swap.key().to_bytes(),
+                &[ctx.accounts.token_swap.bump_seed],
             ]],
         ),
-        to_u64(pool_token_amount)?,
+        pool_tokens_minted,
     )?;
+
+    ctx.accounts.token_a.reload()?;
+    ctx.accounts.token_b.reload()?;
+    ctx.accounts.token_swap.update_reserves(
+        balances_before,
+        (ctx.accounts.token_a.amount, ctx.accounts.token_b.amount),
+    )?;
+    #[cfg(feature = "invariant-check")]
+    {
+        ctx.accounts.pool_mint.reload()?;
+        pool_value.check(
+            &ctx.accounts.token_swap,
+            (ctx.accounts.token_a.amount, ctx.accounts.token_b.amount),
+            ctx.accounts.pool_mint.supply,
+        )?;
+    }
+    emit_event!(
+        ctx,
+        Deposited {
+            token_swap: ctx.accounts.token_swap.key(),
+            user: ctx.accounts.user_transfer_authority.key(),
+            token_a_amount,
+            token_b_amount,
+            pool_tokens_minted,
+            reserve_a: ctx.accounts.token_swap.reserve_a,
+            reserve_b: ctx.accounts.token_swap.reserve_b,
+        }
+    );
     Ok(())
 }
 
+#[cfg_attr(feature = "event-cpi", event_cpi)]
 #[derive(Accounts)]
 pub struct DepositAllTokenTypes<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
     #[account(
-      constraint = !swap_v1.to_account_info().data_is_empty() @ SwapError::IncorrectSwapAccount,
+      mut,
+      constraint = !token_swap.to_account_info().data_is_empty() @ SwapError::IncorrectSwapAccount,
+      constraint = token_swap.status.allows_deposits() @ SwapError::OperationPaused,
+      constraint = token_swap.flash_loan.is_none() @ SwapError::FlashLoanInProgress,
   )]
-    pub swap_v1: Account<'info, SwapV1>,
+    pub token_swap: Account<'info, SwapV2>,
     #[account(
-      seeds = [swap_v1.key().as_ref()],
-      bump,
+      seeds = [token_swap.key().as_ref()],
+      bump = token_swap.bump_seed,
   )]
     pub authority: AccountInfo<'info>,
 
     pub user_transfer_authority: Signer<'info>,
+    /// Omitted to deposit SOL of the user transfer authority, wrapped in
+    /// its temporary wrapped SOL account
     #[account(
       mut,
       token::mint = token_a_mint.key(),
       constraint = source_a.key() != token_a.key() @ SwapError::InvalidInput,
     )]
-    pub source_a: InterfaceAccount<'info, TokenAccount>,
+    pub source_a: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Omitted to deposit SOL of the user transfer authority, wrapped in
+    /// its temporary wrapped SOL account
     #[account(
       mut,
       token::mint = token_b_mint.key(),
       constraint = source_b.key() != token_b.key() @ SwapError::InvalidInput,
     )]
-    pub source_b: InterfaceAccount<'info, TokenAccount>,
+    pub source_b: Option<InterfaceAccount<'info, TokenAccount>>,
     #[account(
       mut,
       token::mint = token_a_mint.key(),
-      constraint = token_a.key() == swap_v1.token_a @ SwapError::InvalidInput,
+      constraint = token_a.key() == token_swap.token_a @ SwapError::InvalidInput,
     )]
     pub token_a: InterfaceAccount<'info, TokenAccount>,
     #[account(
       mut,
       token::mint = token_b_mint.key(),
-      constraint = token_b.key() == swap_v1.token_b @ SwapError::InvalidInput,
+      constraint = token_b.key() == token_swap.token_b @ SwapError::InvalidInput,
     )]
     pub token_b: InterfaceAccount<'info, TokenAccount>,
     #[account(
       mut,
       mint::token_program = token_pool_program.key(),
-      constraint = pool_mint.key() == swap_v1.pool_mint @ SwapError::IncorrectPoolMint,
+      constraint = pool_mint.key() == token_swap.pool_mint @ SwapError::IncorrectPoolMint,
     )]
     pub pool_mint: InterfaceAccount<'info, Mint>,
     #[account(
       mint::token_program = token_a_program.key(),
-      constraint = token_a_mint.key() == swap_v1.token_a_mint @ SwapError::InvalidInput,
+      constraint = token_a_mint.key() == token_swap.token_a_mint @ SwapError::InvalidInput,
     )]
     pub token_a_mint: InterfaceAccount<'info, Mint>,
     #[account(
         mint::token_program = token_b_program.key(),
-      constraint = token_b_mint.key() == swap_v1.token_b_mint @ SwapError::InvalidInput,
+      constraint = token_b_mint.key() == token_swap.token_b_mint @ SwapError::InvalidInput,
     )]
     pub token_b_mint: InterfaceAccount<'info, Mint>,
     #[account(
@@ -164,11 +279,36 @@ pub struct DepositAllTokenTypes<'info> {
     pub destination: InterfaceAccount<'info, TokenAccount>,
     #[account(
      token::mint = pool_mint.key(),
-     constraint = pool_fee_account.key() == swap_v1.pool_fee_account @ SwapError::InvalidInput,
+     constraint = pool_fee_account.key() == token_swap.pool_fee_account @ SwapError::InvalidInput,
     )]
     pub pool_fee_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Only required when the pool supply is zero
+    #[account(
+      mut,
+      seeds = [LOCKED_LIQUIDITY_SEED, token_swap.key().as_ref()],
+      bump,
+    )]
+    pub locked_liquidity: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        constraint = token_pool_program.key() == token_swap.pool_token_program @ SwapError::IncorrectTokenProgramId,
+    )]
     pub token_pool_program: Interface<'info, TokenInterface>,
+    #[account(
+        constraint = token_a_program.key() == token_swap.token_a_program @ SwapError::IncorrectTokenProgramId,
+    )]
     pub token_a_program: Interface<'info, TokenInterface>,
+    #[account(
+        constraint = token_b_program.key() == token_swap.token_b_program @ SwapError::IncorrectTokenProgramId,
+    )]
     pub token_b_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
+    /// Temporary wrapped SOL account of the user transfer authority, only
+    /// required when a wrapped SOL account of the user is omitted, in which
+    /// case the user transfer authority funds it and must be writable
+    #[account(
+        mut,
+        seeds = [NATIVE_SOL_SEED, user_transfer_authority.key().as_ref()],
+        bump,
+    )]
+    pub native_sol_account: Option<UncheckedAccount<'info>>,
 }
@@ -1,58 +1,119 @@
+#[cfg(feature = "invariant-check")]
+use crate::invariant::PoolValue;
 use {
-    crate::{curves::TradeDirection, helper::to_u64, SwapError, SwapState, SwapV1},
+    crate::{
+        curves::TradeDirection,
+        events::{emit_event, Deposited},
+        helper::{
+            split_hook_accounts, to_u64, transfer_checked_with_hook, transfer_fee,
+            UserTokenAccount, NATIVE_SOL_SEED,
+        },
+        instructions::{LOCKED_LIQUIDITY_SEED, MINIMUM_LIQUIDITY},
+        SwapError, SwapState, SwapV2,
+    },
     anchor_lang::prelude::*,
     anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface},
 };
 
-pub fn deposit_single_token_type_exact_amount_in_handler(
-    ctx: Context<DepositSingleTokenType>,
+/// Pool tokens minted by a single-sided deposit of `source_token_amount`,
+/// shared by `deposit_single_token_type_exact_amount_in` and
+/// `quote_deposit_single`.
+///
+/// The swap only receives what is left after the Token-2022 transfer fee of
+/// the source mint, so only that amount is credited. When the pool is empty,
+/// `MINIMUM_LIQUIDITY` of the new supply is locked and not minted to the user.
+pub fn calculate_deposit_single(
+    token_swap: &SwapV2,
+    pool_mint: &InterfaceAccount<Mint>,
+    source_token_mint: &InterfaceAccount<Mint>,
     source_token_amount: u64,
-    min_slippage_amount: u64,
-) -> Result<()> {
-    let swap_v1 = &ctx.accounts.swap_v1;
-    let swap_curve = ctx.accounts.swap_v1.swap_curve();
+    trade_direction: TradeDirection,
+) -> Result<u64> {
+    let swap_curve = token_swap.swap_curve();
     let calculator = swap_curve.calculator.as_ref();
     require!(
         calculator.allows_deposits(),
         SwapError::UnsupportedCurveOperation
     );
-    let trade_direction = if ctx.accounts.source.mint == ctx.accounts.swap_token_a.mint {
-        require_keys_neq!(
-            ctx.accounts.source.key(),
-            ctx.accounts.swap_token_a.key(),
-            SwapError::SameAccountTransfer
-        );
-        TradeDirection::AtoB
-    } else if ctx.accounts.source.mint == ctx.accounts.swap_token_b.mint {
-        require_keys_neq!(
-            ctx.accounts.source.key(),
-            ctx.accounts.swap_token_b.key(),
-            SwapError::SameAccountTransfer
-        );
-        TradeDirection::BtoA
-    } else {
-        return err!(SwapError::IncorrectSwapAccount);
-    };
+    let actual_source_token_amount =
+        source_token_amount.saturating_sub(transfer_fee(source_token_mint, source_token_amount)?);
 
-    let pool_mint_supply = ctx.accounts.pool_mint.supply;
+    let pool_mint_supply = pool_mint.supply;
     let pool_token_amount = if pool_mint_supply > 0 {
         swap_curve
             .deposit_single_token_type(
-                source_token_amount as u128,
-                ctx.accounts.swap_token_a.amount as u128,
-                ctx.accounts.swap_token_b.amount as u128,
+                actual_source_token_amount as u128,
+                token_swap.reserve_a as u128,
+                token_swap.reserve_b as u128,
                 pool_mint_supply as u128,
                 trade_direction,
-                swap_v1.fees(),
+                token_swap.fees(),
             )
             .ok_or(SwapError::ZeroTradingTokens)?
     } else {
-        calculator.new_pool_supply()
+        calculator
+            .new_pool_supply()
+            .checked_sub(u128::from(MINIMUM_LIQUIDITY))
+            .ok_or(SwapError::CalculationFailure)?
     };
     let pool_token_amount = to_u64(pool_token_amount)?;
     if pool_token_amount == 0 {
         return err!(SwapError::ZeroTradingTokens);
     }
+    Ok(pool_token_amount)
+}
+
+pub fn deposit_single_token_type_exact_amount_in_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositSingleTokenType<'info>>,
+    source_token_amount: u64,
+    min_slippage_amount: u64,
+) -> Result<()> {
+    ctx.accounts.token_swap.update_cumulative_prices()?;
+    let balances_before = (
+        ctx.accounts.swap_token_a.amount,
+        ctx.accounts.swap_token_b.amount,
+    );
+    #[cfg(feature = "invariant-check")]
+    let pool_value = PoolValue::new(
+        &ctx.accounts.token_swap,
+        balances_before,
+        ctx.accounts.pool_mint.supply,
+    )?;
+    let source = UserTokenAccount::new(
+        ctx.accounts.source.as_ref(),
+        ctx.accounts.native_sol_account.as_ref(),
+        ctx.bumps.native_sol_account,
+        &ctx.accounts.user_transfer_authority,
+        &ctx.accounts.source_token_mint,
+        &ctx.accounts.source_token_program,
+        &ctx.accounts.system_program,
+    )?;
+    let trade_direction = if ctx.accounts.source_token_mint.key() == ctx.accounts.swap_token_a.mint
+    {
+        require_keys_neq!(
+            source.key(),
+            ctx.accounts.swap_token_a.key(),
+            SwapError::SameAccountTransfer
+        );
+        TradeDirection::AtoB
+    } else if ctx.accounts.source_token_mint.key() == ctx.accounts.swap_token_b.mint {
+        require_keys_neq!(
+            source.key(),
+            ctx.accounts.swap_token_b.key(),
+            SwapError::SameAccountTransfer
+        );
+        TradeDirection::BtoA
+    } else {
+        return err!(SwapError::IncorrectSwapAccount);
+    };
+
+    let pool_token_amount = calculate_deposit_single(
+        &ctx.accounts.token_swap,
+        &ctx.accounts.pool_mint,
+        &ctx.accounts.source_token_mint,
+        source_token_amount,
+        trade_direction,
+    )?;
     if pool_token_amount < min_slippage_amount {
         return err!(SwapError::ExceededSlippage);
     }
@@ -61,20 +122,50 @@ pub fn deposit_single_token_type_exact_amount_in_handler(
         TradeDirection::BtoA => ctx.accounts.swap_token_b.to_account_info(),
     };
 
-    anchor_spl::token_interface::transfer_checked(
+    source.wrap(source_token_amount)?;
+    let mut remaining_accounts = ctx.remaining_accounts;
+    let source_hook_accounts = split_hook_accounts(
+        &ctx.accounts.source_token_mint.to_account_info(),
+        &mut remaining_accounts,
+    )?;
+    transfer_checked_with_hook(
         CpiContext::new(
             ctx.accounts.source_token_program.to_account_info(),
             anchor_spl::token_interface::TransferChecked {
-                from: ctx.accounts.source.to_account_info(),
+                from: source.to_account_info(),
                 to: to_swap_account_info,
                 authority: ctx.accounts.user_transfer_authority.to_account_info(),
                 mint: ctx.accounts.source_token_mint.to_account_info(),
             },
-        ),
+        )
+        .with_remaining_accounts(source_hook_accounts.to_vec()),
         source_token_amount,
         ctx.accounts.source_token_mint.decimals,
     )?;
+    source.unwrap()?;
 
+    if ctx.accounts.pool_mint.supply == 0 {
+        let locked_liquidity = ctx
+            .accounts
+            .locked_liquidity
+            .as_ref()
+            .ok_or(SwapError::MissingLockedLiquidity)?;
+        anchor_spl::token_interface::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_pool_program.to_account_info(),
+                anchor_spl::token_interface::MintTo {
+                    mint: ctx.accounts.pool_mint.to_account_info(),
+                    to: locked_liquidity.to_account_info(),
+                    authority: ctx.accounts.authority.to_account_info(),
+                },
+                &[&[
+                    &ctx.accounts.token_swap.key().to_bytes(),
+                    &[ctx.accounts.token_swap.bump_seed],
+                ]],
+            ),
+            MINIMUM_LIQUIDITY,
+        )?;
+    }
     anchor_spl::token_interface::mint_to(
         CpiContext::new_with_signer(
             ctx.accounts.token_pool_program.to_account_info(),
@@ -84,50 +175,96 @@ pub fn deposit_single_token_type_exact_amount_in_handler(
                 authority: ctx.accounts.authority.to_account_info(),
             },
             &[&[
-                &ctx.accounts.swap_v1.key().to_bytes(),
-                &[ctx.bumps.authority],
+                &ctx.accounts.token_swap.key().to_bytes(),
+                &[ctx.accounts.token_swap.bump_seed],
             ]],
         ),
         pool_token_amount,
     )?;
 
+    ctx.accounts.swap_token_a.reload()?;
+    ctx.accounts.swap_token_b.reload()?;
+    ctx.accounts.token_swap.update_reserves(
+        balances_before,
+        (
+            ctx.accounts.swap_token_a.amount,
+            ctx.accounts.swap_token_b.amount,
+        ),
+    )?;
+    #[cfg(feature = "invariant-check")]
+    {
+        ctx.accounts.pool_mint.reload()?;
+        pool_value.check(
+            &ctx.accounts.token_swap,
+            (
+                ctx.accounts.swap_token_a.amount,
+                ctx.accounts.swap_token_b.amount,
+            ),
+            ctx.accounts.pool_mint.supply,
+        )?;
+    }
+    let (token_a_amount, token_b_amount) = match trade_direction {
+        TradeDirection::AtoB => (source_token_amount, 0),
+        TradeDirection::BtoA => (0, source_token_amount),
+    };
+    emit_event!(
+        ctx,
+        Deposited {
+            token_swap: ctx.accounts.token_swap.key(),
+            user: ctx.accounts.user_transfer_authority.key(),
+            token_a_amount,
+            token_b_amount,
+            pool_tokens_minted: pool_token_amount,
+            reserve_a: ctx.accounts.token_swap.reserve_a,
+            reserve_b: ctx.accounts.token_swap.reserve_b,
+        }
+    );
     Ok(())
 }
 
+#[cfg_attr(feature = "event-cpi", event_cpi)]
 #[derive(Accounts)]
 pub struct DepositSingleTokenType<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
     #[account(
-    constraint = !swap_v1.to_account_info().data_is_empty() @ SwapError::IncorrectSwapAccount,
+    mut,
+    constraint = !token_swap.to_account_info().data_is_empty() @ SwapError::IncorrectSwapAccount,
+    constraint = token_swap.status.allows_single_deposits() @ SwapError::OperationPaused,
+    constraint = token_swap.flash_loan.is_none() @ SwapError::FlashLoanInProgress,
   )]
-    pub swap_v1: Account<'info, SwapV1>,
+    pub token_swap: Account<'info, SwapV2>,
     #[account(
-      seeds = [swap_v1.key().as_ref()],
-      bump,
+      seeds = [token_swap.key().as_ref()],
+      bump = token_swap.bump_seed,
   )]
     pub authority: AccountInfo<'info>,
     pub user_transfer_authority: Signer<'info>,
+    /// Omitted to deposit SOL of the user transfer authority, wrapped in
+    /// its temporary wrapped SOL account
     #[account(
         mut,
-        token::mint = source_token_mint.key()
+        token::mint = source_token_mint.key(),
+        constraint = source.key() != swap_token_a.key() @ SwapError::InvalidInput,
+        constraint = source.key() != swap_token_b.key() @ SwapError::InvalidInput,
     )]
-    pub source: InterfaceAccount<'info, TokenAccount>,
+    pub source: Option<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
         token::mint = swap_token_a.mint,
-        constraint = source.key() != swap_token_a.key() @ SwapError::InvalidInput
+        constraint = token_swap.token_a == swap_token_a.key() @ SwapError::IncorrectSwapAccount
     )]
     pub swap_token_a: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut,
         token::mint = swap_token_b.mint,
-        constraint = source.key() != swap_token_b.key() @ SwapError::InvalidInput
+        constraint = token_swap.token_b == swap_token_b.key() @ SwapError::IncorrectSwapAccount
     )]
     pub swap_token_b: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut,
         mint::token_program = token_pool_program.key(),
+        constraint = pool_mint.key() == token_swap.pool_mint @ SwapError::IncorrectPoolMint,
     )]
     pub pool_mint: InterfaceAccount<'info, Mint>,
     #[account(
@@ -135,12 +272,34 @@ pub struct DepositSingleTokenType<'info> {
       token::mint = pool_mint.key()
     )]
     pub pool_token_destination: InterfaceAccount<'info, TokenAccount>,
+    /// Only required when the pool supply is zero
+    #[account(
+      mut,
+      seeds = [LOCKED_LIQUIDITY_SEED, token_swap.key().as_ref()],
+      bump,
+    )]
+    pub locked_liquidity: Option<InterfaceAccount<'info, TokenAccount>>,
 
     #[account(
         mint::token_program = source_token_program.key(),
     )]
     pub source_token_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        constraint = token_swap.token_program(&source_token_mint.key()) == Some(&source_token_program.key()) @ SwapError::IncorrectTokenProgramId,
+    )]
     pub source_token_program: Interface<'info, TokenInterface>,
+    #[account(
+        constraint = token_pool_program.key() == token_swap.pool_token_program @ SwapError::IncorrectTokenProgramId,
+    )]
     pub token_pool_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
+    /// Temporary wrapped SOL account of the user transfer authority, only
+    /// required when a wrapped SOL account of the user is omitted, in which
+    /// case the user transfer authority funds it and must be writable
+    #[account(
+        mut,
+        seeds = [NATIVE_SOL_SEED, user_transfer_authority.key().as_ref()],
+        bump,
+    )]
+    pub native_sol_account: Option<UncheckedAccount<'info>>,
 }
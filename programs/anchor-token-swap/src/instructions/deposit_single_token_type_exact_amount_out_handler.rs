@@ -0,0 +1,188 @@
+#[cfg(feature = "invariant-check")]
+use crate::invariant::PoolValue;
+use {
+    crate::{
+        curves::TradeDirection,
+        events::{emit_event, Deposited},
+        helper::{
+            inverse_transfer_fee, split_hook_accounts, to_u64, transfer_checked_with_hook,
+            UserTokenAccount,
+        },
+        instructions::DepositSingleTokenType,
+        SwapError, SwapState,
+    },
+    anchor_lang::prelude::*,
+};
+
+pub fn deposit_single_token_type_exact_amount_out_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositSingleTokenType<'info>>,
+    pool_token_amount: u64,
+    maximum_source_amount: u64,
+) -> Result<()> {
+    ctx.accounts.token_swap.update_cumulative_prices()?;
+    let balances_before = (
+        ctx.accounts.swap_token_a.amount,
+        ctx.accounts.swap_token_b.amount,
+    );
+    #[cfg(feature = "invariant-check")]
+    let pool_value = PoolValue::new(
+        &ctx.accounts.token_swap,
+        balances_before,
+        ctx.accounts.pool_mint.supply,
+    )?;
+    let source = UserTokenAccount::new(
+        ctx.accounts.source.as_ref(),
+        ctx.accounts.native_sol_account.as_ref(),
+        ctx.bumps.native_sol_account,
+        &ctx.accounts.user_transfer_authority,
+        &ctx.accounts.source_token_mint,
+        &ctx.accounts.source_token_program,
+        &ctx.accounts.system_program,
+    )?;
+    let trade_direction = if ctx.accounts.source_token_mint.key() == ctx.accounts.swap_token_a.mint
+    {
+        require_keys_neq!(
+            source.key(),
+            ctx.accounts.swap_token_a.key(),
+            SwapError::SameAccountTransfer
+        );
+        TradeDirection::AtoB
+    } else if ctx.accounts.source_token_mint.key() == ctx.accounts.swap_token_b.mint {
+        require_keys_neq!(
+            source.key(),
+            ctx.accounts.swap_token_b.key(),
+            SwapError::SameAccountTransfer
+        );
+        TradeDirection::BtoA
+    } else {
+        return err!(SwapError::IncorrectSwapAccount);
+    };
+
+    let swap_curve = ctx.accounts.token_swap.swap_curve();
+    require!(
+        swap_curve.calculator.allows_deposits(),
+        SwapError::UnsupportedCurveOperation
+    );
+    require_neq!(pool_token_amount, 0, SwapError::ZeroTradingTokens);
+    let source_token_amount = swap_curve
+        .deposit_single_token_type_exact_out(
+            u128::from(pool_token_amount),
+            u128::from(ctx.accounts.token_swap.reserve_a),
+            u128::from(ctx.accounts.token_swap.reserve_b),
+            u128::from(ctx.accounts.pool_mint.supply),
+            trade_direction,
+            ctx.accounts.token_swap.fees(),
+        )
+        .ok_or(SwapError::ZeroTradingTokens)?;
+    // never mint more pool tokens than the deposit is worth, whatever the
+    // precision of the inverse calculation
+    let pool_tokens_minted = swap_curve
+        .deposit_single_token_type(
+            source_token_amount,
+            u128::from(ctx.accounts.token_swap.reserve_a),
+            u128::from(ctx.accounts.token_swap.reserve_b),
+            u128::from(ctx.accounts.pool_mint.supply),
+            trade_direction,
+            ctx.accounts.token_swap.fees(),
+        )
+        .ok_or(SwapError::ZeroTradingTokens)?;
+    require_gte!(
+        pool_tokens_minted,
+        u128::from(pool_token_amount),
+        SwapError::InsufficientDepositAmount
+    );
+    let source_token_amount = to_u64(source_token_amount)?;
+    require_neq!(source_token_amount, 0, SwapError::ZeroTradingTokens);
+    // the swap must receive the full amount, after any transfer fee of the
+    // source mint
+    let source_token_amount = source_token_amount
+        .checked_add(inverse_transfer_fee(
+            &ctx.accounts.source_token_mint,
+            source_token_amount,
+        )?)
+        .ok_or(SwapError::FeeCalculationFailure)?;
+    if source_token_amount > maximum_source_amount {
+        return err!(SwapError::ExceededSlippage);
+    }
+    let to_swap_account_info = match trade_direction {
+        TradeDirection::AtoB => ctx.accounts.swap_token_a.to_account_info(),
+        TradeDirection::BtoA => ctx.accounts.swap_token_b.to_account_info(),
+    };
+
+    source.wrap(source_token_amount)?;
+    let mut remaining_accounts = ctx.remaining_accounts;
+    let source_hook_accounts = split_hook_accounts(
+        &ctx.accounts.source_token_mint.to_account_info(),
+        &mut remaining_accounts,
+    )?;
+    transfer_checked_with_hook(
+        CpiContext::new(
+            ctx.accounts.source_token_program.to_account_info(),
+            anchor_spl::token_interface::TransferChecked {
+                from: source.to_account_info(),
+                to: to_swap_account_info,
+                authority: ctx.accounts.user_transfer_authority.to_account_info(),
+                mint: ctx.accounts.source_token_mint.to_account_info(),
+            },
+        )
+        .with_remaining_accounts(source_hook_accounts.to_vec()),
+        source_token_amount,
+        ctx.accounts.source_token_mint.decimals,
+    )?;
+    source.unwrap()?;
+
+    anchor_spl::token_interface::mint_to(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_pool_program.to_account_info(),
+            anchor_spl::token_interface::MintTo {
+                mint: ctx.accounts.pool_mint.to_account_info(),
+                to: ctx.accounts.pool_token_destination.to_account_info(),
+                authority: ctx.accounts.authority.to_account_info(),
+            },
+            &[&[
+                &ctx.accounts.token_swap.key().to_bytes(),
+                &[ctx.accounts.token_swap.bump_seed],
+            ]],
+        ),
+        pool_token_amount,
+    )?;
+
+    ctx.accounts.swap_token_a.reload()?;
+    ctx.accounts.swap_token_b.reload()?;
+    ctx.accounts.token_swap.update_reserves(
+        balances_before,
+        (
+            ctx.accounts.swap_token_a.amount,
+            ctx.accounts.swap_token_b.amount,
+        ),
+    )?;
+    #[cfg(feature = "invariant-check")]
+    {
+        ctx.accounts.pool_mint.reload()?;
+        pool_value.check(
+            &ctx.accounts.token_swap,
+            (
+                ctx.accounts.swap_token_a.amount,
+                ctx.accounts.swap_token_b.amount,
+            ),
+            ctx.accounts.pool_mint.supply,
+        )?;
+    }
+    let (token_a_amount, token_b_amount) = match trade_direction {
+        TradeDirection::AtoB => (source_token_amount, 0),
+        TradeDirection::BtoA => (0, source_token_amount),
+    };
+    emit_event!(
+        ctx,
+        Deposited {
+            token_swap: ctx.accounts.token_swap.key(),
+            user: ctx.accounts.user_transfer_authority.key(),
+            token_a_amount,
+            token_b_amount,
+            pool_tokens_minted: pool_token_amount,
+            reserve_a: ctx.accounts.token_swap.reserve_a,
+            reserve_b: ctx.accounts.token_swap.reserve_b,
+        }
+    );
+    Ok(())
+}
@@ -0,0 +1,238 @@
+use {
+    crate::{
+        events::{emit_event, FlashBorrowed},
+        helper::{split_hook_accounts, to_u64, transfer_checked_with_hook},
+        instruction, FlashLoan, SwapError, SwapV2,
+    },
+    anchor_lang::{
+        prelude::*,
+        solana_program::sysvar::instructions::{
+            load_current_index_checked, load_instruction_at_checked,
+        },
+        Discriminator,
+    },
+    anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+/// Check that a `flash_repay` of the swap is executed later in the
+/// transaction, it will fail unless the swap got its tokens back with the fee
+fn require_repay_follows(instructions: &AccountInfo, token_swap: &Pubkey) -> Result<()> {
+    let mut index = usize::from(load_current_index_checked(instructions)?);
+    loop {
+        index += 1;
+        let ix = load_instruction_at_checked(index, instructions)
+            .map_err(|_| SwapError::FlashLoanNotRepaid)?;
+        if ix.program_id == crate::ID
+            && ix.data.starts_with(instruction::FlashRepay::DISCRIMINATOR)
+            && ix.accounts.first().map(|meta| &meta.pubkey) == Some(token_swap)
+        {
+            return Ok(());
+        }
+    }
+}
+
+pub fn flash_borrow_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, FlashBorrow<'info>>,
+    amount: u64,
+) -> Result<()> {
+    require!(amount > 0, SwapError::ZeroTradingTokens);
+    require_repay_follows(&ctx.accounts.instructions, &ctx.accounts.token_swap.key())?;
+
+    let fee = ctx
+        .accounts
+        .token_swap
+        .fees
+        .trading_fee(u128::from(amount))
+        .ok_or(SwapError::FeeCalculationFailure)?;
+    let fee = to_u64(fee)?;
+    ctx.accounts.token_swap.flash_loan = Some(FlashLoan {
+        token_account: ctx.accounts.swap_token.key(),
+        amount,
+        fee,
+        reserve_before: ctx.accounts.swap_token.amount,
+    });
+
+    let mut remaining_accounts = ctx.remaining_accounts;
+    let hook_accounts = split_hook_accounts(
+        &ctx.accounts.token_mint.to_account_info(),
+        &mut remaining_accounts,
+    )?;
+    transfer_checked_with_hook(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            anchor_spl::token_interface::TransferChecked {
+                from: ctx.accounts.swap_token.to_account_info(),
+                to: ctx.accounts.destination.to_account_info(),
+                authority: ctx.accounts.authority.to_account_info(),
+                mint: ctx.accounts.token_mint.to_account_info(),
+            },
+            &[&[
+                &ctx.accounts.token_swap.key().to_bytes(),
+                &[ctx.accounts.token_swap.bump_seed],
+            ]],
+        )
+        .with_remaining_accounts(hook_accounts.to_vec()),
+        amount,
+        ctx.accounts.token_mint.decimals,
+    )?;
+
+    emit_event!(
+        ctx,
+        FlashBorrowed {
+            token_swap: ctx.accounts.token_swap.key(),
+            borrower: ctx.accounts.borrower.key(),
+            mint: ctx.accounts.token_mint.key(),
+            amount,
+            fee,
+        }
+    );
+    Ok(())
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct FlashBorrow<'info> {
+    #[account(
+        mut,
+        constraint = !token_swap.to_account_info().data_is_empty() @ SwapError::IncorrectSwapAccount,
+        constraint = token_swap.status.allows_swaps() @ SwapError::OperationPaused,
+        constraint = token_swap.flash_loan.is_none() @ SwapError::FlashLoanInProgress,
+    )]
+    pub token_swap: Account<'info, SwapV2>,
+    #[account(
+        seeds = [token_swap.key().as_ref()],
+        bump = token_swap.bump_seed,
+    )]
+    pub authority: AccountInfo<'info>,
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        token::mint = token_mint.key(),
+        token::token_program = token_program.key(),
+        constraint = (swap_token.key() == token_swap.token_a) || (swap_token.key() == token_swap.token_b)
+        @ SwapError::IncorrectSwapAccount,
+    )]
+    pub swap_token: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = token_mint.key(),
+        constraint = destination.key() != swap_token.key() @ SwapError::SameAccountTransfer
+    )]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mint::token_program = token_program.key(),
+    )]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        constraint = token_swap.token_program(&token_mint.key()) == Some(&token_program.key()) @ SwapError::IncorrectTokenProgramId,
+    )]
+    pub token_program: Interface<'info, TokenInterface>,
+    /// CHECK: instructions sysvar, used to find the matching `flash_repay`
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        anchor_lang::solana_program::sysvar::instructions::{
+            construct_instructions_data, store_current_index, BorrowedAccountMeta,
+            BorrowedInstruction, ID as INSTRUCTIONS_ID,
+        },
+    };
+
+    /// Run `require_repay_follows` for a `flash_borrow` of `token_swap`
+    /// followed by the given `(program_id, first account, data)` instructions
+    fn check_transaction(
+        token_swap: &Pubkey,
+        following: &[(Pubkey, Pubkey, Vec<u8>)],
+    ) -> Result<()> {
+        let borrow_data = instruction::FlashBorrow::DISCRIMINATOR.to_vec();
+        let mut instructions = vec![BorrowedInstruction {
+            program_id: &crate::ID,
+            accounts: vec![BorrowedAccountMeta {
+                pubkey: token_swap,
+                is_signer: false,
+                is_writable: true,
+            }],
+            data: &borrow_data,
+        }];
+        for (program_id, first_account, data) in following {
+            instructions.push(BorrowedInstruction {
+                program_id,
+                accounts: vec![BorrowedAccountMeta {
+                    pubkey: first_account,
+                    is_signer: false,
+                    is_writable: true,
+                }],
+                data,
+            });
+        }
+        let mut data = construct_instructions_data(&instructions);
+        store_current_index(&mut data, 0);
+        let mut lamports = 0;
+        let owner = Pubkey::default();
+        let account = AccountInfo::new(
+            &INSTRUCTIONS_ID,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &owner,
+            false,
+            0,
+        );
+        require_repay_follows(&account, token_swap)
+    }
+
+    fn repay_data() -> Vec<u8> {
+        instruction::FlashRepay::DISCRIMINATOR.to_vec()
+    }
+
+    #[test]
+    fn repay_of_the_swap_later_in_the_transaction() {
+        let token_swap = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        assert!(check_transaction(&token_swap, &[(crate::ID, token_swap, repay_data())]).is_ok());
+        // other instructions may run in between
+        assert!(check_transaction(
+            &token_swap,
+            &[
+                (other, token_swap, repay_data()),
+                (crate::ID, token_swap, vec![]),
+                (crate::ID, token_swap, repay_data()),
+            ]
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn missing_repay() {
+        let token_swap = Pubkey::new_unique();
+        assert_eq!(
+            check_transaction(&token_swap, &[]).unwrap_err(),
+            SwapError::FlashLoanNotRepaid.into()
+        );
+        // another instruction of the program is not a repayment
+        let swap_data = instruction::Swap::DISCRIMINATOR.to_vec();
+        assert_eq!(
+            check_transaction(&token_swap, &[(crate::ID, token_swap, swap_data)]).unwrap_err(),
+            SwapError::FlashLoanNotRepaid.into()
+        );
+    }
+
+    #[test]
+    fn repay_of_another_swap_or_program() {
+        let token_swap = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        assert_eq!(
+            check_transaction(&token_swap, &[(crate::ID, other, repay_data())]).unwrap_err(),
+            SwapError::FlashLoanNotRepaid.into()
+        );
+        assert_eq!(
+            check_transaction(&token_swap, &[(other, token_swap, repay_data())]).unwrap_err(),
+            SwapError::FlashLoanNotRepaid.into()
+        );
+    }
+}
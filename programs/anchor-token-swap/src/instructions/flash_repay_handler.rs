@@ -0,0 +1,120 @@
+use {
+    crate::{
+        events::{emit_event, FlashRepaid},
+        helper::{inverse_transfer_fee, split_hook_accounts, transfer_checked_with_hook},
+        SwapError, SwapV2,
+    },
+    anchor_lang::prelude::*,
+    anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+/// Repay the outstanding flash loan of the swap, transferring whatever is
+/// missing from the swap token account to get back to its balance before
+/// the loan plus the fee, grossed up by the Token-2022 transfer fee
+pub fn flash_repay_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, FlashRepay<'info>>,
+) -> Result<()> {
+    let flash_loan = ctx
+        .accounts
+        .token_swap
+        .flash_loan
+        .ok_or(SwapError::NoFlashLoan)?;
+    ctx.accounts.token_swap.update_cumulative_prices()?;
+    let reserve_after_repay = flash_loan
+        .reserve_after_repay()
+        .ok_or(SwapError::FeeCalculationFailure)?;
+
+    let owed = reserve_after_repay.saturating_sub(ctx.accounts.swap_token.amount);
+    let amount_repaid = owed
+        .checked_add(inverse_transfer_fee(&ctx.accounts.token_mint, owed)?)
+        .ok_or(SwapError::FeeCalculationFailure)?;
+    let mut remaining_accounts = ctx.remaining_accounts;
+    let hook_accounts = split_hook_accounts(
+        &ctx.accounts.token_mint.to_account_info(),
+        &mut remaining_accounts,
+    )?;
+    if amount_repaid > 0 {
+        transfer_checked_with_hook(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token_interface::TransferChecked {
+                    from: ctx.accounts.source.to_account_info(),
+                    to: ctx.accounts.swap_token.to_account_info(),
+                    authority: ctx.accounts.user_transfer_authority.to_account_info(),
+                    mint: ctx.accounts.token_mint.to_account_info(),
+                },
+            )
+            .with_remaining_accounts(hook_accounts.to_vec()),
+            amount_repaid,
+            ctx.accounts.token_mint.decimals,
+        )?;
+    }
+
+    ctx.accounts.swap_token.reload()?;
+    require_gte!(
+        ctx.accounts.swap_token.amount,
+        reserve_after_repay,
+        SwapError::FlashLoanNotRepaid
+    );
+    ctx.accounts.token_swap.flash_loan = None;
+    // the fee is the only part of the repayment that grows the reserve, any
+    // excess is left for `skim`
+    let token_swap = &mut ctx.accounts.token_swap;
+    let reserve = if ctx.accounts.swap_token.key() == token_swap.token_a {
+        &mut token_swap.reserve_a
+    } else {
+        &mut token_swap.reserve_b
+    };
+    *reserve = reserve
+        .checked_add(flash_loan.fee)
+        .ok_or(SwapError::CalculationFailure)?;
+    let reserve = *reserve;
+
+    emit_event!(
+        ctx,
+        FlashRepaid {
+            token_swap: ctx.accounts.token_swap.key(),
+            user: ctx.accounts.user_transfer_authority.key(),
+            mint: ctx.accounts.token_mint.key(),
+            amount_repaid,
+            fee: flash_loan.fee,
+            reserve,
+        }
+    );
+    Ok(())
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct FlashRepay<'info> {
+    /// Must stay the first account, `flash_borrow` looks it up in the
+    /// instruction to find the repayment
+    #[account(
+        mut,
+        constraint = !token_swap.to_account_info().data_is_empty() @ SwapError::IncorrectSwapAccount,
+    )]
+    pub token_swap: Account<'info, SwapV2>,
+    pub user_transfer_authority: Signer<'info>,
+    #[account(
+        mut,
+        token::mint = token_mint.key(),
+        token::token_program = token_program.key(),
+        constraint = token_swap.flash_loan.is_some_and(|loan| loan.token_account == swap_token.key())
+        @ SwapError::NoFlashLoan,
+    )]
+    pub swap_token: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = token_mint.key(),
+        constraint = source.key() != swap_token.key() @ SwapError::SameAccountTransfer
+    )]
+    pub source: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mint::token_program = token_program.key(),
+    )]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        constraint = token_swap.token_program(&token_mint.key()) == Some(&token_program.key()) @ SwapError::IncorrectTokenProgramId,
+    )]
+    pub token_program: Interface<'info, TokenInterface>,
+}
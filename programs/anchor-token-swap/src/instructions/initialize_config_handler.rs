@@ -0,0 +1,49 @@
+use {
+    crate::{
+        events::{emit_event, ConfigUpdated},
+        program::AnchorTokenSwap,
+        SwapConfig, SwapConstraints, SwapError,
+    },
+    anchor_lang::prelude::*,
+};
+
+/// Seed of the config account of the program
+pub const CONFIG_SEED: &[u8] = b"config";
+
+pub fn initialize_config_handler(
+    ctx: Context<InitializeConfig>,
+    constraints: Option<SwapConstraints>,
+) -> Result<()> {
+    if let Some(constraints) = &constraints {
+        constraints.validate()?;
+    }
+    ctx.accounts.config.constraints = constraints.clone();
+
+    emit_event!(ctx, ConfigUpdated { constraints });
+    Ok(())
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = SwapConfig::INIT_SPACE + 8,
+        seeds = [CONFIG_SEED],
+        bump,
+    )]
+    pub config: Account<'info, SwapConfig>,
+    pub upgrade_authority: Signer<'info>,
+    #[account(
+        constraint = swap_program.programdata_address()? == Some(program_data.key()) @ SwapError::InvalidUpgradeAuthority
+    )]
+    pub swap_program: Program<'info, AnchorTokenSwap>,
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(upgrade_authority.key()) @ SwapError::InvalidUpgradeAuthority
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    pub system_program: Program<'info, System>,
+}
@@ -0,0 +1,26 @@
+use {crate::Factory, anchor_lang::prelude::*};
+
+/// Seed of the factory account
+pub const FACTORY_SEED: &[u8] = b"factory";
+/// Seed of the registry entries, along with their index
+pub const POOL_ENTRY_SEED: &[u8] = b"pool_entry";
+
+pub fn initialize_factory_handler(ctx: Context<InitializeFactory>) -> Result<()> {
+    ctx.accounts.factory.pool_count = 0;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitializeFactory<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = Factory::INIT_SPACE + 8,
+        seeds = [FACTORY_SEED],
+        bump,
+    )]
+    pub factory: Account<'info, Factory>,
+    pub system_program: Program<'info, System>,
+}
@@ -1,27 +1,117 @@
 use {
     crate::{
         curves::{CurveType, SwapCurve},
-        helper::to_u64,
-        Fees, SwapError, SwapV1,
+        events::{emit_event, PoolInitialized},
+        helper::{split_hook_accounts, to_u64, transfer_checked_with_hook},
+        instructions::{CONFIG_SEED, FACTORY_SEED, POOL_ENTRY_SEED},
+        CumulativePrices, Factory, Fees, PoolEntry, PoolStatus, SwapConfig, SwapError, SwapV2,
     },
     anchor_lang::prelude::*,
-    anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface},
+    anchor_spl::{
+        associated_token::AssociatedToken,
+        token_interface::{Mint, TokenAccount, TokenInterface},
+    },
 };
 
-pub fn initialize_handler(
-    ctx: Context<Initialize>,
+/// Seed of the swap account, along with the sorted mints, the seed of the
+/// curve type and the fee tier
+pub const SWAP_SEED: &[u8] = b"swap";
+/// Seed of the swap token accounts, along with the swap and their mint
+pub const VAULT_SEED: &[u8] = b"vault";
+/// Seed of the pool token mint, along with the swap
+pub const POOL_MINT_SEED: &[u8] = b"pool_mint";
+/// Decimals of the pool token mint
+pub const POOL_MINT_DECIMALS: u8 = 2;
+/// Seed of the pool token account holding the locked liquidity, along with
+/// the swap
+pub const LOCKED_LIQUIDITY_SEED: &[u8] = b"locked_liquidity";
+/// Pool tokens locked forever whenever the supply of a pool starts from
+/// zero, as Uniswap v2 does, so that a first depositor cannot inflate the
+/// value of a pool token by leaving a dust supply behind
+pub const MINIMUM_LIQUIDITY: u64 = 1_000;
+
+pub fn initialize_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, Initialize<'info>>,
     curve_type: CurveType,
     fees: Fees,
+    fee_tier: u16,
+    initial_token_a_amount: u64,
+    initial_token_b_amount: u64,
 ) -> Result<()> {
     let swap_curve = SwapCurve::new(curve_type);
     let calculator = &swap_curve.calculator;
     fees.validate()?;
+    require!(
+        fees.trade_fee_bps() == Some(fee_tier),
+        SwapError::InvalidFee
+    );
     calculator.validate()?;
+    // the pool mint of a closed swap is reused
+    require_eq!(ctx.accounts.pool_mint.supply, 0, SwapError::InvalidSupply);
+
+    let mut remaining_accounts = ctx.remaining_accounts;
+    let token_a_hook_accounts = split_hook_accounts(
+        &ctx.accounts.token_a_mint.to_account_info(),
+        &mut remaining_accounts,
+    )?;
+    let token_b_hook_accounts = split_hook_accounts(
+        &ctx.accounts.token_b_mint.to_account_info(),
+        &mut remaining_accounts,
+    )?;
+    transfer_checked_with_hook(
+        CpiContext::new(
+            ctx.accounts.token_a_program.to_account_info(),
+            anchor_spl::token_interface::TransferChecked {
+                from: ctx.accounts.user_token_a.to_account_info(),
+                to: ctx.accounts.swap_token_a.to_account_info(),
+                authority: ctx.accounts.user_transfer_authority.to_account_info(),
+                mint: ctx.accounts.token_a_mint.to_account_info(),
+            },
+        )
+        .with_remaining_accounts(token_a_hook_accounts.to_vec()),
+        initial_token_a_amount,
+        ctx.accounts.token_a_mint.decimals,
+    )?;
+    transfer_checked_with_hook(
+        CpiContext::new(
+            ctx.accounts.token_b_program.to_account_info(),
+            anchor_spl::token_interface::TransferChecked {
+                from: ctx.accounts.user_token_b.to_account_info(),
+                to: ctx.accounts.swap_token_b.to_account_info(),
+                authority: ctx.accounts.user_transfer_authority.to_account_info(),
+                mint: ctx.accounts.token_b_mint.to_account_info(),
+            },
+        )
+        .with_remaining_accounts(token_b_hook_accounts.to_vec()),
+        initial_token_b_amount,
+        ctx.accounts.token_b_mint.decimals,
+    )?;
+    ctx.accounts.swap_token_a.reload()?;
+    ctx.accounts.swap_token_b.reload()?;
+
     calculator.validate_supply(
         ctx.accounts.swap_token_a.amount,
         ctx.accounts.swap_token_b.amount,
     )?;
-    let initial_amount = swap_curve.calculator.new_pool_supply();
+    let initial_amount = to_u64(swap_curve.calculator.new_pool_supply())?;
+    let user_amount = initial_amount
+        .checked_sub(MINIMUM_LIQUIDITY)
+        .ok_or(SwapError::CalculationFailure)?;
+    anchor_spl::token_interface::mint_to(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_pool_program.to_account_info(),
+            anchor_spl::token_interface::MintTo {
+                mint: ctx.accounts.pool_mint.to_account_info(),
+                to: ctx.accounts.locked_liquidity.to_account_info(),
+                authority: ctx.accounts.authority.to_account_info(),
+            },
+            &[&[
+                &ctx.accounts.token_swap.key().to_bytes(),
+                &[ctx.bumps.authority],
+            ]],
+        ),
+        MINIMUM_LIQUIDITY,
+    )?;
     anchor_spl::token_interface::mint_to(
         CpiContext::new_with_signer(
             ctx.accounts.token_pool_program.to_account_info(),
@@ -31,76 +121,209 @@ pub fn initialize_handler(
                 authority: ctx.accounts.authority.to_account_info(),
             },
             &[&[
-                &ctx.accounts.swap_v1.key().to_bytes(),
+                &ctx.accounts.token_swap.key().to_bytes(),
                 &[ctx.bumps.authority],
             ]],
         ),
-        to_u64(initial_amount)?,
+        user_amount,
     )?;
 
-    *ctx.accounts.swap_v1 = SwapV1 {
-        token_a: *ctx.accounts.swap_token_a.to_account_info().key,
-        token_b: *ctx.accounts.swap_token_b.to_account_info().key,
-        pool_mint: *ctx.accounts.pool_mint.to_account_info().key,
-        token_a_mint: ctx.accounts.swap_token_a.mint,
-        token_b_mint: ctx.accounts.swap_token_b.mint,
-        pool_fee_account: *ctx.accounts.pool_fee_account.to_account_info().key,
-        fees,
+    *ctx.accounts.token_swap = SwapV2 {
+        bump_seed: ctx.bumps.authority,
+        token_a: ctx.accounts.swap_token_a.key(),
+        token_b: ctx.accounts.swap_token_b.key(),
+        pool_mint: ctx.accounts.pool_mint.key(),
+        token_a_mint: ctx.accounts.token_a_mint.key(),
+        token_b_mint: ctx.accounts.token_b_mint.key(),
+        token_a_program: ctx.accounts.token_a_program.key(),
+        token_b_program: ctx.accounts.token_b_program.key(),
+        pool_token_program: ctx.accounts.token_pool_program.key(),
+        pool_fee_account: ctx.accounts.pool_fee_account.key(),
+        fees: fees.clone(),
         curve_type,
+        fee_tier,
+        admin: ctx.accounts.payer.key(),
+        status: PoolStatus::Active,
+        flash_loan: None,
+        cumulative_prices: CumulativePrices {
+            last_update_timestamp: Clock::get()?.unix_timestamp,
+            ..Default::default()
+        },
+        reserve_a: ctx.accounts.swap_token_a.amount,
+        reserve_b: ctx.accounts.swap_token_b.amount,
+        pool_entry_index: Some(ctx.accounts.factory.pool_count),
+        reserved: [0; 103],
     };
 
+    let factory = &mut ctx.accounts.factory;
+    **ctx.accounts.pool_entry = PoolEntry {
+        index: factory.pool_count,
+        token_swap: ctx.accounts.token_swap.key(),
+        token_a_mint: ctx.accounts.token_a_mint.key(),
+        token_b_mint: ctx.accounts.token_b_mint.key(),
+        curve_type,
+        fee_tier,
+        created_slot: Clock::get()?.slot,
+    };
+    factory.pool_count = factory
+        .pool_count
+        .checked_add(1)
+        .ok_or(SwapError::CalculationFailure)?;
+
+    emit_event!(
+        ctx,
+        PoolInitialized {
+            token_swap: ctx.accounts.token_swap.key(),
+            admin: ctx.accounts.payer.key(),
+            pool_mint: ctx.accounts.pool_mint.key(),
+            token_a_mint: ctx.accounts.token_a_mint.key(),
+            token_b_mint: ctx.accounts.token_b_mint.key(),
+            curve_type,
+            fees,
+            fee_tier,
+            pool_tokens_minted: user_amount,
+            reserve_a: ctx.accounts.swap_token_a.amount,
+            reserve_b: ctx.accounts.swap_token_b.amount,
+        }
+    );
     Ok(())
 }
 
+#[cfg_attr(feature = "event-cpi", event_cpi)]
 #[derive(Accounts)]
+#[instruction(curve_type: CurveType, fees: Fees, fee_tier: u16)]
 pub struct Initialize<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump,
+    )]
+    pub config: Box<Account<'info, SwapConfig>>,
+    #[account(
+        mint::token_program = token_a_program.key(),
+    )]
+    pub token_a_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mint::token_program = token_b_program.key(),
+        constraint = token_b_mint.key() != token_a_mint.key() @ SwapError::RepeatedMint,
+        constraint = token_a_mint.key() < token_b_mint.key() @ SwapError::UnsortedMints,
+    )]
+    pub token_b_mint: Box<InterfaceAccount<'info, Mint>>,
     #[account(
         init,
         payer = payer,
-        space = SwapV1::INIT_SPACE + 8
+        space = SwapV2::INIT_SPACE + 8,
+        seeds = [
+            SWAP_SEED,
+            token_a_mint.key().as_ref(),
+            token_b_mint.key().as_ref(),
+            &[curve_type.discriminant()],
+            &fee_tier.to_le_bytes(),
+        ],
+        bump,
     )]
-    pub swap_v1: Account<'info, SwapV1>,
+    pub token_swap: Account<'info, SwapV2>,
     #[account(
-        seeds = [swap_v1.key().as_ref()],
+        seeds = [token_swap.key().as_ref()],
         bump,
     )]
     pub authority: AccountInfo<'info>,
     #[account(
-      constraint = swap_token_a.delegate.is_none() @ SwapError::InvalidDelegate,
-        constraint = swap_token_a.mint != swap_token_b.mint @ SwapError::RepeatedMint,
-        constraint = swap_token_a.owner == authority.key() @ SwapError::InvalidOwner,
-        constraint = swap_token_a.close_authority.is_none() @ SwapError::InvalidCloseAuthority,
+        init,
+        payer = payer,
+        seeds = [VAULT_SEED, token_swap.key().as_ref(), token_a_mint.key().as_ref()],
+        bump,
+        token::mint = token_a_mint,
+        token::authority = authority,
+        token::token_program = token_a_program,
     )]
-    pub swap_token_a: InterfaceAccount<'info, TokenAccount>,
+    pub swap_token_a: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
-        constraint = swap_token_b.delegate.is_none() @ SwapError::InvalidDelegate,
-        constraint = swap_token_b.owner == authority.key() @ SwapError::InvalidOwner,
-        constraint = swap_token_b.close_authority.is_none() @ SwapError::InvalidCloseAuthority,
+        init,
+        payer = payer,
+        seeds = [VAULT_SEED, token_swap.key().as_ref(), token_b_mint.key().as_ref()],
+        bump,
+        token::mint = token_b_mint,
+        token::authority = authority,
+        token::token_program = token_b_program,
     )]
-    pub swap_token_b: InterfaceAccount<'info, TokenAccount>,
-
+    pub swap_token_b: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// Left behind by `close_pool` when the swap is created again
+    #[account(
+        init_if_needed,
+        payer = payer,
+        seeds = [POOL_MINT_SEED, token_swap.key().as_ref()],
+        bump,
+        mint::decimals = POOL_MINT_DECIMALS,
+        mint::authority = authority,
+        mint::token_program = token_pool_program,
+    )]
+    pub pool_mint: Box<InterfaceAccount<'info, Mint>>,
+    /// Owned by the swap authority, which only burns its tokens to close the
+    /// swap
+    #[account(
+        init,
+        payer = payer,
+        seeds = [LOCKED_LIQUIDITY_SEED, token_swap.key().as_ref()],
+        bump,
+        token::mint = pool_mint,
+        token::authority = authority,
+        token::token_program = token_pool_program,
+    )]
+    pub locked_liquidity: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
-        mint::authority = authority.key(),
-        mint::token_program = token_pool_program.key(),
-        constraint = pool_mint.supply == 0 @ SwapError::InvalidSupply,
-        constraint = pool_mint.freeze_authority.is_none() @ SwapError::InvalidFreezeAuthority,
+        seeds = [FACTORY_SEED],
+        bump,
+    )]
+    pub factory: Box<Account<'info, Factory>>,
+    #[account(
+        init,
+        payer = payer,
+        space = PoolEntry::INIT_SPACE + 8,
+        seeds = [POOL_ENTRY_SEED, &factory.pool_count.to_le_bytes()],
+        bump,
     )]
-    pub pool_mint: InterfaceAccount<'info, Mint>,
+    pub pool_entry: Box<Account<'info, PoolEntry>>,
+
+    pub user_transfer_authority: Signer<'info>,
     #[account(
         mut,
-        token::mint = pool_mint.key(),
-        constraint = user_pool_token.owner != authority.key() @ SwapError::InvalidOwner
+        token::mint = token_a_mint,
+        token::token_program = token_a_program,
     )]
-    pub user_pool_token: InterfaceAccount<'info, TokenAccount>,
+    pub user_token_a: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
-        token::mint = pool_mint.key(),
-        constraint = pool_fee_account.owner != authority.key() @ SwapError::InvalidOwner
+        token::mint = token_b_mint,
+        token::token_program = token_b_program,
     )]
-    pub pool_fee_account: InterfaceAccount<'info, TokenAccount>,
-    #[account(mut)]
-    pub payer: Signer<'info>,
+    pub user_token_b: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = pool_mint,
+        associated_token::authority = user_transfer_authority,
+        associated_token::token_program = token_pool_program,
+    )]
+    pub user_pool_token: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: owner of the pool fee account, checked against the swap
+    /// constraints
+    pub pool_fee_owner: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = pool_mint,
+        associated_token::authority = pool_fee_owner,
+        associated_token::token_program = token_pool_program,
+        constraint = pool_fee_owner.key() != authority.key() @ SwapError::InvalidOwner
+    )]
+    pub pool_fee_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_a_program: Interface<'info, TokenInterface>,
+    pub token_b_program: Interface<'info, TokenInterface>,
     pub token_pool_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
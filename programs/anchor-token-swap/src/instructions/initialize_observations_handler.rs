@@ -0,0 +1,35 @@
+use {
+    crate::{Observation, Observations, SwapError, SwapV2},
+    anchor_lang::prelude::*,
+};
+
+/// Seed of the observations account of a swap, along with the swap address
+pub const OBSERVATIONS_SEED: &[u8] = b"observations";
+
+pub fn initialize_observations_handler(ctx: Context<InitializeObservations>) -> Result<()> {
+    let observations = &mut ctx.accounts.observations;
+    observations.token_swap = ctx.accounts.token_swap.key();
+    observations.index = 0;
+    observations.observations.fill(Observation::default());
+    observations.observations[0] = ctx.accounts.token_swap.cumulative_prices.into();
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitializeObservations<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        constraint = !token_swap.to_account_info().data_is_empty() @ SwapError::IncorrectSwapAccount,
+    )]
+    pub token_swap: Account<'info, SwapV2>,
+    #[account(
+        init,
+        payer = payer,
+        space = Observations::INIT_SPACE + 8,
+        seeds = [OBSERVATIONS_SEED, token_swap.key().as_ref()],
+        bump,
+    )]
+    pub observations: Box<Account<'info, Observations>>,
+    pub system_program: Program<'info, System>,
+}
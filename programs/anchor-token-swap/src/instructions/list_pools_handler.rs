@@ -0,0 +1,64 @@
+use {
+    crate::{
+        instructions::{FACTORY_SEED, POOL_ENTRY_SEED},
+        Factory, PoolEntry, SwapError,
+    },
+    anchor_lang::prelude::*,
+};
+
+/// Maximum number of entries returned by `list_pools`, bounded by the size
+/// of the return data
+pub const MAX_POOLS_PER_PAGE: usize = 7;
+
+/// Page of the registry, returned through the return data
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PoolPage {
+    /// Total number of swaps in the registry
+    pub pool_count: u64,
+    /// Entries of the page, in registry order, without the closed swaps
+    pub entries: Vec<PoolEntry>,
+}
+
+/// List the registry entries passed as remaining accounts, which must be
+/// consecutive starting at `start_index`. The entries closed by `close_pool`
+/// are left out.
+pub fn list_pools_handler<'info>(
+    ctx: Context<'_, '_, 'info, 'info, ListPools<'info>>,
+    start_index: u64,
+) -> Result<PoolPage> {
+    require!(
+        ctx.remaining_accounts.len() <= MAX_POOLS_PER_PAGE,
+        SwapError::InvalidInput
+    );
+    let entries = ctx
+        .remaining_accounts
+        .iter()
+        .zip(start_index..)
+        .filter_map(|(account, index)| {
+            if account.owner == &System::id() {
+                let (closed_entry, _) = Pubkey::find_program_address(
+                    &[POOL_ENTRY_SEED, &index.to_le_bytes()],
+                    &crate::ID,
+                );
+                return (account.key() != closed_entry).then(|| err!(SwapError::InvalidInput));
+            }
+            Some(Account::<PoolEntry>::try_from(account).and_then(|entry| {
+                require_eq!(entry.index, index, SwapError::InvalidInput);
+                Ok(entry.into_inner())
+            }))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(PoolPage {
+        pool_count: ctx.accounts.factory.pool_count,
+        entries,
+    })
+}
+
+#[derive(Accounts)]
+pub struct ListPools<'info> {
+    #[account(
+        seeds = [FACTORY_SEED],
+        bump,
+    )]
+    pub factory: Account<'info, Factory>,
+}
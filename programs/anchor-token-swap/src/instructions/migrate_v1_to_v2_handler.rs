@@ -0,0 +1,94 @@
+use {
+    crate::{LegacySwapV1, SwapError, SwapV2},
+    anchor_lang::{prelude::*, system_program},
+    anchor_spl::token_interface::{Mint, TokenAccount},
+};
+
+/// Rewrite a first version swap into the `SwapV2` layout, growing the account
+/// and topping up its rent from the payer. Anyone may migrate a swap, the new
+/// fields are all derived from the swap itself, its admin being the owner of
+/// its pool fee account.
+pub fn migrate_v1_to_v2_handler(ctx: Context<MigrateV1ToV2>) -> Result<()> {
+    let token_swap = ctx.accounts.token_swap.to_account_info();
+    let swap_v1 = LegacySwapV1::try_deserialize(&mut &token_swap.try_borrow_data()?[..])?;
+    require_keys_eq!(
+        ctx.accounts.swap_token_a.key(),
+        swap_v1.token_a,
+        SwapError::IncorrectSwapAccount
+    );
+    require_keys_eq!(
+        ctx.accounts.swap_token_b.key(),
+        swap_v1.token_b,
+        SwapError::IncorrectSwapAccount
+    );
+    require_keys_eq!(
+        ctx.accounts.pool_mint.key(),
+        swap_v1.pool_mint,
+        SwapError::IncorrectPoolMint
+    );
+    require_keys_eq!(
+        ctx.accounts.pool_fee_account.key(),
+        swap_v1.pool_fee_account,
+        SwapError::InvalidFeeAccount
+    );
+    let (_, bump_seed) = Pubkey::find_program_address(&[token_swap.key.as_ref()], &crate::ID);
+    let swap_v2 = SwapV2::from_v1(
+        swap_v1,
+        bump_seed,
+        ctx.accounts.pool_fee_account.owner,
+        (
+            *ctx.accounts.swap_token_a.to_account_info().owner,
+            *ctx.accounts.swap_token_b.to_account_info().owner,
+            *ctx.accounts.pool_mint.to_account_info().owner,
+        ),
+        (
+            ctx.accounts.swap_token_a.amount,
+            ctx.accounts.swap_token_b.amount,
+        ),
+        Clock::get()?.unix_timestamp,
+    );
+
+    let space = SwapV2::INIT_SPACE + 8;
+    let lamports = Rent::get()?
+        .minimum_balance(space)
+        .saturating_sub(token_swap.lamports());
+    if lamports > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.payer.to_account_info(),
+                    to: token_swap.clone(),
+                },
+            ),
+            lamports,
+        )?;
+    }
+    token_swap.realloc(space, true)?;
+    swap_v2.try_serialize(&mut &mut token_swap.try_borrow_mut_data()?[..])?;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct MigrateV1ToV2<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: deserialized as a `LegacySwapV1` by the handler, which checks
+    /// its discriminator
+    #[account(
+        mut,
+        owner = crate::ID @ SwapError::IncorrectSwapAccount,
+    )]
+    pub token_swap: UncheckedAccount<'info>,
+    /// Token A account of the swap, its owner is stored as the token A
+    /// program and its balance as the reserve of token A
+    pub swap_token_a: InterfaceAccount<'info, TokenAccount>,
+    /// Token B account of the swap, its owner is stored as the token B
+    /// program and its balance as the reserve of token B
+    pub swap_token_b: InterfaceAccount<'info, TokenAccount>,
+    /// Pool mint of the swap, its owner is stored as the pool token program
+    pub pool_mint: InterfaceAccount<'info, Mint>,
+    /// Pool fee account of the swap, its owner becomes the admin
+    pub pool_fee_account: InterfaceAccount<'info, TokenAccount>,
+    pub system_program: Program<'info, System>,
+}
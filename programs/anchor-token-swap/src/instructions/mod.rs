@@ -1,12 +1,43 @@
+mod close_pool_handler;
 mod deposit_all_token_types_handler;
 mod deposit_single_token_type_exact_amount_in_handler;
+mod deposit_single_token_type_exact_amount_out_handler;
+mod flash_borrow_handler;
+mod flash_repay_handler;
+mod initialize_config_handler;
+mod initialize_factory_handler;
 mod initialize_handler;
+mod initialize_observations_handler;
+mod list_pools_handler;
+mod migrate_v1_to_v2_handler;
+mod quote_deposit_single_handler;
+mod quote_swap_handler;
+mod quote_withdraw_single_handler;
+mod record_observation_handler;
+mod route_swap_handler;
+mod set_admin_handler;
+mod set_pool_status_handler;
+mod skim_handler;
+mod swap_exact_amount_out_handler;
 mod swap_handler;
+mod sync_handler;
+mod update_config_handler;
+mod update_fees_handler;
 mod withdraw_all_token_types_handler;
+mod withdraw_single_token_type_exact_amount_in_handler;
 mod withdraw_single_token_type_exact_amount_out_handler;
 
 pub use {
-    deposit_all_token_types_handler::*, deposit_single_token_type_exact_amount_in_handler::*,
-    initialize_handler::*, swap_handler::*, withdraw_all_token_types_handler::*,
+    close_pool_handler::*, deposit_all_token_types_handler::*,
+    deposit_single_token_type_exact_amount_in_handler::*,
+    deposit_single_token_type_exact_amount_out_handler::*, flash_borrow_handler::*,
+    flash_repay_handler::*, initialize_config_handler::*, initialize_factory_handler::*,
+    initialize_handler::*, initialize_observations_handler::*, list_pools_handler::*,
+    migrate_v1_to_v2_handler::*, quote_deposit_single_handler::*, quote_swap_handler::*,
+    quote_withdraw_single_handler::*, record_observation_handler::*, route_swap_handler::*,
+    set_admin_handler::*, set_pool_status_handler::*, skim_handler::*,
+    swap_exact_amount_out_handler::*, swap_handler::*, sync_handler::*, update_config_handler::*,
+    update_fees_handler::*, withdraw_all_token_types_handler::*,
+    withdraw_single_token_type_exact_amount_in_handler::*,
     withdraw_single_token_type_exact_amount_out_handler::*,
 };
@@ -0,0 +1,50 @@
+use {
+    crate::{curves::TradeDirection, instructions::calculate_deposit_single, SwapError, SwapV2},
+    anchor_lang::prelude::*,
+    anchor_spl::token_interface::Mint,
+};
+
+/// Result of `quote_deposit_single`, returned through the return data
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct DepositSingleQuote {
+    /// Pool tokens minted to the user
+    pub pool_token_amount: u64,
+}
+
+pub fn quote_deposit_single_handler(
+    ctx: Context<QuoteDepositSingle>,
+    source_token_amount: u64,
+) -> Result<DepositSingleQuote> {
+    let trade_direction =
+        if ctx.accounts.source_token_mint.key() == ctx.accounts.token_swap.token_a_mint {
+            TradeDirection::AtoB
+        } else if ctx.accounts.source_token_mint.key() == ctx.accounts.token_swap.token_b_mint {
+            TradeDirection::BtoA
+        } else {
+            return err!(SwapError::IncorrectSwapAccount);
+        };
+    let pool_token_amount = calculate_deposit_single(
+        &ctx.accounts.token_swap,
+        &ctx.accounts.pool_mint,
+        &ctx.accounts.source_token_mint,
+        source_token_amount,
+        trade_direction,
+    )?;
+    Ok(DepositSingleQuote { pool_token_amount })
+}
+
+#[derive(Accounts)]
+pub struct QuoteDepositSingle<'info> {
+    #[account(
+        constraint = !token_swap.to_account_info().data_is_empty() @ SwapError::IncorrectSwapAccount,
+        constraint = token_swap.status.allows_single_deposits() @ SwapError::OperationPaused,
+        constraint = token_swap.flash_loan.is_none() @ SwapError::FlashLoanInProgress,
+    )]
+    pub token_swap: Account<'info, SwapV2>,
+    #[account(
+        constraint = pool_mint.key() == token_swap.pool_mint @ SwapError::IncorrectPoolMint
+    )]
+    pub pool_mint: InterfaceAccount<'info, Mint>,
+    pub source_token_mint: InterfaceAccount<'info, Mint>,
+}
@@ -0,0 +1,70 @@
+use {
+    crate::{
+        helper::to_u64,
+        instructions::{calculate_swap, SwapCalculation},
+        SwapError, SwapV2,
+    },
+    anchor_lang::prelude::*,
+    anchor_spl::token_interface::{Mint, TokenAccount},
+};
+
+/// Result of `quote_swap`, returned through the return data
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct SwapQuote {
+    /// Amount of source token transferred from the user
+    pub amount_in: u64,
+    /// Amount of destination token received by the user, after any transfer
+    /// fee
+    pub amount_out: u64,
+    /// Trade fee in source token
+    pub trade_fee: u64,
+    /// Owner trade fee in source token
+    pub owner_fee: u64,
+}
+
+pub fn quote_swap_handler(ctx: Context<QuoteSwap>, amount_in: u64) -> Result<SwapQuote> {
+    let SwapCalculation {
+        result,
+        source_transfer_amount,
+        amount_received,
+        ..
+    } = calculate_swap(
+        &ctx.accounts.token_swap,
+        &ctx.accounts.swap_source,
+        &ctx.accounts.source_token_mint,
+        &ctx.accounts.destination_token_mint,
+        amount_in,
+    )?;
+    Ok(SwapQuote {
+        amount_in: source_transfer_amount,
+        amount_out: amount_received,
+        trade_fee: to_u64(result.trade_fee)?,
+        owner_fee: to_u64(result.owner_fee)?,
+    })
+}
+
+#[derive(Accounts)]
+pub struct QuoteSwap<'info> {
+    #[account(
+        constraint = !token_swap.to_account_info().data_is_empty() @ SwapError::IncorrectSwapAccount,
+        constraint = token_swap.status.allows_swaps() @ SwapError::OperationPaused,
+        constraint = token_swap.flash_loan.is_none() @ SwapError::FlashLoanInProgress,
+    )]
+    pub token_swap: Account<'info, SwapV2>,
+    #[account(
+        token::mint = source_token_mint.key(),
+        constraint = (swap_source.key() == token_swap.token_a.key()) || (swap_source.key() == token_swap.token_b.key())
+        @ SwapError::IncorrectSwapAccount,
+        constraint = swap_source.key() != swap_destination.key() @ SwapError::SameAccountTransfer
+    )]
+    pub swap_source: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        token::mint = destination_token_mint.key(),
+        constraint = (swap_destination.key() == token_swap.token_a.key()) || (swap_destination.key() == token_swap.token_b.key())
+        @ SwapError::IncorrectSwapAccount,
+    )]
+    pub swap_destination: InterfaceAccount<'info, TokenAccount>,
+    pub source_token_mint: InterfaceAccount<'info, Mint>,
+    pub destination_token_mint: InterfaceAccount<'info, Mint>,
+}
@@ -0,0 +1,78 @@
+use {
+    crate::{
+        curves::TradeDirection,
+        helper::{to_u64, transfer_fee},
+        instructions::{calculate_withdraw_single, WithdrawSingleCalculation},
+        SwapError, SwapV2,
+    },
+    anchor_lang::prelude::*,
+    anchor_spl::token_interface::{Mint, TokenAccount},
+};
+
+/// Result of `quote_withdraw_single`, returned through the return data
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct WithdrawSingleQuote {
+    /// Pool tokens taken from the user, including the withdraw fee
+    pub pool_token_amount: u64,
+    /// Owner withdraw fee in pool tokens
+    pub withdraw_fee: u64,
+    /// Amount of destination token received by the user, after any transfer
+    /// fee
+    pub amount_out: u64,
+}
+
+/// Quote a single-sided withdrawal, the owner withdraw fee is included when
+/// the pool fee account is provided, like in the real withdrawal
+pub fn quote_withdraw_single_handler(
+    ctx: Context<QuoteWithdrawSingle>,
+    destination_token_amount: u64,
+) -> Result<WithdrawSingleQuote> {
+    let trade_direction = if ctx.accounts.destination_token_mint.key()
+        == ctx.accounts.token_swap.token_a_mint
+    {
+        TradeDirection::AtoB
+    } else if ctx.accounts.destination_token_mint.key() == ctx.accounts.token_swap.token_b_mint {
+        TradeDirection::BtoA
+    } else {
+        return err!(SwapError::IncorrectSwapAccount);
+    };
+    let WithdrawSingleCalculation {
+        pool_token_amount,
+        withdraw_fee,
+    } = calculate_withdraw_single(
+        &ctx.accounts.token_swap,
+        &ctx.accounts.pool_mint,
+        destination_token_amount,
+        trade_direction,
+        ctx.accounts.pool_fee_account.is_some(),
+    )?;
+    let amount_out = destination_token_amount.saturating_sub(transfer_fee(
+        &ctx.accounts.destination_token_mint,
+        destination_token_amount,
+    )?);
+    Ok(WithdrawSingleQuote {
+        pool_token_amount: to_u64(pool_token_amount)?,
+        withdraw_fee: to_u64(withdraw_fee)?,
+        amount_out,
+    })
+}
+
+#[derive(Accounts)]
+pub struct QuoteWithdrawSingle<'info> {
+    #[account(
+        constraint = !token_swap.to_account_info().data_is_empty() @ SwapError::IncorrectSwapAccount,
+        constraint = token_swap.status.allows_single_withdrawals() @ SwapError::OperationPaused,
+        constraint = token_swap.flash_loan.is_none() @ SwapError::FlashLoanInProgress,
+    )]
+    pub token_swap: Account<'info, SwapV2>,
+    #[account(
+        constraint = pool_mint.key() == token_swap.pool_mint @ SwapError::IncorrectPoolMint
+    )]
+    pub pool_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        constraint = pool_fee_account.key() == token_swap.pool_fee_account @ SwapError::InvalidFeeAccount
+    )]
+    pub pool_fee_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub destination_token_mint: InterfaceAccount<'info, Mint>,
+}
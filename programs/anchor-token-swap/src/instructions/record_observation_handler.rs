@@ -0,0 +1,39 @@
+use {
+    crate::{instructions::OBSERVATIONS_SEED, Observations, SwapError, SwapV2},
+    anchor_lang::prelude::*,
+};
+
+/// Bring the cumulative prices of the swap up to date and record them in its
+/// observations, can be called by anyone at most once per second.
+///
+/// Swaps, deposits and withdrawals only update the cumulative prices of the
+/// swap, the observations are written by this instruction alone. It must be
+/// cranked at least as often as the window of the averages read from them,
+/// and fails rather than skipping a record within the same second.
+pub fn record_observation_handler(ctx: Context<RecordObservation>) -> Result<()> {
+    ctx.accounts.token_swap.update_cumulative_prices()?;
+    require!(
+        ctx.accounts
+            .observations
+            .record(ctx.accounts.token_swap.cumulative_prices),
+        SwapError::ObservationTooRecent
+    );
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct RecordObservation<'info> {
+    #[account(
+        mut,
+        constraint = !token_swap.to_account_info().data_is_empty() @ SwapError::IncorrectSwapAccount,
+        constraint = token_swap.flash_loan.is_none() @ SwapError::FlashLoanInProgress,
+    )]
+    pub token_swap: Account<'info, SwapV2>,
+    #[account(
+        mut,
+        seeds = [OBSERVATIONS_SEED, token_swap.key().as_ref()],
+        bump,
+        has_one = token_swap @ SwapError::IncorrectSwapAccount,
+    )]
+    pub observations: Box<Account<'info, Observations>>,
+}
@@ -0,0 +1,474 @@
+#[cfg(feature = "invariant-check")]
+use crate::invariant::{balances, PoolValue};
+use {
+    crate::{
+        curves::{RoundDirection, SwapResult, TradeDirection},
+        events::{emit_event, Swapped},
+        helper::{split_hook_accounts, to_u64, transfer_checked_with_hook},
+        instructions::{calculate_swap, SwapCalculation},
+        state::SwapState,
+        SwapError, SwapV2,
+    },
+    anchor_lang::prelude::*,
+    anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+/// Number of remaining accounts describing each hop of a route, followed by
+/// the transfer hook accounts of its destination mint (see
+/// `split_hook_accounts`), in order:
+///
+///   0. `[writable]` Token-swap
+///   1. `[]` swap authority
+///   2. `[writable]` swap source token account, its mint is the destination
+///      mint of the previous hop, or the source mint of the route
+///   3. `[writable]` swap destination token account
+///   4. `[]` destination token mint
+///   5. `[]` destination token program
+///   6. `[writable]` pool token mint
+///   7. `[writable]` pool fee account, receiving the owner trade fee
+///   8. `[]` pool token program
+///   9. `[writable]` host fee account, receiving the host part of the owner
+///      trade fee, or the swap program for none
+pub const ACCOUNTS_PER_HOP: usize = 10;
+
+/// Validated accounts of one hop of a route
+struct RouteHop<'info> {
+    token_swap: Account<'info, SwapV2>,
+    authority: &'info AccountInfo<'info>,
+    swap_source: InterfaceAccount<'info, TokenAccount>,
+    swap_destination: InterfaceAccount<'info, TokenAccount>,
+    destination_token_mint: InterfaceAccount<'info, Mint>,
+    destination_token_program: Interface<'info, TokenInterface>,
+    pool_mint: InterfaceAccount<'info, Mint>,
+    pool_fee_account: InterfaceAccount<'info, TokenAccount>,
+    token_pool_program: Interface<'info, TokenInterface>,
+    host_fee_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Transfer hook accounts of the destination mint
+    hook_accounts: &'info [AccountInfo<'info>],
+    #[cfg(feature = "invariant-check")]
+    pool_value: PoolValue,
+}
+
+impl<'info> RouteHop<'info> {
+    /// Load and validate the `ACCOUNTS_PER_HOP` accounts of a hop followed by
+    /// the transfer hook accounts of its destination mint, taking them off
+    /// the front of `remaining_accounts`. Applies the same checks as the
+    /// `TokenSwap` accounts of a single swap.
+    fn load(remaining_accounts: &mut &'info [AccountInfo<'info>]) -> Result<Self> {
+        require_gte!(
+            remaining_accounts.len(),
+            ACCOUNTS_PER_HOP,
+            SwapError::InvalidRoute
+        );
+        let (accounts, rest) = remaining_accounts.split_at(ACCOUNTS_PER_HOP);
+        *remaining_accounts = rest;
+        let hook_accounts = split_hook_accounts(&accounts[4], remaining_accounts)?;
+
+        let mut token_swap = Account::<SwapV2>::try_from(&accounts[0])?;
+        require!(token_swap.status.allows_swaps(), SwapError::OperationPaused);
+        require!(
+            token_swap.flash_loan.is_none(),
+            SwapError::FlashLoanInProgress
+        );
+        let authority_key = Pubkey::create_program_address(
+            &[token_swap.key().as_ref(), &[token_swap.bump_seed]],
+            &crate::ID,
+        )
+        .map_err(|_| SwapError::InvalidProgramAddress)?;
+        let authority = &accounts[1];
+        require_keys_eq!(
+            authority.key(),
+            authority_key,
+            SwapError::InvalidProgramAddress
+        );
+
+        let swap_source = InterfaceAccount::<TokenAccount>::try_from(&accounts[2])?;
+        let swap_destination = InterfaceAccount::<TokenAccount>::try_from(&accounts[3])?;
+        require!(
+            swap_source.key() == token_swap.token_a || swap_source.key() == token_swap.token_b,
+            SwapError::IncorrectSwapAccount
+        );
+        require!(
+            swap_destination.key() == token_swap.token_a
+                || swap_destination.key() == token_swap.token_b,
+            SwapError::IncorrectSwapAccount
+        );
+        require_keys_neq!(
+            swap_source.key(),
+            swap_destination.key(),
+            SwapError::SameAccountTransfer
+        );
+
+        token_swap.update_cumulative_prices()?;
+
+        let destination_token_mint = InterfaceAccount::<Mint>::try_from(&accounts[4])?;
+        let destination_token_program = Interface::<TokenInterface>::try_from(&accounts[5])?;
+        require_keys_eq!(
+            swap_destination.mint,
+            destination_token_mint.key(),
+            SwapError::IncorrectSwapAccount
+        );
+        require!(
+            token_swap.token_program(&destination_token_mint.key())
+                == Some(&destination_token_program.key()),
+            SwapError::IncorrectTokenProgramId
+        );
+
+        let pool_mint = InterfaceAccount::<Mint>::try_from(&accounts[6])?;
+        let pool_fee_account = InterfaceAccount::<TokenAccount>::try_from(&accounts[7])?;
+        let token_pool_program = Interface::<TokenInterface>::try_from(&accounts[8])?;
+        require_keys_eq!(
+            pool_mint.key(),
+            token_swap.pool_mint,
+            SwapError::IncorrectPoolMint
+        );
+        require_keys_eq!(
+            pool_fee_account.key(),
+            token_swap.pool_fee_account,
+            SwapError::InvalidFeeAccount
+        );
+        require_keys_eq!(
+            token_pool_program.key(),
+            token_swap.pool_token_program,
+            SwapError::IncorrectTokenProgramId
+        );
+        let host_fee_account = if accounts[9].key() == crate::ID {
+            None
+        } else {
+            let host_fee_account = InterfaceAccount::<TokenAccount>::try_from(&accounts[9])?;
+            require_keys_eq!(
+                host_fee_account.mint,
+                pool_mint.key(),
+                SwapError::IncorrectPoolMint
+            );
+            require_keys_neq!(
+                host_fee_account.owner,
+                authority.key(),
+                SwapError::InvalidOwner
+            );
+            Some(host_fee_account)
+        };
+        #[cfg(feature = "invariant-check")]
+        let pool_value = PoolValue::new(
+            &token_swap,
+            balances(
+                hop_trade_direction(&token_swap, &swap_source),
+                swap_source.amount,
+                swap_destination.amount,
+            ),
+            pool_mint.supply,
+        )?;
+
+        Ok(Self {
+            token_swap,
+            authority,
+            swap_source,
+            swap_destination,
+            destination_token_mint,
+            destination_token_program,
+            pool_mint,
+            pool_fee_account,
+            token_pool_program,
+            host_fee_account,
+            hook_accounts,
+            #[cfg(feature = "invariant-check")]
+            pool_value,
+        })
+    }
+
+    /// Mint the owner trade fee of the hop to the pool fee account, less the
+    /// host fee minted to the host fee account if any, returns the host fee
+    fn mint_owner_fee(&self, result: &SwapResult, trade_direction: TradeDirection) -> Result<u64> {
+        if result.owner_fee == 0 {
+            return Ok(0);
+        }
+        let (swap_token_a_amount, swap_token_b_amount) = match trade_direction {
+            TradeDirection::AtoB => (
+                result.new_swap_source_amount,
+                result.new_swap_destination_amount,
+            ),
+            TradeDirection::BtoA => (
+                result.new_swap_destination_amount,
+                result.new_swap_source_amount,
+            ),
+        };
+        let mut pool_token_amount = self
+            .token_swap
+            .swap_curve()
+            .calculator
+            .withdraw_single_token_type_exact_out(
+                result.owner_fee,
+                swap_token_a_amount,
+                swap_token_b_amount,
+                self.pool_mint.supply as u128,
+                trade_direction,
+                RoundDirection::Floor,
+            )
+            .ok_or(SwapError::FeeCalculationFailure)?;
+        let mut host_fee = 0;
+        if let Some(host_fee_account) = &self.host_fee_account {
+            host_fee = self
+                .token_swap
+                .fees()
+                .host_fee(pool_token_amount)
+                .ok_or(SwapError::FeeCalculationFailure)?;
+            if host_fee > 0 {
+                pool_token_amount = pool_token_amount
+                    .checked_sub(host_fee)
+                    .ok_or(SwapError::FeeCalculationFailure)?;
+                self.mint_pool_tokens(host_fee_account.to_account_info(), host_fee)?;
+            }
+        }
+        self.mint_pool_tokens(self.pool_fee_account.to_account_info(), pool_token_amount)?;
+        to_u64(host_fee)
+    }
+
+    /// Mint `amount` pool tokens of the hop
+    fn mint_pool_tokens(&self, to: AccountInfo<'info>, amount: u128) -> Result<()> {
+        anchor_spl::token_interface::mint_to(
+            CpiContext::new_with_signer(
+                self.token_pool_program.to_account_info(),
+                anchor_spl::token_interface::MintTo {
+                    mint: self.pool_mint.to_account_info(),
+                    to,
+                    authority: self.authority.clone(),
+                },
+                &[&[
+                    &self.token_swap.key().to_bytes(),
+                    &[self.token_swap.bump_seed],
+                ]],
+            ),
+            to_u64(amount)?,
+        )
+    }
+
+    /// Transfer `amount` destination tokens out of the swap
+    fn transfer_out(&self, to: AccountInfo<'info>, amount: u64) -> Result<()> {
+        transfer_checked_with_hook(
+            CpiContext::new_with_signer(
+                self.destination_token_program.to_account_info(),
+                anchor_spl::token_interface::TransferChecked {
+                    from: self.swap_destination.to_account_info(),
+                    to,
+                    authority: self.authority.clone(),
+                    mint: self.destination_token_mint.to_account_info(),
+                },
+                &[&[
+                    &self.token_swap.key().to_bytes(),
+                    &[self.token_swap.bump_seed],
+                ]],
+            )
+            .with_remaining_accounts(self.hook_accounts.to_vec()),
+            amount,
+            self.destination_token_mint.decimals,
+        )
+    }
+}
+
+/// Direction of the trade of a hop, given its swap source account
+#[cfg(feature = "invariant-check")]
+fn hop_trade_direction(
+    token_swap: &SwapV2,
+    swap_source: &InterfaceAccount<TokenAccount>,
+) -> TradeDirection {
+    match swap_source.key() == token_swap.token_a {
+        true => TradeDirection::AtoB,
+        false => TradeDirection::BtoA,
+    }
+}
+
+/// Swap `amount_in` source tokens through `hops` pools, described by the
+/// remaining accounts after the transfer hook accounts of the source mint.
+/// Intermediate tokens move directly between the vaults of consecutive pools,
+/// each of which must receive the amount used by its curve. Only the output
+/// of the last pool is checked against `minimum_amount_out`.
+pub fn route_swap_handler<'info>(
+    ctx: Context<'_, '_, 'info, 'info, RouteSwap<'info>>,
+    amount_in: u64,
+    minimum_amount_out: u64,
+    hops: u8,
+) -> Result<()> {
+    let hops = usize::from(hops);
+    require!(hops > 0, SwapError::InvalidRoute);
+    let mut remaining_accounts = ctx.remaining_accounts;
+    let source_hook_accounts = split_hook_accounts(
+        &ctx.accounts.source_token_mint.to_account_info(),
+        &mut remaining_accounts,
+    )?;
+    let mut route = (0..hops)
+        .map(|_| RouteHop::load(&mut remaining_accounts))
+        .collect::<Result<Vec<_>>>()?;
+    require!(remaining_accounts.is_empty(), SwapError::InvalidRoute);
+    // the reserves of each pool are read once up front, so a pool may only
+    // be used once in a route
+    for (i, hop) in route.iter().enumerate() {
+        require!(
+            route[..i]
+                .iter()
+                .all(|previous| previous.token_swap.key() != hop.token_swap.key()),
+            SwapError::InvalidRoute
+        );
+    }
+
+    let mut source_token_mint = ctx.accounts.source_token_mint.clone();
+    let mut hop_amount_in = amount_in;
+    let mut amount_received = 0;
+    let mut swaps = Vec::with_capacity(hops);
+    for i in 0..route.len() {
+        let hop = &route[i];
+        require_keys_eq!(
+            hop.swap_source.mint,
+            source_token_mint.key(),
+            SwapError::InvalidRoute
+        );
+        let SwapCalculation {
+            trade_direction,
+            result,
+            source_transfer_amount,
+            destination_transfer_amount,
+            amount_received: hop_amount_received,
+        } = calculate_swap(
+            &hop.token_swap,
+            &hop.swap_source,
+            &source_token_mint,
+            &hop.destination_token_mint,
+            hop_amount_in,
+        )?;
+
+        let transferred_in = match i.checked_sub(1) {
+            None => {
+                transfer_checked_with_hook(
+                    CpiContext::new(
+                        ctx.accounts.token_source_program.to_account_info(),
+                        anchor_spl::token_interface::TransferChecked {
+                            from: ctx.accounts.user_source.to_account_info(),
+                            to: hop.swap_source.to_account_info(),
+                            authority: ctx.accounts.user_transfer_authority.to_account_info(),
+                            mint: source_token_mint.to_account_info(),
+                        },
+                    )
+                    .with_remaining_accounts(source_hook_accounts.to_vec()),
+                    source_transfer_amount,
+                    source_token_mint.decimals,
+                )?;
+                source_transfer_amount
+            }
+            Some(previous) => {
+                route[previous].transfer_out(hop.swap_source.to_account_info(), hop_amount_in)?;
+                hop_amount_in
+            }
+        };
+        // the curve assumed the swap receives the full source amount swapped
+        let hop = &mut route[i];
+        let swap_source_amount_before = hop.swap_source.amount;
+        hop.swap_source.reload()?;
+        require_gte!(
+            hop.swap_source
+                .amount
+                .saturating_sub(swap_source_amount_before),
+            to_u64(result.source_amount_swapped)?,
+            SwapError::InsufficientTransferAmount
+        );
+        let host_fee = hop.mint_owner_fee(&result, trade_direction)?;
+
+        swaps.push((
+            transferred_in,
+            destination_transfer_amount,
+            result,
+            host_fee,
+        ));
+        source_token_mint = hop.destination_token_mint.clone();
+        hop_amount_in = destination_transfer_amount;
+        amount_received = hop_amount_received;
+    }
+
+    require_keys_eq!(
+        ctx.accounts.user_destination.mint,
+        source_token_mint.key(),
+        SwapError::InvalidRoute
+    );
+    // the slippage limit applies to what the user receives, after any
+    // transfer fee of the destination mint
+    require_gte!(
+        amount_received,
+        minimum_amount_out,
+        SwapError::ExceededSlippage
+    );
+    let last = route.last().ok_or(SwapError::InvalidRoute)?;
+    last.transfer_out(
+        ctx.accounts.user_destination.to_account_info(),
+        hop_amount_in,
+    )?;
+
+    let mut source_mint = ctx.accounts.source_token_mint.key();
+    for (hop, (amount_in, amount_out, result, host_fee)) in route.iter_mut().zip(swaps) {
+        // the reserves are what the curve computed, anything else received
+        // by the swap is left as excess
+        let (reserve_a, reserve_b) = if hop.swap_source.key() == hop.token_swap.token_a {
+            (
+                result.new_swap_source_amount,
+                result.new_swap_destination_amount,
+            )
+        } else {
+            (
+                result.new_swap_destination_amount,
+                result.new_swap_source_amount,
+            )
+        };
+        hop.token_swap.reserve_a = to_u64(reserve_a)?;
+        hop.token_swap.reserve_b = to_u64(reserve_b)?;
+        #[cfg(feature = "invariant-check")]
+        {
+            hop.swap_source.reload()?;
+            hop.swap_destination.reload()?;
+            hop.pool_mint.reload()?;
+            hop.pool_value.check(
+                &hop.token_swap,
+                balances(
+                    hop_trade_direction(&hop.token_swap, &hop.swap_source),
+                    hop.swap_source.amount,
+                    hop.swap_destination.amount,
+                ),
+                hop.pool_mint.supply,
+            )?;
+        }
+        emit_event!(
+            ctx,
+            Swapped {
+                token_swap: hop.token_swap.key(),
+                user: ctx.accounts.user_transfer_authority.key(),
+                source_mint,
+                destination_mint: hop.destination_token_mint.key(),
+                amount_in,
+                amount_out,
+                trade_fee: to_u64(result.trade_fee)?,
+                owner_fee: to_u64(result.owner_fee)?,
+                host_fee,
+                reserve_a: hop.token_swap.reserve_a,
+                reserve_b: hop.token_swap.reserve_b,
+            }
+        );
+        source_mint = hop.destination_token_mint.key();
+        hop.token_swap.exit(&crate::ID)?;
+    }
+    Ok(())
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct RouteSwap<'info> {
+    pub user_transfer_authority: Signer<'info>,
+    #[account(
+        mut,
+        token::mint = source_token_mint.key(),
+        token::token_program = token_source_program.key(),
+    )]
+    pub user_source: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_destination: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mint::token_program = token_source_program.key(),
+    )]
+    pub source_token_mint: InterfaceAccount<'info, Mint>,
+    pub token_source_program: Interface<'info, TokenInterface>,
+}
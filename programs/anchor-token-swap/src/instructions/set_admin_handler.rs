@@ -0,0 +1,35 @@
+use {
+    crate::{
+        events::{emit_event, AdminUpdated},
+        SwapError, SwapV2,
+    },
+    anchor_lang::prelude::*,
+};
+
+/// Hand the admin role of a swap over to `new_admin`, the current admin loses
+/// every right on the swap
+pub fn set_admin_handler(ctx: Context<SetAdmin>, new_admin: Pubkey) -> Result<()> {
+    let old_admin = std::mem::replace(&mut ctx.accounts.token_swap.admin, new_admin);
+
+    emit_event!(
+        ctx,
+        AdminUpdated {
+            token_swap: ctx.accounts.token_swap.key(),
+            old_admin,
+            new_admin,
+        }
+    );
+    Ok(())
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SetAdmin<'info> {
+    #[account(
+        mut,
+        has_one = admin @ SwapError::InvalidAdmin,
+        constraint = !token_swap.to_account_info().data_is_empty() @ SwapError::IncorrectSwapAccount,
+    )]
+    pub token_swap: Account<'info, SwapV2>,
+    pub admin: Signer<'info>,
+}
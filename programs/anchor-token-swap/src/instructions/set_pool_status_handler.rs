@@ -0,0 +1,33 @@
+use {
+    crate::{
+        events::{emit_event, PoolStatusUpdated},
+        PoolStatus, SwapError, SwapV2,
+    },
+    anchor_lang::prelude::*,
+};
+
+pub fn set_pool_status_handler(ctx: Context<SetPoolStatus>, status: PoolStatus) -> Result<()> {
+    let old_status = std::mem::replace(&mut ctx.accounts.token_swap.status, status);
+
+    emit_event!(
+        ctx,
+        PoolStatusUpdated {
+            token_swap: ctx.accounts.token_swap.key(),
+            old_status,
+            new_status: status,
+        }
+    );
+    Ok(())
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SetPoolStatus<'info> {
+    #[account(
+        mut,
+        has_one = admin @ SwapError::InvalidAdmin,
+        constraint = !token_swap.to_account_info().data_is_empty() @ SwapError::IncorrectSwapAccount,
+    )]
+    pub token_swap: Account<'info, SwapV2>,
+    pub admin: Signer<'info>,
+}
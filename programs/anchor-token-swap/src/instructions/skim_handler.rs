@@ -0,0 +1,136 @@
+use {
+    crate::{
+        events::{emit_event, Skimmed},
+        helper::{split_hook_accounts, transfer_checked_with_hook},
+        SwapError, SwapV2,
+    },
+    anchor_lang::prelude::*,
+    anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+/// Transfer the tokens held by the swap above its reserves to the given
+/// destinations, leaving the reserves untouched. Anyone may skim a swap.
+pub fn skim_handler<'info>(ctx: Context<'_, '_, '_, 'info, Skim<'info>>) -> Result<()> {
+    let token_swap_key = ctx.accounts.token_swap.key();
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        token_swap_key.as_ref(),
+        &[ctx.accounts.token_swap.bump_seed],
+    ]];
+    let token_a_amount = ctx
+        .accounts
+        .swap_token_a
+        .amount
+        .saturating_sub(ctx.accounts.token_swap.reserve_a);
+    let token_b_amount = ctx
+        .accounts
+        .swap_token_b
+        .amount
+        .saturating_sub(ctx.accounts.token_swap.reserve_b);
+    let mut remaining_accounts = ctx.remaining_accounts;
+    for (token_program, swap_token, destination, mint, amount) in [
+        (
+            &ctx.accounts.token_a_program,
+            &ctx.accounts.swap_token_a,
+            &ctx.accounts.destination_a,
+            &ctx.accounts.token_a_mint,
+            token_a_amount,
+        ),
+        (
+            &ctx.accounts.token_b_program,
+            &ctx.accounts.swap_token_b,
+            &ctx.accounts.destination_b,
+            &ctx.accounts.token_b_mint,
+            token_b_amount,
+        ),
+    ] {
+        let hook_accounts = split_hook_accounts(&mint.to_account_info(), &mut remaining_accounts)?;
+        if amount > 0 {
+            transfer_checked_with_hook(
+                CpiContext::new_with_signer(
+                    token_program.to_account_info(),
+                    anchor_spl::token_interface::TransferChecked {
+                        from: swap_token.to_account_info(),
+                        to: destination.to_account_info(),
+                        authority: ctx.accounts.authority.to_account_info(),
+                        mint: mint.to_account_info(),
+                    },
+                    signer_seeds,
+                )
+                .with_remaining_accounts(hook_accounts.to_vec()),
+                amount,
+                mint.decimals,
+            )?;
+        }
+    }
+
+    emit_event!(
+        ctx,
+        Skimmed {
+            token_swap: token_swap_key,
+            token_a_amount,
+            token_b_amount,
+        }
+    );
+    Ok(())
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct Skim<'info> {
+    #[account(
+        constraint = !token_swap.to_account_info().data_is_empty() @ SwapError::IncorrectSwapAccount,
+        constraint = token_swap.flash_loan.is_none() @ SwapError::FlashLoanInProgress,
+    )]
+    pub token_swap: Account<'info, SwapV2>,
+    #[account(
+        seeds = [token_swap.key().as_ref()],
+        bump = token_swap.bump_seed,
+    )]
+    pub authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        token::mint = token_a_mint,
+        token::token_program = token_a_program,
+        constraint = swap_token_a.key() == token_swap.token_a @ SwapError::IncorrectSwapAccount,
+    )]
+    pub swap_token_a: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        token::mint = token_b_mint,
+        token::token_program = token_b_program,
+        constraint = swap_token_b.key() == token_swap.token_b @ SwapError::IncorrectSwapAccount,
+    )]
+    pub swap_token_b: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        constraint = token_a_mint.key() == token_swap.token_a_mint @ SwapError::IncorrectSwapAccount,
+    )]
+    pub token_a_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        constraint = token_b_mint.key() == token_swap.token_b_mint @ SwapError::IncorrectSwapAccount,
+    )]
+    pub token_b_mint: InterfaceAccount<'info, Mint>,
+    /// Receives the excess of token A
+    #[account(
+        mut,
+        token::mint = token_a_mint,
+        token::token_program = token_a_program,
+        constraint = destination_a.key() != swap_token_a.key() @ SwapError::SameAccountTransfer,
+    )]
+    pub destination_a: InterfaceAccount<'info, TokenAccount>,
+    /// Receives the excess of token B
+    #[account(
+        mut,
+        token::mint = token_b_mint,
+        token::token_program = token_b_program,
+        constraint = destination_b.key() != swap_token_b.key() @ SwapError::SameAccountTransfer,
+    )]
+    pub destination_b: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        constraint = token_a_program.key() == token_swap.token_a_program @ SwapError::IncorrectTokenProgramId,
+    )]
+    pub token_a_program: Interface<'info, TokenInterface>,
+    #[account(
+        constraint = token_b_program.key() == token_swap.token_b_program @ SwapError::IncorrectTokenProgramId,
+    )]
+    pub token_b_program: Interface<'info, TokenInterface>,
+}
@@ -0,0 +1,87 @@
+use {
+    crate::{
+        curves::TradeDirection,
+        helper::{inverse_transfer_fee, to_u64},
+        instructions::{execute_swap, SwapCalculation, TokenSwap},
+        state::SwapState,
+        SwapError, SwapV2,
+    },
+    anchor_lang::prelude::*,
+    anchor_spl::token_interface::{Mint, TokenAccount},
+};
+
+/// Run the curve calculation of a swap delivering exactly `amount_out`
+/// destination tokens to the user, accounting for Token-2022 transfer fees
+/// on both mints
+pub fn calculate_swap_exact_out(
+    token_swap: &SwapV2,
+    swap_source: &InterfaceAccount<TokenAccount>,
+    source_token_mint: &InterfaceAccount<Mint>,
+    destination_token_mint: &InterfaceAccount<Mint>,
+    amount_out: u64,
+) -> Result<SwapCalculation> {
+    let trade_direction = match swap_source.key() == token_swap.token_a.key() {
+        true => TradeDirection::AtoB,
+        false => TradeDirection::BtoA,
+    };
+    let (source_reserve, destination_reserve) = token_swap.reserves(trade_direction);
+
+    // the user must receive exactly `amount_out`, so the swap has to send
+    // out the transfer fee of the destination mint on top of it
+    let destination_transfer_amount = amount_out
+        .checked_add(inverse_transfer_fee(destination_token_mint, amount_out)?)
+        .ok_or(SwapError::FeeCalculationFailure)?;
+
+    let result = token_swap
+        .swap_curve()
+        .swap_exact_out(
+            u128::from(destination_transfer_amount),
+            u128::from(source_reserve),
+            u128::from(destination_reserve),
+            trade_direction,
+            token_swap.fees(),
+        )
+        .ok_or(SwapError::ZeroTradingTokens)?;
+
+    // the swap must receive the full source amount, so the user has to pay
+    // the transfer fee of the source mint on top of it
+    let source_amount_swapped = to_u64(result.source_amount_swapped)?;
+    let source_transfer_amount = source_amount_swapped
+        .checked_add(inverse_transfer_fee(
+            source_token_mint,
+            source_amount_swapped,
+        )?)
+        .ok_or(SwapError::FeeCalculationFailure)?;
+
+    Ok(SwapCalculation {
+        trade_direction,
+        result,
+        source_transfer_amount,
+        destination_transfer_amount,
+        amount_received: amount_out,
+    })
+}
+
+pub fn swap_exact_amount_out_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, TokenSwap<'info>>,
+    amount_out: u64,
+    maximum_amount_in: u64,
+) -> Result<()> {
+    ctx.accounts.token_swap.update_cumulative_prices()?;
+
+    let calculation = calculate_swap_exact_out(
+        &ctx.accounts.token_swap,
+        &ctx.accounts.swap_source,
+        &ctx.accounts.source_token_mint,
+        &ctx.accounts.destination_token_mint,
+        amount_out,
+    )?;
+    // the slippage limit applies to what the user pays, including any
+    // transfer fee of the source mint
+    require_gte!(
+        maximum_amount_in,
+        calculation.source_transfer_amount,
+        SwapError::ExceededSlippage
+    );
+    execute_swap(ctx, calculation)
+}
@@ -1,93 +1,159 @@
+#[cfg(feature = "invariant-check")]
+use crate::invariant::{balances, PoolValue};
 use {
     crate::{
-        curves::{RoundDirection, TradeDirection},
-        helper::to_u64,
+        curves::{RoundDirection, SwapResult, TradeDirection},
+        events::{emit_event, Swapped},
+        helper::{
+            inverse_transfer_fee, split_hook_accounts, to_u64, transfer_checked_with_hook,
+            transfer_fee, UserTokenAccount, NATIVE_SOL_SEED,
+        },
         state::SwapState,
-        SwapError, SwapV1,
+        SwapError, SwapV2,
     },
     anchor_lang::prelude::*,
     anchor_spl::{
-        token_2022::spl_token_2022::extension::transfer_fee::TransferFeeConfig,
-        token_interface::{get_mint_extension_data, Mint, TokenAccount, TokenInterface},
+        associated_token::AssociatedToken,
+        token_interface::{Mint, TokenAccount, TokenInterface},
     },
 };
-pub fn swap_handler(
-    ctx: Context<TokenSwap>,
+/// Amounts moved by a swap, shared by `swap`, `swap_exact_amount_out` and
+/// `quote_swap`
+pub struct SwapCalculation {
+    /// Direction of the trade
+    pub trade_direction: TradeDirection,
+    /// Result of the curve calculation, including fees
+    pub result: SwapResult,
+    /// Amount of source token transferred from the user to the swap, so
+    /// that the swap receives the source amount swapped after any transfer
+    /// fee
+    pub source_transfer_amount: u64,
+    /// Amount of destination token transferred from the swap to the user,
+    /// before any transfer fee
+    pub destination_transfer_amount: u64,
+    /// Amount of destination token received by the user, after any transfer
+    /// fee
+    pub amount_received: u64,
+}
+
+/// Run the curve calculation of a swap of `amount_in` source tokens,
+/// accounting for Token-2022 transfer fees on both mints
+pub fn calculate_swap(
+    token_swap: &SwapV2,
+    swap_source: &InterfaceAccount<TokenAccount>,
+    source_token_mint: &InterfaceAccount<Mint>,
+    destination_token_mint: &InterfaceAccount<Mint>,
     amount_in: u64,
-    minimum_amount_out: u64,
-) -> Result<()> {
-    let swap_curve = &ctx.accounts.token_swap.swap_curve();
-    let source_mint_transfer_fee_config = get_mint_extension_data::<TransferFeeConfig>(
-        &ctx.accounts.source_token_mint.to_account_info(),
-    );
+) -> Result<SwapCalculation> {
+    let actual_amount_in = amount_in.saturating_sub(transfer_fee(source_token_mint, amount_in)?);
 
-    let actual_amount_in = if let Ok(ref config) = source_mint_transfer_fee_config {
-        amount_in.saturating_sub(
-            config
-                .calculate_epoch_fee(Clock::get()?.epoch, amount_in)
-                .ok_or(SwapError::FeeCalculationFailure)?,
-        )
-    } else {
-        amount_in
+    let trade_direction = match swap_source.key() == token_swap.token_a.key() {
+        true => TradeDirection::AtoB,
+        false => TradeDirection::BtoA,
     };
+    let (source_reserve, destination_reserve) = token_swap.reserves(trade_direction);
 
-    let trade_direction =
-        match ctx.accounts.swap_source.key() == ctx.accounts.token_swap.token_a.key() {
-            true => TradeDirection::AtoB,
-            false => TradeDirection::BtoA,
-        };
-
-    let result = ctx
-        .accounts
-        .token_swap
+    let result = token_swap
         .swap_curve()
         .swap(
             u128::from(actual_amount_in),
-            u128::from(ctx.accounts.swap_source.amount),
-            u128::from(ctx.accounts.swap_destination.amount),
+            u128::from(source_reserve),
+            u128::from(destination_reserve),
             trade_direction,
-            &ctx.accounts.token_swap.fees,
+            token_swap.fees(),
         )
         .ok_or(SwapError::ZeroTradingTokens)?;
 
-    let (source_transfer_amount, source_mint_decimals) = {
-        let source_amount_swapped = to_u64(result.source_amount_swapped)?;
-        let amount = if let Ok(ref config) = source_mint_transfer_fee_config {
-            source_amount_swapped.saturating_sub(
-                config
-                    .calculate_epoch_fee(Clock::get()?.epoch, source_amount_swapped)
-                    .ok_or(SwapError::FeeCalculationFailure)?,
-            )
-        } else {
-            source_amount_swapped
-        };
-        (amount, ctx.accounts.source_token_mint.decimals)
-    };
+    // the swap must receive the full source amount swapped, so the transfer
+    // fee of the source mint is added on top of it, which never exceeds
+    // `amount_in` since the curve was given the amount left after its fee
+    let source_amount_swapped = to_u64(result.source_amount_swapped)?;
+    let source_transfer_amount = source_amount_swapped
+        .checked_add(inverse_transfer_fee(
+            source_token_mint,
+            source_amount_swapped,
+        )?)
+        .ok_or(SwapError::FeeCalculationFailure)?;
 
-    let destination_mint_transfer_fee_config = get_mint_extension_data::<TransferFeeConfig>(
-        &ctx.accounts.destination_token_mint.to_account_info(),
+    // the swap sends out exactly the destination amount swapped, the
+    // transfer fee of the destination mint is borne by the user
+    let destination_transfer_amount = to_u64(result.destination_amount_swapped)?;
+    let amount_received = destination_transfer_amount.saturating_sub(transfer_fee(
+        destination_token_mint,
+        destination_transfer_amount,
+    )?);
+
+    Ok(SwapCalculation {
+        trade_direction,
+        result,
+        source_transfer_amount,
+        destination_transfer_amount,
+        amount_received,
+    })
+}
+
+pub fn swap_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, TokenSwap<'info>>,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Result<()> {
+    ctx.accounts.token_swap.update_cumulative_prices()?;
+
+    let calculation = calculate_swap(
+        &ctx.accounts.token_swap,
+        &ctx.accounts.swap_source,
+        &ctx.accounts.source_token_mint,
+        &ctx.accounts.destination_token_mint,
+        amount_in,
+    )?;
+    // the slippage limit applies to what the user receives, after any
+    // transfer fee of the destination mint
+    require_gte!(
+        calculation.amount_received,
+        minimum_amount_out,
+        SwapError::ExceededSlippage
     );
-    let (destination_transfer_amount, destination_mint_decimals) = {
-        let amount_out = to_u64(result.destination_amount_swapped)?;
-        let amount_received = if let Ok(ref config) = destination_mint_transfer_fee_config {
-            amount_out.saturating_sub(
-                config
-                    .calculate_epoch_fee(Clock::get()?.epoch, amount_out)
-                    .ok_or(SwapError::FeeCalculationFailure)?,
-            )
-        } else {
-            amount_out
-        };
-        require_gte!(
-            amount_received,
-            minimum_amount_out,
-            SwapError::ExceededSlippage
-        );
-        (
-            amount_received,
-            ctx.accounts.destination_token_mint.decimals,
-        )
-    };
+    execute_swap(ctx, calculation)
+}
+
+/// Transfer the amounts of a calculated swap, mint the owner and host fees
+/// and update the reserves, shared by `swap` and `swap_exact_amount_out`
+pub fn execute_swap<'info>(
+    ctx: Context<'_, '_, '_, 'info, TokenSwap<'info>>,
+    calculation: SwapCalculation,
+) -> Result<()> {
+    let SwapCalculation {
+        trade_direction,
+        result,
+        source_transfer_amount,
+        destination_transfer_amount,
+        ..
+    } = calculation;
+    let swap_curve = &ctx.accounts.token_swap.swap_curve();
+    let source_mint_decimals = ctx.accounts.source_token_mint.decimals;
+    let destination_mint_decimals = ctx.accounts.destination_token_mint.decimals;
+
+    let user_source = UserTokenAccount::new(
+        ctx.accounts.user_source.as_ref(),
+        ctx.accounts.native_sol_account.as_ref(),
+        ctx.bumps.native_sol_account,
+        &ctx.accounts.user_transfer_authority,
+        &ctx.accounts.source_token_mint,
+        &ctx.accounts.token_source_program,
+        &ctx.accounts.system_program,
+    )?;
+    let user_destination = UserTokenAccount::new(
+        ctx.accounts
+            .user_destination
+            .as_ref()
+            .or(ctx.accounts.user_destination_ata.as_deref()),
+        ctx.accounts.native_sol_account.as_ref(),
+        ctx.bumps.native_sol_account,
+        &ctx.accounts.user_transfer_authority,
+        &ctx.accounts.destination_token_mint,
+        &ctx.accounts.token_destination_program,
+        &ctx.accounts.system_program,
+    )?;
     let (swap_token_a_amount, swap_token_b_amount) = match trade_direction {
         TradeDirection::AtoB => (
             result.new_swap_source_amount,
@@ -98,20 +164,54 @@ pub fn swap_handler(
             result.new_swap_source_amount,
         ),
     };
-    anchor_spl::token_interface::transfer_checked(
+    #[cfg(feature = "invariant-check")]
+    let pool_value = PoolValue::new(
+        &ctx.accounts.token_swap,
+        balances(
+            trade_direction,
+            ctx.accounts.swap_source.amount,
+            ctx.accounts.swap_destination.amount,
+        ),
+        ctx.accounts.pool_mint.supply,
+    )?;
+    user_source.wrap(source_transfer_amount)?;
+    user_destination.wrap(0)?;
+    let mut remaining_accounts = ctx.remaining_accounts;
+    let source_hook_accounts = split_hook_accounts(
+        &ctx.accounts.source_token_mint.to_account_info(),
+        &mut remaining_accounts,
+    )?;
+    let destination_hook_accounts = split_hook_accounts(
+        &ctx.accounts.destination_token_mint.to_account_info(),
+        &mut remaining_accounts,
+    )?;
+    transfer_checked_with_hook(
         CpiContext::new(
             ctx.accounts.token_source_program.to_account_info(),
             anchor_spl::token_interface::TransferChecked {
-                from: ctx.accounts.user_source.to_account_info(),
+                from: user_source.to_account_info(),
                 to: ctx.accounts.swap_source.to_account_info(),
                 authority: ctx.accounts.user_transfer_authority.to_account_info(),
                 mint: ctx.accounts.source_token_mint.to_account_info(),
             },
-        ),
+        )
+        .with_remaining_accounts(source_hook_accounts.to_vec()),
         source_transfer_amount,
         source_mint_decimals,
     )?;
+    // the curve assumed the swap receives the full source amount swapped
+    let swap_source_amount_before = ctx.accounts.swap_source.amount;
+    ctx.accounts.swap_source.reload()?;
+    require_gte!(
+        ctx.accounts
+            .swap_source
+            .amount
+            .saturating_sub(swap_source_amount_before),
+        to_u64(result.source_amount_swapped)?,
+        SwapError::InsufficientTransferAmount
+    );
 
+    let mut host_fee = 0;
     if result.owner_fee > 0 {
         let mut pool_token_amount = swap_curve
             .calculator
@@ -126,7 +226,7 @@ pub fn swap_handler(
             .ok_or(SwapError::FeeCalculationFailure)?;
 
         if let Some(host_fee_account) = &ctx.accounts.host_fee_account {
-            let host_fee = ctx
+            host_fee = ctx
                 .accounts
                 .token_swap
                 .fees()
@@ -146,7 +246,7 @@ pub fn swap_handler(
                         },
                         &[&[
                             &ctx.accounts.token_swap.key().to_bytes(),
-                            &[ctx.bumps.authority],
+                            &[ctx.accounts.token_swap.bump_seed],
                         ]],
                     ),
                     to_u64(host_fee)?,
@@ -164,7 +264,7 @@ pub fn swap_handler(
                     },
                     &[&[
                         &ctx.accounts.token_swap.key().to_bytes(),
-                        &[ctx.bumps.authority],
+                        &[ctx.accounts.token_swap.bump_seed],
                     ]],
                 ),
                 to_u64(pool_token_amount)?,
@@ -172,45 +272,89 @@ pub fn swap_handler(
         }
     }
 
-    anchor_spl::token_interface::transfer_checked(
+    transfer_checked_with_hook(
         CpiContext::new_with_signer(
             ctx.accounts.token_destination_program.to_account_info(),
             anchor_spl::token_interface::TransferChecked {
                 from: ctx.accounts.swap_destination.to_account_info(),
-                to: ctx.accounts.user_destination.to_account_info(),
+                to: user_destination.to_account_info(),
                 authority: ctx.accounts.authority.to_account_info(),
                 mint: ctx.accounts.destination_token_mint.to_account_info(),
             },
             &[&[
                 &ctx.accounts.token_swap.key().to_bytes(),
-                &[ctx.bumps.authority],
+                &[ctx.accounts.token_swap.bump_seed],
             ]],
-        ),
+        )
+        .with_remaining_accounts(destination_hook_accounts.to_vec()),
         destination_transfer_amount,
         destination_mint_decimals,
     )?;
+    user_source.unwrap()?;
+    user_destination.unwrap()?;
 
+    // the reserves are what the curve computed, anything else received by
+    // the swap is left as excess
+    let token_swap = &mut ctx.accounts.token_swap;
+    token_swap.reserve_a = to_u64(swap_token_a_amount)?;
+    token_swap.reserve_b = to_u64(swap_token_b_amount)?;
+    #[cfg(feature = "invariant-check")]
+    {
+        ctx.accounts.swap_destination.reload()?;
+        ctx.accounts.pool_mint.reload()?;
+        pool_value.check(
+            &ctx.accounts.token_swap,
+            balances(
+                trade_direction,
+                ctx.accounts.swap_source.amount,
+                ctx.accounts.swap_destination.amount,
+            ),
+            ctx.accounts.pool_mint.supply,
+        )?;
+    }
+    emit_event!(
+        ctx,
+        Swapped {
+            token_swap: ctx.accounts.token_swap.key(),
+            user: ctx.accounts.user_transfer_authority.key(),
+            source_mint: ctx.accounts.source_token_mint.key(),
+            destination_mint: ctx.accounts.destination_token_mint.key(),
+            amount_in: source_transfer_amount,
+            amount_out: destination_transfer_amount,
+            trade_fee: to_u64(result.trade_fee)?,
+            owner_fee: to_u64(result.owner_fee)?,
+            host_fee: to_u64(host_fee)?,
+            reserve_a: ctx.accounts.token_swap.reserve_a,
+            reserve_b: ctx.accounts.token_swap.reserve_b,
+        }
+    );
     Ok(())
 }
 
+#[cfg_attr(feature = "event-cpi", event_cpi)]
 #[derive(Accounts)]
 pub struct TokenSwap<'info> {
     #[account(
+        mut,
         constraint = !token_swap.to_account_info().data_is_empty() @ SwapError::IncorrectSwapAccount,
+        constraint = token_swap.status.allows_swaps() @ SwapError::OperationPaused,
+        constraint = token_swap.flash_loan.is_none() @ SwapError::FlashLoanInProgress,
     )]
-    pub token_swap: Account<'info, SwapV1>,
+    pub token_swap: Account<'info, SwapV2>,
     #[account(
         seeds = [token_swap.key().as_ref()],
-        bump,
+        bump = token_swap.bump_seed,
     )]
     pub authority: AccountInfo<'info>,
     pub user_transfer_authority: Signer<'info>,
+    /// Omitted to swap SOL of the user transfer authority, wrapped in its
+    /// temporary wrapped SOL account
     #[account(
         mut,
         token::mint = swap_source.mint,
        constraint = user_source.key() != swap_source.key() @ SwapError::InvalidInput
     )]
-    pub user_source: InterfaceAccount<'info, TokenAccount>,
+    pub user_source: Option<InterfaceAccount<'info, TokenAccount>>,
 
     #[account(
         mut,
@@ -221,12 +365,14 @@ pub struct TokenSwap<'info> {
     )]
     pub swap_source: InterfaceAccount<'info, TokenAccount>,
 
+    /// Omitted to receive SOL in the user transfer authority, unwrapped
+    /// from its temporary wrapped SOL account
     #[account(
         mut,
         token::mint = swap_destination.mint,
         constraint = user_destination.key() != swap_destination.key() @ SwapError::SameAccountTransfer
     )]
-    pub user_destination: InterfaceAccount<'info, TokenAccount>,
+    pub user_destination: Option<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
         token::mint = swap_destination.mint,
@@ -263,8 +409,41 @@ pub struct TokenSwap<'info> {
     )]
     pub destination_token_mint: InterfaceAccount<'info, Mint>,
 
+    #[account(
+        constraint = token_swap.token_program(&source_token_mint.key()) == Some(&token_source_program.key()) @ SwapError::IncorrectTokenProgramId,
+    )]
     pub token_source_program: Interface<'info, TokenInterface>,
+    #[account(
+        constraint = token_swap.token_program(&destination_token_mint.key()) == Some(&token_destination_program.key()) @ SwapError::IncorrectTokenProgramId,
+    )]
     pub token_destination_program: Interface<'info, TokenInterface>,
+    #[account(
+        constraint = token_pool_program.key() == token_swap.pool_token_program @ SwapError::IncorrectTokenProgramId,
+    )]
     pub token_pool_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
+    /// Temporary wrapped SOL account of the user transfer authority, only
+    /// required when a wrapped SOL account of the user is omitted, in which
+    /// case the user transfer authority funds it and must be writable
+    #[account(
+        mut,
+        seeds = [NATIVE_SOL_SEED, user_transfer_authority.key().as_ref()],
+        bump,
+    )]
+    pub native_sol_account: Option<UncheckedAccount<'info>>,
+    /// Pays for `user_destination_ata` when it is created
+    #[account(mut)]
+    pub payer: Option<Signer<'info>>,
+    /// Associated token account of the user transfer authority for the
+    /// destination mint, created if needed and used when
+    /// `user_destination` is omitted
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = destination_token_mint,
+        associated_token::authority = user_transfer_authority,
+        associated_token::token_program = token_destination_program,
+    )]
+    pub user_destination_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    pub associated_token_program: Option<Program<'info, AssociatedToken>>,
 }
@@ -0,0 +1,47 @@
+use {
+    crate::{
+        events::{emit_event, ReservesSynced},
+        SwapError, SwapV2,
+    },
+    anchor_lang::prelude::*,
+    anchor_spl::token_interface::TokenAccount,
+};
+
+/// Set the reserves of the swap to the balances of its token accounts,
+/// handing any tokens donated to the swap to its liquidity providers. Anyone
+/// may sync a swap.
+pub fn sync_handler(ctx: Context<SyncReserves>) -> Result<()> {
+    let token_swap = &mut ctx.accounts.token_swap;
+    token_swap.update_cumulative_prices()?;
+    token_swap.reserve_a = ctx.accounts.swap_token_a.amount;
+    token_swap.reserve_b = ctx.accounts.swap_token_b.amount;
+
+    emit_event!(
+        ctx,
+        ReservesSynced {
+            token_swap: ctx.accounts.token_swap.key(),
+            reserve_a: ctx.accounts.token_swap.reserve_a,
+            reserve_b: ctx.accounts.token_swap.reserve_b,
+        }
+    );
+    Ok(())
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SyncReserves<'info> {
+    #[account(
+        mut,
+        constraint = !token_swap.to_account_info().data_is_empty() @ SwapError::IncorrectSwapAccount,
+        constraint = token_swap.flash_loan.is_none() @ SwapError::FlashLoanInProgress,
+    )]
+    pub token_swap: Account<'info, SwapV2>,
+    #[account(
+        constraint = swap_token_a.key() == token_swap.token_a @ SwapError::IncorrectSwapAccount,
+    )]
+    pub swap_token_a: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        constraint = swap_token_b.key() == token_swap.token_b @ SwapError::IncorrectSwapAccount,
+    )]
+    pub swap_token_b: InterfaceAccount<'info, TokenAccount>,
+}
@@ -0,0 +1,42 @@
+use {
+    crate::{
+        events::{emit_event, ConfigUpdated},
+        instructions::CONFIG_SEED,
+        program::AnchorTokenSwap,
+        SwapConfig, SwapConstraints, SwapError,
+    },
+    anchor_lang::prelude::*,
+};
+
+pub fn update_config_handler(
+    ctx: Context<UpdateConfig>,
+    constraints: Option<SwapConstraints>,
+) -> Result<()> {
+    if let Some(constraints) = &constraints {
+        constraints.validate()?;
+    }
+    ctx.accounts.config.constraints = constraints.clone();
+
+    emit_event!(ctx, ConfigUpdated { constraints });
+    Ok(())
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump,
+    )]
+    pub config: Account<'info, SwapConfig>,
+    pub upgrade_authority: Signer<'info>,
+    #[account(
+        constraint = swap_program.programdata_address()? == Some(program_data.key()) @ SwapError::InvalidUpgradeAuthority
+    )]
+    pub swap_program: Program<'info, AnchorTokenSwap>,
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(upgrade_authority.key()) @ SwapError::InvalidUpgradeAuthority
+    )]
+    pub program_data: Account<'info, ProgramData>,
+}
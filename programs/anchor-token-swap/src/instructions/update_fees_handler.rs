@@ -0,0 +1,49 @@
+use {
+    crate::{
+        events::{emit_event, FeesUpdated},
+        instructions::CONFIG_SEED,
+        Fees, SwapConfig, SwapError, SwapV2,
+    },
+    anchor_lang::prelude::*,
+    anchor_spl::token_interface::TokenAccount,
+};
+
+/// Replace the fees of a swap, checked against the swap constraints.
+///
+/// The trade fee may be repriced as well: the fee tier stays the trade fee
+/// the swap was created with, since it is part of the address of the swap.
+pub fn update_fees_handler(ctx: Context<UpdateFees>, new_fees: Fees) -> Result<()> {
+    new_fees.validate()?;
+    let old_fees = std::mem::replace(&mut ctx.accounts.token_swap.fees, new_fees.clone());
+
+    emit_event!(
+        ctx,
+        FeesUpdated {
+            token_swap: ctx.accounts.token_swap.key(),
+            old_fees,
+            new_fees,
+        }
+    );
+    Ok(())
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct UpdateFees<'info> {
+    #[account(
+        mut,
+        has_one = admin @ SwapError::InvalidAdmin,
+        constraint = !token_swap.to_account_info().data_is_empty() @ SwapError::IncorrectSwapAccount,
+    )]
+    pub token_swap: Account<'info, SwapV2>,
+    pub admin: Signer<'info>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump,
+    )]
+    pub config: Account<'info, SwapConfig>,
+    #[account(
+        constraint = pool_fee_account.key() == token_swap.pool_fee_account.key() @ SwapError::InvalidFeeAccount
+    )]
+    pub pool_fee_account: InterfaceAccount<'info, TokenAccount>,
+}
@@ -1,31 +1,51 @@
+#[cfg(feature = "invariant-check")]
+use crate::invariant::PoolValue;
 use {
     crate::{
         curves::{RoundDirection, SwapCurve},
-        helper::to_u64,
-        SwapError, SwapState, SwapV1,
+        events::{emit_event, Withdrawn},
+        helper::{
+            split_hook_accounts, to_u64, transfer_checked_with_hook, UserTokenAccount,
+            NATIVE_SOL_SEED,
+        },
+        SwapError, SwapState, SwapV2,
     },
     anchor_lang::prelude::*,
-    anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface},
+    anchor_spl::{
+        associated_token::AssociatedToken,
+        token_interface::{Mint, TokenAccount, TokenInterface},
+    },
     std::cmp::min,
 };
 
-pub fn withdraw_all_token_types_handler(
-    ctx: Context<WithdrawAllTokenTypes>,
+pub fn withdraw_all_token_types_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, WithdrawAllTokenTypes<'info>>,
     pool_token_amount: u64,
     min_a_amount_slippage: u64,
     min_b_amount_slippage: u64,
 ) -> Result<()> {
-    let swap_curve = SwapCurve::new(ctx.accounts.swap_v1.curve_type);
+    ctx.accounts.token_swap.update_cumulative_prices()?;
+    let balances_before = (
+        ctx.accounts.swap_token_a.amount,
+        ctx.accounts.swap_token_b.amount,
+    );
+    #[cfg(feature = "invariant-check")]
+    let pool_value = PoolValue::new(
+        &ctx.accounts.token_swap,
+        balances_before,
+        ctx.accounts.pool_mint.supply,
+    )?;
+    let swap_curve = SwapCurve::new(ctx.accounts.token_swap.curve_type);
     let calculator = swap_curve.calculator;
     let withdraw_fee = match &ctx.accounts.pool_fee_account {
         Some(_) => {
-            if ctx.accounts.swap_v1.pool_fee_account.key()
+            if ctx.accounts.token_swap.pool_fee_account.key()
                 == ctx.accounts.user_pool_token_source.key()
             {
                 0
             } else {
                 ctx.accounts
-                    .swap_v1
+                    .token_swap
                     .fees()
                     .owner_withdraw_fee(pool_token_amount as u128)
                     .ok_or(SwapError::FeeCalculationFailure)?
@@ -40,54 +60,54 @@ pub fn withdraw_all_token_types_handler(
         .pool_tokens_to_trading_tokens(
             pool_token_amount,
             u128::from(ctx.accounts.pool_mint.supply),
-            u128::from(ctx.accounts.swap_token_a.amount),
-            u128::from(ctx.accounts.swap_token_b.amount),
+            u128::from(ctx.accounts.token_swap.reserve_a),
+            u128::from(ctx.accounts.token_swap.reserve_b),
             RoundDirection::Floor,
         )
         .ok_or(SwapError::ZeroTradingTokens)?;
 
     let mut token_a_amount = to_u64(results.token_a_amount)?;
-    token_a_amount = min(token_a_amount, ctx.accounts.swap_token_a.amount);
+    token_a_amount = min(token_a_amount, ctx.accounts.token_swap.reserve_a);
     require_gte!(
         token_a_amount,
         min_a_amount_slippage,
         SwapError::ExceededSlippage
     );
     require!(
-        token_a_amount != 0 || ctx.accounts.swap_token_a.amount == 0,
+        token_a_amount != 0 || ctx.accounts.token_swap.reserve_a == 0,
         SwapError::ZeroTradingTokens
     );
     let mut token_b_amount = to_u64(results.token_b_amount)?;
-    token_b_amount = min(token_b_amount, ctx.accounts.swap_token_b.amount);
+    token_b_amount = min(token_b_amount, ctx.accounts.token_swap.reserve_b);
     require_gte!(
         token_b_amount,
         min_b_amount_slippage,
         SwapError::ExceededSlippage
     );
     require!(
-        token_b_amount != 0 || ctx.accounts.swap_token_b.amount == 0,
+        token_b_amount != 0 || ctx.accounts.token_swap.reserve_b == 0,
         SwapError::ZeroTradingTokens
     );
-    if withdraw_fee > 0 && ctx.accounts.pool_fee_account.is_some() {
-        anchor_spl::token_interface::transfer_checked(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_pool_program.to_account_info(),
-                anchor_spl::token_interface::TransferChecked {
-                    from: ctx.accounts.user_pool_token_source.to_account_info(),
-                    to: ctx
-                        .accounts
-                        .pool_fee_account
-                        .as_ref()
-                        .unwrap()
-                        .to_account_info(),
-                    authority: ctx.accounts.user_transfer_authority.to_account_info(),
-                    mint: ctx.accounts.pool_mint.to_account_info(),
-                },
-                &[&[ctx.accounts.swap_v1.key().as_ref(), &[ctx.bumps.authority]]],
-            ),
-            to_u64(withdraw_fee)?,
-            ctx.accounts.pool_mint.decimals,
-        )?;
+    if withdraw_fee > 0 {
+        if let Some(pool_fee_account) = &ctx.accounts.pool_fee_account {
+            anchor_spl::token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_pool_program.to_account_info(),
+                    anchor_spl::token_interface::TransferChecked {
+                        from: ctx.accounts.user_pool_token_source.to_account_info(),
+                        to: pool_fee_account.to_account_info(),
+                        authority: ctx.accounts.user_transfer_authority.to_account_info(),
+                        mint: ctx.accounts.pool_mint.to_account_info(),
+                    },
+                    &[&[
+                        ctx.accounts.token_swap.key().as_ref(),
+                        &[ctx.accounts.token_swap.bump_seed],
+                    ]],
+                ),
+                to_u64(withdraw_fee)?,
+                ctx.accounts.pool_mint.decimals,
+            )?;
+        }
     }
     anchor_spl::token_interface::burn_checked(
         CpiContext::new(
@@ -98,57 +118,140 @@ pub fn withdraw_all_token_types_handler(
                 mint: ctx.accounts.pool_mint.to_account_info(),
             },
         ),
-        token_a_amount,
-        ctx.accounts.token_a_mint.decimals,
+        to_u64(pool_token_amount)?,
+        ctx.accounts.pool_mint.decimals,
     )?;
 
+    let destination_a = UserTokenAccount::new(
+        ctx.accounts
+            .destination_a
+            .as_ref()
+            .or(ctx.accounts.destination_a_ata.as_deref()),
+        ctx.accounts.native_sol_account.as_ref(),
+        ctx.bumps.native_sol_account,
+        &ctx.accounts.user_transfer_authority,
+        &ctx.accounts.token_a_mint,
+        &ctx.accounts.token_a_program,
+        &ctx.accounts.system_program,
+    )?;
+    let destination_b = UserTokenAccount::new(
+        ctx.accounts
+            .destination_b
+            .as_ref()
+            .or(ctx.accounts.destination_b_ata.as_deref()),
+        ctx.accounts.native_sol_account.as_ref(),
+        ctx.bumps.native_sol_account,
+        &ctx.accounts.user_transfer_authority,
+        &ctx.accounts.token_b_mint,
+        &ctx.accounts.token_b_program,
+        &ctx.accounts.system_program,
+    )?;
+    destination_a.wrap(0)?;
+    destination_b.wrap(0)?;
+    let mut remaining_accounts = ctx.remaining_accounts;
+    let token_a_hook_accounts = split_hook_accounts(
+        &ctx.accounts.token_a_mint.to_account_info(),
+        &mut remaining_accounts,
+    )?;
+    let token_b_hook_accounts = split_hook_accounts(
+        &ctx.accounts.token_b_mint.to_account_info(),
+        &mut remaining_accounts,
+    )?;
     if token_a_amount > 0 {
-        anchor_spl::token_interface::transfer_checked(
+        transfer_checked_with_hook(
             CpiContext::new_with_signer(
                 ctx.accounts.token_a_program.to_account_info(),
                 anchor_spl::token_interface::TransferChecked {
                     from: ctx.accounts.swap_token_a.to_account_info(),
-                    to: ctx.accounts.destination_a.to_account_info(),
+                    to: destination_a.to_account_info(),
                     authority: ctx.accounts.authority.to_account_info(),
                     mint: ctx.accounts.token_a_mint.to_account_info(),
                 },
-                &[&[ctx.accounts.swap_v1.key().as_ref(), &[ctx.bumps.authority]]],
-            ),
+                &[&[
+                    ctx.accounts.token_swap.key().as_ref(),
+                    &[ctx.accounts.token_swap.bump_seed],
+                ]],
+            )
+            .with_remaining_accounts(token_a_hook_accounts.to_vec()),
             token_a_amount,
             ctx.accounts.token_a_mint.decimals,
         )?;
     }
     if token_b_amount > 0 {
-        anchor_spl::token_interface::transfer_checked(
+        transfer_checked_with_hook(
             CpiContext::new_with_signer(
                 ctx.accounts.token_b_program.to_account_info(),
                 anchor_spl::token_interface::TransferChecked {
                     from: ctx.accounts.swap_token_b.to_account_info(),
-                    to: ctx.accounts.destination_b.to_account_info(),
+                    to: destination_b.to_account_info(),
                     authority: ctx.accounts.authority.to_account_info(),
                     mint: ctx.accounts.token_b_mint.to_account_info(),
                 },
-                &[&[ctx.accounts.swap_v1.key().as_ref(), &[ctx.bumps.authority]]],
-            ),
+                &[&[
+                    ctx.accounts.token_swap.key().as_ref(),
+                    &[ctx.accounts.token_swap.bump_seed],
+                ]],
+            )
+            .with_remaining_accounts(token_b_hook_accounts.to_vec()),
             token_b_amount,
             ctx.accounts.token_b_mint.decimals,
         )?;
     }
+    destination_a.unwrap()?;
+    destination_b.unwrap()?;
 
+    ctx.accounts.swap_token_a.reload()?;
+    ctx.accounts.swap_token_b.reload()?;
+    ctx.accounts.token_swap.update_reserves(
+        balances_before,
+        (
+            ctx.accounts.swap_token_a.amount,
+            ctx.accounts.swap_token_b.amount,
+        ),
+    )?;
+    #[cfg(feature = "invariant-check")]
+    {
+        ctx.accounts.pool_mint.reload()?;
+        pool_value.check(
+            &ctx.accounts.token_swap,
+            (
+                ctx.accounts.swap_token_a.amount,
+                ctx.accounts.swap_token_b.amount,
+            ),
+            ctx.accounts.pool_mint.supply,
+        )?;
+    }
+    emit_event!(
+        ctx,
+        Withdrawn {
+            token_swap: ctx.accounts.token_swap.key(),
+            user: ctx.accounts.user_transfer_authority.key(),
+            token_a_amount,
+            token_b_amount,
+            pool_tokens_burned: to_u64(pool_token_amount)?,
+            withdraw_fee: to_u64(withdraw_fee)?,
+            reserve_a: ctx.accounts.token_swap.reserve_a,
+            reserve_b: ctx.accounts.token_swap.reserve_b,
+        }
+    );
     Ok(())
 }
 
+#[cfg_attr(feature = "event-cpi", event_cpi)]
 #[derive(Accounts)]
 pub struct WithdrawAllTokenTypes<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
     #[account(
-      constraint = !swap_v1.to_account_info().data_is_empty() @ SwapError::IncorrectSwapAccount,
+      mut,
+      constraint = !token_swap.to_account_info().data_is_empty() @ SwapError::IncorrectSwapAccount,
+      constraint = token_swap.status.allows_withdrawals() @ SwapError::OperationPaused,
+      constraint = token_swap.flash_loan.is_none() @ SwapError::FlashLoanInProgress,
   )]
-    pub swap_v1: Account<'info, SwapV1>,
+    pub token_swap: Account<'info, SwapV2>,
     #[account(
-      seeds = [swap_v1.key().as_ref()],
-      bump,
+      seeds = [token_swap.key().as_ref()],
+      bump = token_swap.bump_seed,
   )]
     pub authority: AccountInfo<'info>,
 
@@ -157,48 +260,52 @@ pub struct WithdrawAllTokenTypes<'info> {
       mut,
       token::mint = token_a_mint.key(),
       constraint = swap_token_a.owner == authority.key() @ SwapError::InvalidInput,
-      constraint = swap_token_a.key() == swap_v1.token_a @ SwapError::InvalidInput,
+      constraint = swap_token_a.key() == token_swap.token_a @ SwapError::InvalidInput,
     )]
     pub swap_token_a: InterfaceAccount<'info, TokenAccount>,
     #[account(
       mut,
       token::mint = token_b_mint.key(),
       constraint = swap_token_a.owner == authority.key() @ SwapError::InvalidInput,
-      constraint = swap_token_b.key() == swap_v1.token_b @ SwapError::InvalidInput,
+      constraint = swap_token_b.key() == token_swap.token_b @ SwapError::InvalidInput,
     )]
     pub swap_token_b: InterfaceAccount<'info, TokenAccount>,
     #[account(
       mut,
       mint::token_program = token_pool_program.key(),
-      constraint = pool_mint.key() == swap_v1.pool_mint @ SwapError::IncorrectPoolMint,
+      constraint = pool_mint.key() == token_swap.pool_mint @ SwapError::IncorrectPoolMint,
     )]
     pub pool_mint: InterfaceAccount<'info, Mint>,
+    /// Omitted to receive SOL in the user transfer authority, unwrapped
+    /// from its temporary wrapped SOL account
     #[account(
       mut,
       token::mint = token_a_mint.key(),
       constraint = destination_a.key() != swap_token_a.key() @ SwapError::InvalidInput
     )]
-    pub destination_a: InterfaceAccount<'info, TokenAccount>,
+    pub destination_a: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Omitted to receive SOL in the user transfer authority, unwrapped
+    /// from its temporary wrapped SOL account
     #[account(
       mut,
       token::mint = token_b_mint.key(),
       constraint = destination_b.key() != swap_token_b.key() @ SwapError::InvalidInput,
     )]
-    pub destination_b: InterfaceAccount<'info, TokenAccount>,
+    pub destination_b: Option<InterfaceAccount<'info, TokenAccount>>,
     #[account(
       mint::token_program = token_a_program.key(),
-      constraint = token_a_mint.key() == swap_v1.token_a_mint @ SwapError::InvalidInput,
+      constraint = token_a_mint.key() == token_swap.token_a_mint @ SwapError::InvalidInput,
     )]
     pub token_a_mint: InterfaceAccount<'info, Mint>,
     #[account(
       mint::token_program = token_b_program.key(),
-      constraint = token_b_mint.key() == swap_v1.token_b_mint @ SwapError::InvalidInput,
+      constraint = token_b_mint.key() == token_swap.token_b_mint @ SwapError::InvalidInput,
     )]
     pub token_b_mint: InterfaceAccount<'info, Mint>,
     #[account(
       mut,
       token::mint = pool_mint.key(),
-      constraint = pool_fee_account.key() == swap_v1.pool_fee_account @ SwapError::InvalidInput,
+      constraint = pool_fee_account.key() == token_swap.pool_fee_account @ SwapError::InvalidInput,
     )]
     pub pool_fee_account: Option<InterfaceAccount<'info, TokenAccount>>,
     #[account(
@@ -208,7 +315,46 @@ pub struct WithdrawAllTokenTypes<'info> {
     )]
     pub user_pool_token_source: InterfaceAccount<'info, TokenAccount>,
     pub system_program: Program<'info, System>,
+    #[account(
+        constraint = token_a_program.key() == token_swap.token_a_program @ SwapError::IncorrectTokenProgramId,
+    )]
     pub token_a_program: Interface<'info, TokenInterface>,
+    #[account(
+        constraint = token_b_program.key() == token_swap.token_b_program @ SwapError::IncorrectTokenProgramId,
+    )]
     pub token_b_program: Interface<'info, TokenInterface>,
+    #[account(
+        constraint = token_pool_program.key() == token_swap.pool_token_program @ SwapError::IncorrectTokenProgramId,
+    )]
     pub token_pool_program: Interface<'info, TokenInterface>,
+    /// Temporary wrapped SOL account of the user transfer authority, only
+    /// required when a wrapped SOL account of the user is omitted, in which
+    /// case the user transfer authority funds it and must be writable
+    #[account(
+        mut,
+        seeds = [NATIVE_SOL_SEED, user_transfer_authority.key().as_ref()],
+        bump,
+    )]
+    pub native_sol_account: Option<UncheckedAccount<'info>>,
+    /// Associated token account of the user transfer authority for token
+    /// A, created if needed and used when `destination_a` is omitted
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = token_a_mint,
+        associated_token::authority = user_transfer_authority,
+        associated_token::token_program = token_a_program,
+    )]
+    pub destination_a_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    /// Associated token account of the user transfer authority for token
+    /// B, created if needed and used when `destination_b` is omitted
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = token_b_mint,
+        associated_token::authority = user_transfer_authority,
+        associated_token::token_program = token_b_program,
+    )]
+    pub destination_b_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    pub associated_token_program: Option<Program<'info, AssociatedToken>>,
 }
@@ -0,0 +1,203 @@
+#[cfg(feature = "invariant-check")]
+use crate::invariant::PoolValue;
+use {
+    crate::{
+        curves::TradeDirection,
+        events::{emit_event, Withdrawn},
+        helper::{
+            split_hook_accounts, to_u64, transfer_checked_with_hook, transfer_fee, UserTokenAccount,
+        },
+        instructions::WithdrawSingleTokenType,
+        SwapError, SwapState,
+    },
+    anchor_lang::prelude::*,
+};
+
+pub fn withdraw_single_token_type_exact_amount_in_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, WithdrawSingleTokenType<'info>>,
+    pool_token_amount: u64,
+    minimum_token_out: u64,
+) -> Result<()> {
+    ctx.accounts.token_swap.update_cumulative_prices()?;
+    let balances_before = (
+        ctx.accounts.swap_token_a.amount,
+        ctx.accounts.swap_token_b.amount,
+    );
+    #[cfg(feature = "invariant-check")]
+    let pool_value = PoolValue::new(
+        &ctx.accounts.token_swap,
+        balances_before,
+        ctx.accounts.pool_mint.supply,
+    )?;
+    let user_token_destination = UserTokenAccount::new(
+        ctx.accounts
+            .user_token_destination
+            .as_ref()
+            .or(ctx.accounts.user_token_destination_ata.as_deref()),
+        ctx.accounts.native_sol_account.as_ref(),
+        ctx.bumps.native_sol_account,
+        &ctx.accounts.user_transfer_authority,
+        &ctx.accounts.destination_token_mint,
+        &ctx.accounts.destination_token_program,
+        &ctx.accounts.system_program,
+    )?;
+    let trade_direction =
+        if ctx.accounts.destination_token_mint.key() == ctx.accounts.swap_token_a.mint {
+            require_keys_neq!(
+                user_token_destination.key(),
+                ctx.accounts.swap_token_a.key(),
+                SwapError::SameAccountTransfer
+            );
+            TradeDirection::AtoB
+        } else if ctx.accounts.destination_token_mint.key() == ctx.accounts.swap_token_b.mint {
+            require_keys_neq!(
+                user_token_destination.key(),
+                ctx.accounts.swap_token_b.key(),
+                SwapError::SameAccountTransfer
+            );
+            TradeDirection::BtoA
+        } else {
+            return err!(SwapError::IncorrectSwapAccount);
+        };
+
+    let withdraw_fee = match &ctx.accounts.pool_fee_account {
+        Some(pool_fee_account)
+            if pool_fee_account.key() != ctx.accounts.pool_token_source.key() =>
+        {
+            ctx.accounts
+                .token_swap
+                .fees()
+                .owner_withdraw_fee(u128::from(pool_token_amount))
+                .ok_or(SwapError::FeeCalculationFailure)?
+        }
+        _ => 0,
+    };
+    let burn_pool_token_amount = u128::from(pool_token_amount)
+        .checked_sub(withdraw_fee)
+        .ok_or(SwapError::CalculationFailure)?;
+    let destination_token_amount = ctx
+        .accounts
+        .token_swap
+        .swap_curve()
+        .withdraw_single_token_type_exact_in(
+            burn_pool_token_amount,
+            u128::from(ctx.accounts.token_swap.reserve_a),
+            u128::from(ctx.accounts.token_swap.reserve_b),
+            u128::from(ctx.accounts.pool_mint.supply),
+            trade_direction,
+            ctx.accounts.token_swap.fees(),
+        )
+        .ok_or(SwapError::ZeroTradingTokens)?;
+    let destination_token_amount = to_u64(destination_token_amount)?;
+    require_neq!(destination_token_amount, 0, SwapError::ZeroTradingTokens);
+    // the slippage limit applies to what the user receives, after any
+    // transfer fee of the destination mint
+    let amount_received = destination_token_amount.saturating_sub(transfer_fee(
+        &ctx.accounts.destination_token_mint,
+        destination_token_amount,
+    )?);
+    require_gte!(
+        amount_received,
+        minimum_token_out,
+        SwapError::ExceededSlippage
+    );
+
+    if withdraw_fee > 0 {
+        if let Some(pool_fee_account) = &ctx.accounts.pool_fee_account {
+            anchor_spl::token_interface::transfer_checked(
+                CpiContext::new(
+                    ctx.accounts.token_pool_program.to_account_info(),
+                    anchor_spl::token_interface::TransferChecked {
+                        from: ctx.accounts.pool_token_source.to_account_info(),
+                        to: pool_fee_account.to_account_info(),
+                        authority: ctx.accounts.user_transfer_authority.to_account_info(),
+                        mint: ctx.accounts.pool_mint.to_account_info(),
+                    },
+                ),
+                to_u64(withdraw_fee)?,
+                ctx.accounts.pool_mint.decimals,
+            )?;
+        }
+    }
+    anchor_spl::token_interface::burn_checked(
+        CpiContext::new(
+            ctx.accounts.token_pool_program.to_account_info(),
+            anchor_spl::token_interface::BurnChecked {
+                mint: ctx.accounts.pool_mint.to_account_info(),
+                from: ctx.accounts.pool_token_source.to_account_info(),
+                authority: ctx.accounts.user_transfer_authority.to_account_info(),
+            },
+        ),
+        to_u64(burn_pool_token_amount)?,
+        ctx.accounts.pool_mint.decimals,
+    )?;
+    let from_token_account = match trade_direction {
+        TradeDirection::AtoB => ctx.accounts.swap_token_a.to_account_info(),
+        TradeDirection::BtoA => ctx.accounts.swap_token_b.to_account_info(),
+    };
+    user_token_destination.wrap(0)?;
+    let mut remaining_accounts = ctx.remaining_accounts;
+    let destination_hook_accounts = split_hook_accounts(
+        &ctx.accounts.destination_token_mint.to_account_info(),
+        &mut remaining_accounts,
+    )?;
+    transfer_checked_with_hook(
+        CpiContext::new_with_signer(
+            ctx.accounts.destination_token_program.to_account_info(),
+            anchor_spl::token_interface::TransferChecked {
+                from: from_token_account,
+                to: user_token_destination.to_account_info(),
+                authority: ctx.accounts.authority.to_account_info(),
+                mint: ctx.accounts.destination_token_mint.to_account_info(),
+            },
+            &[&[
+                &ctx.accounts.token_swap.key().to_bytes(),
+                &[ctx.accounts.token_swap.bump_seed],
+            ]],
+        )
+        .with_remaining_accounts(destination_hook_accounts.to_vec()),
+        destination_token_amount,
+        ctx.accounts.destination_token_mint.decimals,
+    )?;
+    user_token_destination.unwrap()?;
+
+    ctx.accounts.swap_token_a.reload()?;
+    ctx.accounts.swap_token_b.reload()?;
+    ctx.accounts.token_swap.update_reserves(
+        balances_before,
+        (
+            ctx.accounts.swap_token_a.amount,
+            ctx.accounts.swap_token_b.amount,
+        ),
+    )?;
+    #[cfg(feature = "invariant-check")]
+    {
+        ctx.accounts.pool_mint.reload()?;
+        pool_value.check(
+            &ctx.accounts.token_swap,
+            (
+                ctx.accounts.swap_token_a.amount,
+                ctx.accounts.swap_token_b.amount,
+            ),
+            ctx.accounts.pool_mint.supply,
+        )?;
+    }
+    let (token_a_amount, token_b_amount) = match trade_direction {
+        TradeDirection::AtoB => (destination_token_amount, 0),
+        TradeDirection::BtoA => (0, destination_token_amount),
+    };
+    emit_event!(
+        ctx,
+        Withdrawn {
+            token_swap: ctx.accounts.token_swap.key(),
+            user: ctx.accounts.user_transfer_authority.key(),
+            token_a_amount,
+            token_b_amount,
+            pool_tokens_burned: pool_token_amount,
+            withdraw_fee: to_u64(withdraw_fee)?,
+            reserve_a: ctx.accounts.token_swap.reserve_a,
+            reserve_b: ctx.accounts.token_swap.reserve_b,
+        }
+    );
+    Ok(())
+}
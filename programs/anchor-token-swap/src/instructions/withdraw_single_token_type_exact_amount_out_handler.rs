@@ -1,28 +1,112 @@
+#[cfg(feature = "invariant-check")]
+use crate::invariant::PoolValue;
 use {
-    crate::{curves::TradeDirection, helper::to_u64, SwapError, SwapState, SwapV1},
+    crate::{
+        curves::TradeDirection,
+        events::{emit_event, Withdrawn},
+        helper::{
+            split_hook_accounts, to_u64, transfer_checked_with_hook, UserTokenAccount,
+            NATIVE_SOL_SEED,
+        },
+        SwapError, SwapState, SwapV2,
+    },
     anchor_lang::prelude::*,
-    anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface},
+    anchor_spl::{
+        associated_token::AssociatedToken,
+        token_interface::{Mint, TokenAccount, TokenInterface},
+    },
 };
 
-pub fn withdraw_single_token_type_exact_amount_out_handler(
-    ctx: Context<WithdrawSingleTokenTypeExactAmountOut>,
+/// Pool tokens taken from the user by a single-sided withdrawal of
+/// `destination_token_amount`, shared by
+/// `withdraw_single_token_type_exact_amount_out` and `quote_withdraw_single`
+pub struct WithdrawSingleCalculation {
+    /// Total pool tokens burned, including the withdraw fee
+    pub pool_token_amount: u128,
+    /// Owner withdraw fee in pool tokens
+    pub withdraw_fee: u128,
+}
+
+/// Run the curve calculation of a single-sided withdrawal, the owner withdraw
+/// fee is only charged if `charge_withdraw_fee` is set
+pub fn calculate_withdraw_single(
+    token_swap: &SwapV2,
+    pool_mint: &InterfaceAccount<Mint>,
+    destination_token_amount: u64,
+    trade_direction: TradeDirection,
+    charge_withdraw_fee: bool,
+) -> Result<WithdrawSingleCalculation> {
+    let burn_pool_token_amount = token_swap
+        .swap_curve()
+        .withdraw_single_token_type_exact_out(
+            u128::from(destination_token_amount),
+            u128::from(token_swap.reserve_a),
+            u128::from(token_swap.reserve_b),
+            u128::from(pool_mint.supply),
+            trade_direction,
+            token_swap.fees(),
+        )
+        .ok_or(SwapError::ZeroTradingTokens)?;
+
+    let withdraw_fee = if charge_withdraw_fee {
+        token_swap
+            .fees()
+            .owner_withdraw_fee(burn_pool_token_amount)
+            .ok_or(SwapError::FeeCalculationFailure)?
+    } else {
+        0
+    };
+    let pool_token_amount = burn_pool_token_amount
+        .checked_add(withdraw_fee)
+        .ok_or(SwapError::FeeCalculationFailure)?;
+    if pool_token_amount == 0 {
+        return err!(SwapError::ZeroTradingTokens);
+    }
+    Ok(WithdrawSingleCalculation {
+        pool_token_amount,
+        withdraw_fee,
+    })
+}
+
+pub fn withdraw_single_token_type_exact_amount_out_handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, WithdrawSingleTokenType<'info>>,
     destination_token_amount: u64,
     maximum_pool_token_amount: u64,
 ) -> Result<()> {
-    let swap_v1 = &ctx.accounts.swap_v1;
-    let swap_curve = &swap_v1.swap_curve();
-
+    ctx.accounts.token_swap.update_cumulative_prices()?;
+    let balances_before = (
+        ctx.accounts.swap_token_a.amount,
+        ctx.accounts.swap_token_b.amount,
+    );
+    #[cfg(feature = "invariant-check")]
+    let pool_value = PoolValue::new(
+        &ctx.accounts.token_swap,
+        balances_before,
+        ctx.accounts.pool_mint.supply,
+    )?;
+    let user_token_destination = UserTokenAccount::new(
+        ctx.accounts
+            .user_token_destination
+            .as_ref()
+            .or(ctx.accounts.user_token_destination_ata.as_deref()),
+        ctx.accounts.native_sol_account.as_ref(),
+        ctx.bumps.native_sol_account,
+        &ctx.accounts.user_transfer_authority,
+        &ctx.accounts.destination_token_mint,
+        &ctx.accounts.destination_token_program,
+        &ctx.accounts.system_program,
+    )?;
     let trade_direction =
-        if ctx.accounts.user_token_destination.mint == ctx.accounts.swap_token_a.mint {
+        if ctx.accounts.destination_token_mint.key() == ctx.accounts.swap_token_a.mint {
             require_keys_neq!(
-                ctx.accounts.user_token_destination.key(),
+                user_token_destination.key(),
                 ctx.accounts.swap_token_a.key(),
                 SwapError::SameAccountTransfer
             );
             TradeDirection::AtoB
-        } else if ctx.accounts.user_token_destination.mint == ctx.accounts.swap_token_b.mint {
+        } else if ctx.accounts.destination_token_mint.key() == ctx.accounts.swap_token_b.mint {
             require_keys_neq!(
-                ctx.accounts.user_token_destination.key(),
+                user_token_destination.key(),
                 ctx.accounts.swap_token_b.key(),
                 SwapError::SameAccountTransfer
             );
@@ -31,57 +115,39 @@ pub fn withdraw_single_token_type_exact_amount_out_handler(
             return err!(SwapError::IncorrectSwapAccount);
         };
 
-    let pool_mint_supply = u128::from(ctx.accounts.pool_mint.supply);
-    let swap_token_a_amount = u128::from(ctx.accounts.swap_token_a.amount);
-    let swap_token_b_amount = u128::from(ctx.accounts.swap_token_b.amount);
-
-    let burn_pool_token_amount = swap_curve
-        .withdraw_single_token_type_exact_out(
-            u128::from(destination_token_amount),
-            swap_token_a_amount,
-            swap_token_b_amount,
-            pool_mint_supply,
-            trade_direction,
-            swap_v1.fees(),
-        )
-        .ok_or(SwapError::ZeroTradingTokens)?;
-
-    let withdraw_fee = match &ctx.accounts.pool_fee_account {
+    let charge_withdraw_fee = match &ctx.accounts.pool_fee_account {
         Some(ref pool_fee_account) => {
-            if pool_fee_account.key() == ctx.accounts.pool_token_source.key() {
-                0
-            } else {
-                swap_v1
-                    .fees()
-                    .owner_withdraw_fee(burn_pool_token_amount)
-                    .ok_or(SwapError::FeeCalculationFailure)?
-            }
+            pool_fee_account.key() != ctx.accounts.pool_token_source.key()
         }
-        None => 0,
+        None => false,
     };
-    let pool_token_amount = burn_pool_token_amount
-        .checked_add(withdraw_fee)
-        .ok_or(SwapError::FeeCalculationFailure)?;
+    let WithdrawSingleCalculation {
+        pool_token_amount,
+        withdraw_fee,
+    } = calculate_withdraw_single(
+        &ctx.accounts.token_swap,
+        &ctx.accounts.pool_mint,
+        destination_token_amount,
+        trade_direction,
+        charge_withdraw_fee,
+    )?;
     if to_u64(pool_token_amount)? > maximum_pool_token_amount {
         return err!(SwapError::ExceededSlippage);
     }
-    if pool_token_amount == 0 {
-        return err!(SwapError::ZeroTradingTokens);
-    }
     if withdraw_fee > 0 {
         if let Some(pool_fee_account) = &ctx.accounts.pool_fee_account {
             anchor_spl::token_interface::transfer_checked(
                 CpiContext::new_with_signer(
                     ctx.accounts.destination_token_program.to_account_info(),
                     anchor_spl::token_interface::TransferChecked {
-                        from: ctx.accounts.user_token_destination.to_account_info(),
+                        from: user_token_destination.to_account_info(),
                         to: pool_fee_account.to_account_info(),
                         authority: ctx.accounts.authority.to_account_info(),
                         mint: ctx.accounts.destination_token_mint.to_account_info(),
                     },
                     &[&[
-                        &ctx.accounts.swap_v1.key().to_bytes(),
-                        &[ctx.bumps.authority],
+                        &ctx.accounts.token_swap.key().to_bytes(),
+                        &[ctx.accounts.token_swap.bump_seed],
                     ]],
                 ),
                 to_u64(withdraw_fee)?,
@@ -105,37 +171,88 @@ pub fn withdraw_single_token_type_exact_amount_out_handler(
         TradeDirection::AtoB => ctx.accounts.swap_token_a.to_account_info(),
         TradeDirection::BtoA => ctx.accounts.swap_token_b.to_account_info(),
     };
-    anchor_spl::token_interface::transfer_checked(
+    user_token_destination.wrap(0)?;
+    let mut remaining_accounts = ctx.remaining_accounts;
+    let destination_hook_accounts = split_hook_accounts(
+        &ctx.accounts.destination_token_mint.to_account_info(),
+        &mut remaining_accounts,
+    )?;
+    transfer_checked_with_hook(
         CpiContext::new_with_signer(
             ctx.accounts.destination_token_program.to_account_info(),
             anchor_spl::token_interface::TransferChecked {
                 from: from_token_account,
-                to: ctx.accounts.user_token_destination.to_account_info(),
+                to: user_token_destination.to_account_info(),
                 authority: ctx.accounts.authority.to_account_info(),
                 mint: ctx.accounts.destination_token_mint.to_account_info(),
             },
             &[&[
-                &ctx.accounts.swap_v1.key().to_bytes(),
-                &[ctx.bumps.authority],
+                &ctx.accounts.token_swap.key().to_bytes(),
+                &[ctx.accounts.token_swap.bump_seed],
             ]],
-        ),
+        )
+        .with_remaining_accounts(destination_hook_accounts.to_vec()),
         destination_token_amount,
         ctx.accounts.destination_token_mint.decimals,
     )?;
+    user_token_destination.unwrap()?;
+
+    ctx.accounts.swap_token_a.reload()?;
+    ctx.accounts.swap_token_b.reload()?;
+    ctx.accounts.token_swap.update_reserves(
+        balances_before,
+        (
+            ctx.accounts.swap_token_a.amount,
+            ctx.accounts.swap_token_b.amount,
+        ),
+    )?;
+    #[cfg(feature = "invariant-check")]
+    {
+        ctx.accounts.pool_mint.reload()?;
+        pool_value.check(
+            &ctx.accounts.token_swap,
+            (
+                ctx.accounts.swap_token_a.amount,
+                ctx.accounts.swap_token_b.amount,
+            ),
+            ctx.accounts.pool_mint.supply,
+        )?;
+    }
+    let (token_a_amount, token_b_amount) = match trade_direction {
+        TradeDirection::AtoB => (destination_token_amount, 0),
+        TradeDirection::BtoA => (0, destination_token_amount),
+    };
+    emit_event!(
+        ctx,
+        Withdrawn {
+            token_swap: ctx.accounts.token_swap.key(),
+            user: ctx.accounts.user_transfer_authority.key(),
+            token_a_amount,
+            token_b_amount,
+            pool_tokens_burned: to_u64(pool_token_amount)?,
+            withdraw_fee: to_u64(withdraw_fee)?,
+            reserve_a: ctx.accounts.token_swap.reserve_a,
+            reserve_b: ctx.accounts.token_swap.reserve_b,
+        }
+    );
     Ok(())
 }
 
+#[cfg_attr(feature = "event-cpi", event_cpi)]
 #[derive(Accounts)]
-pub struct WithdrawSingleTokenTypeExactAmountOut<'info> {
+pub struct WithdrawSingleTokenType<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
     #[account(
-    constraint = !swap_v1.to_account_info().data_is_empty() @ SwapError::IncorrectSwapAccount,
+    mut,
+    constraint = !token_swap.to_account_info().data_is_empty() @ SwapError::IncorrectSwapAccount,
+    constraint = token_swap.status.allows_single_withdrawals() @ SwapError::OperationPaused,
+    constraint = token_swap.flash_loan.is_none() @ SwapError::FlashLoanInProgress,
   )]
-    pub swap_v1: Account<'info, SwapV1>,
+    pub token_swap: Account<'info, SwapV2>,
     #[account(
-      seeds = [swap_v1.key().as_ref()],
-      bump,
+      seeds = [token_swap.key().as_ref()],
+      bump = token_swap.bump_seed,
   )]
     pub authority: AccountInfo<'info>,
     pub user_transfer_authority: Signer<'info>,
@@ -147,13 +264,13 @@ pub struct WithdrawSingleTokenTypeExactAmountOut<'info> {
     #[account(
         mut,
         token::mint = swap_token_a.mint,
-        constraint = swap_v1.token_a == swap_token_a.key() @ SwapError::IncorrectSwapAccount
+        constraint = token_swap.token_a == swap_token_a.key() @ SwapError::IncorrectSwapAccount
     )]
     pub swap_token_a: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut,
         token::mint = swap_token_b.mint,
-        constraint = swap_v1.token_b == swap_token_b.key() @ SwapError::IncorrectSwapAccount
+        constraint = token_swap.token_b == swap_token_b.key() @ SwapError::IncorrectSwapAccount
     )]
     pub swap_token_b: InterfaceAccount<'info, TokenAccount>,
 
@@ -162,24 +279,55 @@ pub struct WithdrawSingleTokenTypeExactAmountOut<'info> {
     #[account(
         mut,
         mint::token_program = token_pool_program.key(),
+        constraint = pool_mint.key() == token_swap.pool_mint @ SwapError::IncorrectPoolMint,
     )]
     pub pool_mint: InterfaceAccount<'info, Mint>,
     #[account(
         mut,
-        token::mint = pool_mint.key()
+        token::mint = pool_mint.key(),
+        constraint = pool_fee_account.key() == token_swap.pool_fee_account @ SwapError::InvalidFeeAccount,
     )]
     pub pool_fee_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Omitted to receive SOL in the user transfer authority, unwrapped
+    /// from its temporary wrapped SOL account
     #[account(
         mut,
         token::mint = destination_token_mint.key()
     )]
-    pub user_token_destination: InterfaceAccount<'info, TokenAccount>,
+    pub user_token_destination: Option<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
         mint::token_program = destination_token_program.key(),
     )]
     pub destination_token_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        constraint = token_swap.token_program(&destination_token_mint.key()) == Some(&destination_token_program.key()) @ SwapError::IncorrectTokenProgramId,
+    )]
     pub destination_token_program: Interface<'info, TokenInterface>,
+    #[account(
+        constraint = token_pool_program.key() == token_swap.pool_token_program @ SwapError::IncorrectTokenProgramId,
+    )]
     pub token_pool_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
+    /// Temporary wrapped SOL account of the user transfer authority, only
+    /// required when a wrapped SOL account of the user is omitted, in which
+    /// case the user transfer authority funds it and must be writable
+    #[account(
+        mut,
+        seeds = [NATIVE_SOL_SEED, user_transfer_authority.key().as_ref()],
+        bump,
+    )]
+    pub native_sol_account: Option<UncheckedAccount<'info>>,
+    /// Associated token account of the user transfer authority for the
+    /// destination mint, created if needed and used when
+    /// `user_token_destination` is omitted
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = destination_token_mint,
+        associated_token::authority = user_transfer_authority,
+        associated_token::token_program = destination_token_program,
+    )]
+    pub user_token_destination_ata: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    pub associated_token_program: Option<Program<'info, AssociatedToken>>,
 }
@@ -0,0 +1,267 @@
+use {
+    crate::{curves::TradeDirection, SwapError, SwapState, SwapV2},
+    anchor_lang::prelude::*,
+    spl_math::precise_number::PreciseNumber,
+};
+
+/// Value of a swap before an instruction, checked against its value after
+/// the instruction so that a pool token never loses value, as measured by
+/// the normalized value of the curve. This enforces on chain what the curve
+/// tests check off chain, catching any rounding or transfer fee error the
+/// handlers would let through.
+pub struct PoolValue {
+    reserve_a: u64,
+    reserve_b: u64,
+    balances: (u64, u64),
+    value: PreciseNumber,
+    pool_token_supply: u64,
+}
+
+impl PoolValue {
+    /// Value of the swap given the balances of its token A and B accounts
+    /// and the supply of its pool mint before the instruction
+    pub fn new(token_swap: &SwapV2, balances: (u64, u64), pool_token_supply: u64) -> Result<Self> {
+        let value = token_swap
+            .swap_curve()
+            .calculator
+            .normalized_value(
+                u128::from(token_swap.reserve_a),
+                u128::from(token_swap.reserve_b),
+            )
+            .ok_or(SwapError::CalculationFailure)?;
+        Ok(Self {
+            reserve_a: token_swap.reserve_a,
+            reserve_b: token_swap.reserve_b,
+            balances,
+            value,
+            pool_token_supply,
+        })
+    }
+
+    /// Fail if the value per pool token decreased, given the balances of the
+    /// token A and B accounts and the supply of the pool mint after the
+    /// instruction. The reserves are moved by the change of the balances
+    /// rather than read from the swap, so that the check covers what the
+    /// token accounts actually received and sent.
+    pub fn check(
+        &self,
+        token_swap: &SwapV2,
+        (balance_a, balance_b): (u64, u64),
+        pool_token_supply: u64,
+    ) -> Result<()> {
+        // nothing is owed to anyone by a swap without pool tokens
+        if self.pool_token_supply == 0 {
+            return Ok(());
+        }
+        let reserve_a = u128::from(self.reserve_a)
+            .checked_add(u128::from(balance_a))
+            .and_then(|amount| amount.checked_sub(u128::from(self.balances.0)))
+            .ok_or(SwapError::PoolValueDecreased)?;
+        let reserve_b = u128::from(self.reserve_b)
+            .checked_add(u128::from(balance_b))
+            .and_then(|amount| amount.checked_sub(u128::from(self.balances.1)))
+            .ok_or(SwapError::PoolValueDecreased)?;
+        let calculator = token_swap.swap_curve().calculator;
+        let value = calculator
+            .normalized_value(reserve_a, reserve_b)
+            .ok_or(SwapError::CalculationFailure)?;
+        // an approximated value may drop within its precision without any
+        // value leaving the pool
+        let previous_value = calculator
+            .normalized_value_precision()
+            .and_then(|precision| self.value.checked_mul(&precision))
+            .and_then(|error| self.value.checked_sub(&error))
+            .ok_or(SwapError::CalculationFailure)?;
+
+        // value / pool_token_supply >= self.value / self.pool_token_supply,
+        // cross multiplied to stay in integers
+        let new_value = value
+            .checked_mul(&precise(self.pool_token_supply)?)
+            .ok_or(SwapError::CalculationFailure)?;
+        let previous_value = previous_value
+            .checked_mul(&precise(pool_token_supply)?)
+            .ok_or(SwapError::CalculationFailure)?;
+        require!(
+            new_value.greater_than_or_equal(&previous_value),
+            SwapError::PoolValueDecreased
+        );
+        Ok(())
+    }
+}
+
+/// Balances of the token A and B accounts of a swap, given the balances of
+/// its source and destination accounts in a trade
+pub fn balances(trade_direction: TradeDirection, source: u64, destination: u64) -> (u64, u64) {
+    match trade_direction {
+        TradeDirection::AtoB => (source, destination),
+        TradeDirection::BtoA => (destination, source),
+    }
+}
+
+fn precise(amount: u64) -> Result<PreciseNumber> {
+    PreciseNumber::new(u128::from(amount)).ok_or_else(|| SwapError::CalculationFailure.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{curves::CurveType, Fees, LegacySwapV1},
+    };
+
+    /// Constant product swap with the given reserves
+    fn swap(reserve_a: u64, reserve_b: u64) -> SwapV2 {
+        swap_with_curve(CurveType::ConstantProduct, reserve_a, reserve_b)
+    }
+
+    /// Swap of the given curve with the given reserves
+    fn swap_with_curve(curve_type: CurveType, reserve_a: u64, reserve_b: u64) -> SwapV2 {
+        let swap_v1 = LegacySwapV1 {
+            token_a: Pubkey::new_unique(),
+            token_b: Pubkey::new_unique(),
+            pool_mint: Pubkey::new_unique(),
+            token_a_mint: Pubkey::new_unique(),
+            token_b_mint: Pubkey::new_unique(),
+            pool_fee_account: Pubkey::new_unique(),
+            fees: Fees {
+                trade_fee_numerator: 25,
+                trade_fee_denominator: 10_000,
+                owner_trade_fee_numerator: 5,
+                owner_trade_fee_denominator: 10_000,
+                owner_withdraw_fee_numerator: 0,
+                owner_withdraw_fee_denominator: 0,
+                host_fee_numerator: 20,
+                host_fee_denominator: 100,
+            },
+            curve_type,
+        };
+        let programs = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        SwapV2::from_v1(
+            swap_v1,
+            255,
+            Pubkey::new_unique(),
+            programs,
+            (reserve_a, reserve_b),
+            0,
+        )
+    }
+
+    #[test]
+    fn swap_keeping_the_value_passes() {
+        let token_swap = swap(1_000, 1_000);
+        let pool_value = PoolValue::new(
+            &token_swap,
+            balances(TradeDirection::BtoA, 1_000, 1_000),
+            100,
+        )
+        .unwrap();
+        // 100 of token B in for 90 of token A, leaving a larger product
+        assert!(pool_value
+            .check(&token_swap, balances(TradeDirection::BtoA, 1_100, 910), 100)
+            .is_ok());
+    }
+
+    #[test]
+    fn swap_decreasing_the_value_fails() {
+        let token_swap = swap(1_000, 1_000);
+        let pool_value = PoolValue::new(
+            &token_swap,
+            balances(TradeDirection::AtoB, 1_000, 1_000),
+            100,
+        )
+        .unwrap();
+        // 100 of token A in for 100 of token B, leaving a smaller product
+        assert_eq!(
+            pool_value
+                .check(&token_swap, balances(TradeDirection::AtoB, 1_100, 900), 100)
+                .unwrap_err(),
+            SwapError::PoolValueDecreased.into()
+        );
+    }
+
+    #[test]
+    fn withdrawal_above_its_share_fails() {
+        let token_swap = swap(1_000, 1_000);
+        let pool_value = PoolValue::new(&token_swap, (1_000, 1_000), 100).unwrap();
+        // burning 10% of the pool tokens is worth 10% of each reserve
+        assert!(pool_value.check(&token_swap, (900, 900), 90).is_ok());
+        assert_eq!(
+            pool_value.check(&token_swap, (890, 890), 90).unwrap_err(),
+            SwapError::PoolValueDecreased.into()
+        );
+    }
+
+    #[test]
+    fn excess_balance_is_not_counted() {
+        // the swap token A account holds 500 above its reserve
+        let token_swap = swap(1_000, 1_000);
+        let pool_value = PoolValue::new(&token_swap, (1_500, 1_000), 100).unwrap();
+        // sending out the excess along with token B leaves less than before
+        assert_eq!(
+            pool_value
+                .check(&token_swap, (1_000, 900), 100)
+                .unwrap_err(),
+            SwapError::PoolValueDecreased.into()
+        );
+    }
+
+    #[test]
+    fn weighted_withdrawal_of_its_share_passes() {
+        let token_swap = swap_with_curve(
+            CurveType::Weighted {
+                weight_a: 80,
+                weight_b: 20,
+            },
+            1_000,
+            5_000,
+        );
+        let pool_value = PoolValue::new(&token_swap, (1_000, 5_000), 100).unwrap();
+        // the approximated value of the remaining reserves is slightly below
+        // 90% of the previous one, within its precision
+        assert!(pool_value.check(&token_swap, (900, 4_500), 90).is_ok());
+        assert_eq!(
+            pool_value.check(&token_swap, (899, 4_500), 90).unwrap_err(),
+            SwapError::PoolValueDecreased.into()
+        );
+    }
+
+    #[test]
+    fn weighted_swap_decreasing_the_value_fails() {
+        let token_swap = swap_with_curve(
+            CurveType::Weighted {
+                weight_a: 80,
+                weight_b: 20,
+            },
+            1_000,
+            5_000,
+        );
+        let pool_value = PoolValue::new(
+            &token_swap,
+            balances(TradeDirection::AtoB, 1_000, 5_000),
+            100,
+        )
+        .unwrap();
+        // 10 of token A in is worth about 200 of token B at these weights
+        assert!(pool_value
+            .check(
+                &token_swap,
+                balances(TradeDirection::AtoB, 1_010, 4_810),
+                100
+            )
+            .is_ok());
+        assert_eq!(
+            pool_value
+                .check(
+                    &token_swap,
+                    balances(TradeDirection::AtoB, 1_010, 4_790),
+                    100
+                )
+                .unwrap_err(),
+            SwapError::PoolValueDecreased.into()
+        );
+    }
+}
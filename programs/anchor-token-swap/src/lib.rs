@@ -1,43 +1,110 @@
 pub mod curves;
+mod deadline;
 mod errors;
+mod events;
 pub mod helper;
 pub mod instructions;
+#[cfg(any(test, feature = "invariant-check"))]
+mod invariant;
 
 mod state;
 mod swap_constraints;
 use {crate::curves::CurveType, anchor_lang::prelude::*};
-pub use {errors::*, instructions::*, state::*, swap_constraints::*};
+pub use {deadline::*, errors::*, events::*, instructions::*, state::*, swap_constraints::*};
 declare_id!("HUYZTGf7FbXt6A28HhvhMiN5SnFGBKe5F9t8Lqw7KSeG");
 
 #[program]
 pub mod anchor_token_swap {
 
-    #[cfg(feature = "upgradable-test")]
-    use crate::program::AnchorTokenSwap;
     use {super::*, crate::curves::CurveType};
+    #[cfg(feature = "upgradable-test")]
+    use {
+        crate::program::AnchorTokenSwap,
+        anchor_spl::token_interface::{Mint, TokenAccount},
+    };
+
+    pub fn initialize_config(
+        ctx: Context<InitializeConfig>,
+        constraints: Option<SwapConstraints>,
+    ) -> Result<()> {
+        instructions::initialize_config_handler(ctx, constraints)
+    }
+
+    pub fn update_config(
+        ctx: Context<UpdateConfig>,
+        constraints: Option<SwapConstraints>,
+    ) -> Result<()> {
+        instructions::update_config_handler(ctx, constraints)
+    }
+
+    pub fn initialize_factory(ctx: Context<InitializeFactory>) -> Result<()> {
+        instructions::initialize_factory_handler(ctx)
+    }
 
     #[access_control(
         validate_swap_constraints(
             &curve_type,
             &fees,
             ctx.accounts.pool_fee_account.owner,
-            None
+            &ctx.accounts.config.constraints
         )
-        validate_mint_uncloseable(&ctx.accounts.pool_mint)
     )]
-    pub fn initialize(ctx: Context<Initialize>, curve_type: CurveType, fees: Fees) -> Result<()> {
-        instructions::initialize_handler(ctx, curve_type, fees)
+    pub fn initialize<'info>(
+        ctx: Context<'_, '_, '_, 'info, Initialize<'info>>,
+        curve_type: CurveType,
+        fees: Fees,
+        fee_tier: u16,
+        initial_token_a_amount: u64,
+        initial_token_b_amount: u64,
+    ) -> Result<()> {
+        instructions::initialize_handler(
+            ctx,
+            curve_type,
+            fees,
+            fee_tier,
+            initial_token_a_amount,
+            initial_token_b_amount,
+        )
     }
 
-    pub fn swap(ctx: Context<TokenSwap>, amount_in: u64, minimum_amount_out: u64) -> Result<()> {
+    #[access_control(validate_deadline(&deadline))]
+    pub fn swap<'info>(
+        ctx: Context<'_, '_, '_, 'info, TokenSwap<'info>>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        deadline: Option<Deadline>,
+    ) -> Result<()> {
         instructions::swap_handler(ctx, amount_in, minimum_amount_out)
     }
 
-    pub fn deposit_all_token_types(
-        ctx: Context<DepositAllTokenTypes>,
+    #[access_control(validate_deadline(&deadline))]
+    pub fn swap_exact_amount_out<'info>(
+        ctx: Context<'_, '_, '_, 'info, TokenSwap<'info>>,
+        amount_out: u64,
+        maximum_amount_in: u64,
+        deadline: Option<Deadline>,
+    ) -> Result<()> {
+        instructions::swap_exact_amount_out_handler(ctx, amount_out, maximum_amount_in)
+    }
+
+    #[access_control(validate_deadline(&deadline))]
+    pub fn route_swap<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RouteSwap<'info>>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        hops: u8,
+        deadline: Option<Deadline>,
+    ) -> Result<()> {
+        instructions::route_swap_handler(ctx, amount_in, minimum_amount_out, hops)
+    }
+
+    #[access_control(validate_deadline(&deadline))]
+    pub fn deposit_all_token_types<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAllTokenTypes<'info>>,
         pool_token_amount: u64,
         token_a_slippage_limit: u64,
         token_b_slippage_limit: u64,
+        deadline: Option<Deadline>,
     ) -> Result<()> {
         instructions::deposit_all_token_types_handler(
             ctx,
@@ -46,11 +113,13 @@ pub mod anchor_token_swap {
             token_b_slippage_limit,
         )
     }
-    pub fn withdraw_all_token_types(
-        ctx: Context<WithdrawAllTokenTypes>,
+    #[access_control(validate_deadline(&deadline))]
+    pub fn withdraw_all_token_types<'info>(
+        ctx: Context<'_, '_, '_, 'info, WithdrawAllTokenTypes<'info>>,
         pool_token_amount: u64,
         slippage_a_amount: u64,
         slippage_b_amount: u64,
+        deadline: Option<Deadline>,
     ) -> Result<()> {
         instructions::withdraw_all_token_types_handler(
             ctx,
@@ -59,10 +128,12 @@ pub mod anchor_token_swap {
             slippage_b_amount,
         )
     }
-    pub fn deposit_single_token_type_exact_amount_in(
-        ctx: Context<DepositSingleTokenType>,
+    #[access_control(validate_deadline(&deadline))]
+    pub fn deposit_single_token_type_exact_amount_in<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositSingleTokenType<'info>>,
         source_token_amount: u64,
         slippage_amount: u64,
+        deadline: Option<Deadline>,
     ) -> Result<()> {
         instructions::deposit_single_token_type_exact_amount_in_handler(
             ctx,
@@ -70,10 +141,25 @@ pub mod anchor_token_swap {
             slippage_amount,
         )
     }
-    pub fn withdraw_single_token_type_exact_amount_out(
-        ctx: Context<WithdrawSingleTokenTypeExactAmountOut>,
+    #[access_control(validate_deadline(&deadline))]
+    pub fn deposit_single_token_type_exact_amount_out<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositSingleTokenType<'info>>,
+        pool_token_amount: u64,
+        maximum_source_amount: u64,
+        deadline: Option<Deadline>,
+    ) -> Result<()> {
+        instructions::deposit_single_token_type_exact_amount_out_handler(
+            ctx,
+            pool_token_amount,
+            maximum_source_amount,
+        )
+    }
+    #[access_control(validate_deadline(&deadline))]
+    pub fn withdraw_single_token_type_exact_amount_out<'info>(
+        ctx: Context<'_, '_, '_, 'info, WithdrawSingleTokenType<'info>>,
         destination_token_amount: u64,
         maximum_pool_token_amount: u64,
+        deadline: Option<Deadline>,
     ) -> Result<()> {
         instructions::withdraw_single_token_type_exact_amount_out_handler(
             ctx,
@@ -81,10 +167,115 @@ pub mod anchor_token_swap {
             maximum_pool_token_amount,
         )
     }
+    #[access_control(validate_deadline(&deadline))]
+    pub fn withdraw_single_token_type_exact_amount_in<'info>(
+        ctx: Context<'_, '_, '_, 'info, WithdrawSingleTokenType<'info>>,
+        pool_token_amount: u64,
+        minimum_token_out: u64,
+        deadline: Option<Deadline>,
+    ) -> Result<()> {
+        instructions::withdraw_single_token_type_exact_amount_in_handler(
+            ctx,
+            pool_token_amount,
+            minimum_token_out,
+        )
+    }
+    pub fn flash_borrow<'info>(
+        ctx: Context<'_, '_, '_, 'info, FlashBorrow<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        instructions::flash_borrow_handler(ctx, amount)
+    }
+
+    pub fn flash_repay<'info>(ctx: Context<'_, '_, '_, 'info, FlashRepay<'info>>) -> Result<()> {
+        instructions::flash_repay_handler(ctx)
+    }
+
+    pub fn initialize_observations(ctx: Context<InitializeObservations>) -> Result<()> {
+        instructions::initialize_observations_handler(ctx)
+    }
+
+    pub fn record_observation(ctx: Context<RecordObservation>) -> Result<()> {
+        instructions::record_observation_handler(ctx)
+    }
+
+    pub fn sync(ctx: Context<SyncReserves>) -> Result<()> {
+        instructions::sync_handler(ctx)
+    }
+
+    pub fn skim<'info>(ctx: Context<'_, '_, '_, 'info, Skim<'info>>) -> Result<()> {
+        instructions::skim_handler(ctx)
+    }
+
+    pub fn list_pools<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ListPools<'info>>,
+        start_index: u64,
+    ) -> Result<PoolPage> {
+        instructions::list_pools_handler(ctx, start_index)
+    }
+
+    pub fn quote_swap(ctx: Context<QuoteSwap>, amount_in: u64) -> Result<SwapQuote> {
+        instructions::quote_swap_handler(ctx, amount_in)
+    }
+    pub fn quote_deposit_single(
+        ctx: Context<QuoteDepositSingle>,
+        source_token_amount: u64,
+    ) -> Result<DepositSingleQuote> {
+        instructions::quote_deposit_single_handler(ctx, source_token_amount)
+    }
+    pub fn quote_withdraw_single(
+        ctx: Context<QuoteWithdrawSingle>,
+        destination_token_amount: u64,
+    ) -> Result<WithdrawSingleQuote> {
+        instructions::quote_withdraw_single_handler(ctx, destination_token_amount)
+    }
+    #[access_control(
+        validate_swap_constraints(
+            &ctx.accounts.token_swap.curve_type,
+            &new_fees,
+            ctx.accounts.pool_fee_account.owner,
+            &ctx.accounts.config.constraints
+        )
+    )]
+    pub fn update_fees(ctx: Context<UpdateFees>, new_fees: Fees) -> Result<()> {
+        instructions::update_fees_handler(ctx, new_fees)
+    }
+    pub fn set_admin(ctx: Context<SetAdmin>, new_admin: Pubkey) -> Result<()> {
+        instructions::set_admin_handler(ctx, new_admin)
+    }
+    pub fn set_pool_status(ctx: Context<SetPoolStatus>, status: PoolStatus) -> Result<()> {
+        instructions::set_pool_status_handler(ctx, status)
+    }
+    pub fn close_pool<'info>(ctx: Context<'_, '_, '_, 'info, ClosePool<'info>>) -> Result<()> {
+        instructions::close_pool_handler(ctx)
+    }
+    pub fn migrate_v1_to_v2(ctx: Context<MigrateV1ToV2>) -> Result<()> {
+        instructions::migrate_v1_to_v2_handler(ctx)
+    }
     #[cfg(feature = "upgradable-test")]
     pub fn upgrade_verifier(_ctx: Context<UpgradableVerifier>) -> Result<()> {
         Ok(())
     }
+    /// Write a swap in the first version layout, so that the migration can be
+    /// tested against a real `SwapV1` account
+    #[cfg(feature = "upgradable-test")]
+    pub fn create_swap_v1(
+        ctx: Context<CreateSwapV1>,
+        fees: Fees,
+        curve_type: CurveType,
+    ) -> Result<()> {
+        ctx.accounts.token_swap.set_inner(LegacySwapV1 {
+            token_a: ctx.accounts.swap_token_a.key(),
+            token_b: ctx.accounts.swap_token_b.key(),
+            pool_mint: ctx.accounts.pool_mint.key(),
+            token_a_mint: ctx.accounts.swap_token_a.mint,
+            token_b_mint: ctx.accounts.swap_token_b.mint,
+            pool_fee_account: ctx.accounts.pool_fee_account.key(),
+            fees,
+            curve_type,
+        });
+        Ok(())
+    }
     #[cfg(feature = "upgradable-test")]
     #[derive(Accounts)]
     pub struct UpgradableVerifier<'info> {
@@ -100,4 +291,21 @@ pub mod anchor_token_swap {
         pub program_data: Account<'info, ProgramData>,
         pub system_program: Program<'info, System>,
     }
+    #[cfg(feature = "upgradable-test")]
+    #[derive(Accounts)]
+    pub struct CreateSwapV1<'info> {
+        #[account(mut)]
+        pub payer: Signer<'info>,
+        #[account(
+            init,
+            payer = payer,
+            space = LegacySwapV1::INIT_SPACE + 8,
+        )]
+        pub token_swap: Account<'info, LegacySwapV1>,
+        pub swap_token_a: InterfaceAccount<'info, TokenAccount>,
+        pub swap_token_b: InterfaceAccount<'info, TokenAccount>,
+        pub pool_mint: InterfaceAccount<'info, Mint>,
+        pub pool_fee_account: InterfaceAccount<'info, TokenAccount>,
+        pub system_program: Program<'info, System>,
+    }
 }
@@ -95,6 +95,20 @@ impl Fees {
         validate_fraction(self.host_fee_numerator, self.host_fee_denominator)?;
         Ok(())
     }
+    /// Trade fee in basis points, used as the fee tier of the swap, only
+    /// defined if the trade fee is a whole number of basis points
+    #[inline]
+    pub fn trade_fee_bps(&self) -> Option<u16> {
+        if self.trade_fee_numerator == 0 || self.trade_fee_denominator == 0 {
+            return Some(0);
+        }
+        let numerator = u128::from(self.trade_fee_numerator).checked_mul(10_000)?;
+        let denominator = u128::from(self.trade_fee_denominator);
+        if numerator % denominator != 0 {
+            return None;
+        }
+        u16::try_from(numerator / denominator).ok()
+    }
     /// Calculate the host fee based on the owner fee, only used in production
     /// situations where a program is hosted by multiple frontends
     #[inline]
@@ -0,0 +1,24 @@
+use anchor_lang::prelude::*;
+
+/// Flash loan lent out by `flash_borrow` and not yet repaid by
+/// `flash_repay`, only ever set within a single transaction
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct FlashLoan {
+    /// Swap token account the loan was taken from
+    pub token_account: Pubkey,
+    /// Amount lent out
+    pub amount: u64,
+    /// Fee owed on top of the amount, credited to liquidity providers
+    pub fee: u64,
+    /// Balance of the swap token account before the loan
+    pub reserve_before: u64,
+}
+
+impl FlashLoan {
+    /// Balance the swap token account must hold once the loan is repaid
+    #[inline]
+    pub fn reserve_after_repay(&self) -> Option<u64> {
+        self.reserve_before.checked_add(self.fee)
+    }
+}
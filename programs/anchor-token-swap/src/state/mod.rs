@@ -1,3 +1,11 @@
 mod fees;
+mod flash_loan;
+mod pool_status;
+mod price_oracle;
+mod registry;
+mod swap_config;
 mod swap_state;
-pub use {fees::*, swap_state::*};
+pub use {
+    fees::*, flash_loan::*, pool_status::*, price_oracle::*, registry::*, swap_config::*,
+    swap_state::*,
+};
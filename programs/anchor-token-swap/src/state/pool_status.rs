@@ -0,0 +1,55 @@
+use anchor_lang::prelude::*;
+
+/// Operations allowed on a swap, set by its admin to react to a misbehaving
+/// token without migrating the liquidity.
+///
+/// Single-sided deposits and withdrawals implicitly perform a swap, so they
+/// are only allowed when both swaps and the deposit or withdrawal are.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, InitSpace)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub enum PoolStatus {
+    /// All operations are allowed
+    #[default]
+    Active,
+    /// Swaps are paused, liquidity can still be added and removed on both
+    /// sides
+    SwapsPaused,
+    /// Deposits are paused, swaps and withdrawals are still allowed
+    DepositsPaused,
+    /// Only withdrawals of both token types are allowed, so liquidity
+    /// providers can exit the pool at the current ratio
+    WithdrawOnly,
+}
+
+impl PoolStatus {
+    /// Whether swaps are allowed
+    #[inline]
+    pub fn allows_swaps(&self) -> bool {
+        matches!(self, PoolStatus::Active | PoolStatus::DepositsPaused)
+    }
+
+    /// Whether deposits of both token types are allowed
+    #[inline]
+    pub fn allows_deposits(&self) -> bool {
+        matches!(self, PoolStatus::Active | PoolStatus::SwapsPaused)
+    }
+
+    /// Whether single-sided deposits are allowed
+    #[inline]
+    pub fn allows_single_deposits(&self) -> bool {
+        self.allows_swaps() && self.allows_deposits()
+    }
+
+    /// Whether withdrawals of both token types are allowed, which is always
+    /// the case to never lock liquidity in a pool
+    #[inline]
+    pub fn allows_withdrawals(&self) -> bool {
+        true
+    }
+
+    /// Whether single-sided withdrawals are allowed
+    #[inline]
+    pub fn allows_single_withdrawals(&self) -> bool {
+        self.allows_swaps() && self.allows_withdrawals()
+    }
+}
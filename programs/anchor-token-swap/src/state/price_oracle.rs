@@ -0,0 +1,232 @@
+use anchor_lang::prelude::*;
+
+/// Number of fractional bits of the fixed point prices
+pub const PRICE_FRACTIONAL_BITS: u32 = 64;
+
+/// Number of observations kept by the `Observations` ring buffer
+pub const OBSERVATIONS_LEN: usize = 64;
+
+/// Price of one token in the other as an unsigned Q64.64 fixed point number,
+/// `numerator` being the reserve of the token the price is expressed in
+#[inline]
+pub fn fixed_point_price(numerator: u64, denominator: u64) -> Option<u128> {
+    (u128::from(numerator) << PRICE_FRACTIONAL_BITS).checked_div(u128::from(denominator))
+}
+
+/// Uniswap-v2 style cumulative prices of a swap, from the ratio of its
+/// reserves.
+///
+/// Both sums are expected to overflow and wrap around, consumers compute the
+/// time weighted average price between two points in time from the wrapping
+/// difference of the sums divided by the elapsed time.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, InitSpace)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct CumulativePrices {
+    /// Sum of the price of token A in token B, as a Q64.64 fixed point
+    /// number, times the number of seconds it was the price
+    pub price_a_cumulative: u128,
+    /// Sum of the price of token B in token A, as a Q64.64 fixed point
+    /// number, times the number of seconds it was the price
+    pub price_b_cumulative: u128,
+    /// Unix timestamp of the last update of the sums
+    pub last_update_timestamp: i64,
+}
+
+impl CumulativePrices {
+    /// Accumulate the prices given by the reserves since the last update,
+    /// must be called with the reserves before they change
+    pub fn update(&mut self, reserve_a: u64, reserve_b: u64, timestamp: i64) {
+        let elapsed = timestamp.saturating_sub(self.last_update_timestamp);
+        if elapsed <= 0 {
+            return;
+        }
+        if let (Some(price_a), Some(price_b)) = (
+            fixed_point_price(reserve_b, reserve_a),
+            fixed_point_price(reserve_a, reserve_b),
+        ) {
+            let elapsed = elapsed as u128;
+            self.price_a_cumulative = self
+                .price_a_cumulative
+                .wrapping_add(price_a.wrapping_mul(elapsed));
+            self.price_b_cumulative = self
+                .price_b_cumulative
+                .wrapping_add(price_b.wrapping_mul(elapsed));
+        }
+        self.last_update_timestamp = timestamp;
+    }
+}
+
+/// Cumulative prices of a swap recorded at a point in time
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, InitSpace)]
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct Observation {
+    /// Unix timestamp of the observation
+    pub timestamp: i64,
+    /// Cumulative price of token A in token B
+    pub price_a_cumulative: u128,
+    /// Cumulative price of token B in token A
+    pub price_b_cumulative: u128,
+}
+
+impl From<CumulativePrices> for Observation {
+    fn from(prices: CumulativePrices) -> Self {
+        Self {
+            timestamp: prices.last_update_timestamp,
+            price_a_cumulative: prices.price_a_cumulative,
+            price_b_cumulative: prices.price_b_cumulative,
+        }
+    }
+}
+
+impl Observation {
+    /// Time weighted average prices of token A in token B and of token B in
+    /// token A between `self` and the later observation `newer`, as Q64.64
+    /// fixed point numbers
+    pub fn average_prices(&self, newer: &Observation) -> Option<(u128, u128)> {
+        let elapsed = newer.timestamp.checked_sub(self.timestamp)?;
+        if elapsed <= 0 {
+            return None;
+        }
+        let elapsed = elapsed as u128;
+        Some((
+            newer
+                .price_a_cumulative
+                .wrapping_sub(self.price_a_cumulative)
+                / elapsed,
+            newer
+                .price_b_cumulative
+                .wrapping_sub(self.price_b_cumulative)
+                / elapsed,
+        ))
+    }
+}
+
+/// Ring buffer of the cumulative prices of a swap, written by
+/// `record_observation` so that time weighted average prices can be read
+/// over past windows. Nothing else records observations, the buffer only
+/// covers the windows over which it was cranked.
+#[account]
+#[derive(InitSpace)]
+pub struct Observations {
+    /// Swap the observations belong to
+    pub token_swap: Pubkey,
+    /// Index of the most recent observation
+    pub index: u16,
+    /// Recorded observations, the oldest one is the one following `index`
+    /// once the buffer has wrapped around
+    pub observations: [Observation; OBSERVATIONS_LEN],
+}
+
+impl Observations {
+    /// Most recent observation
+    #[inline]
+    pub fn latest(&self) -> &Observation {
+        &self.observations[usize::from(self.index)]
+    }
+
+    /// Record the cumulative prices, at most once per second, returns whether
+    /// they were recorded
+    pub fn record(&mut self, prices: CumulativePrices) -> bool {
+        if prices.last_update_timestamp <= self.latest().timestamp {
+            return false;
+        }
+        self.index = ((usize::from(self.index) + 1) % OBSERVATIONS_LEN) as u16;
+        self.observations[usize::from(self.index)] = prices.into();
+        true
+    }
+
+    /// Most recent observation at or before `timestamp`
+    pub fn observation_before(&self, timestamp: i64) -> Option<&Observation> {
+        (0..OBSERVATIONS_LEN)
+            .map(|offset| {
+                &self.observations
+                    [(usize::from(self.index) + OBSERVATIONS_LEN - offset) % OBSERVATIONS_LEN]
+            })
+            .take_while(|observation| observation.timestamp > 0)
+            .find(|observation| observation.timestamp <= timestamp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ONE: u128 = 1 << PRICE_FRACTIONAL_BITS;
+
+    #[test]
+    fn cumulative_prices_accumulate_over_time() {
+        let mut prices = CumulativePrices {
+            last_update_timestamp: 100,
+            ..Default::default()
+        };
+        prices.update(1_000, 2_000, 110);
+        assert_eq!(prices.price_a_cumulative, 2 * ONE * 10);
+        assert_eq!(prices.price_b_cumulative, ONE / 2 * 10);
+        assert_eq!(prices.last_update_timestamp, 110);
+
+        // same timestamp, nothing accumulated
+        prices.update(1, 1_000_000, 110);
+        assert_eq!(prices.price_a_cumulative, 2 * ONE * 10);
+
+        let start = Observation::from(prices);
+        prices.update(4_000, 1_000, 130);
+        let (price_a, price_b) = start.average_prices(&prices.into()).unwrap();
+        assert_eq!(price_a, ONE / 4);
+        assert_eq!(price_b, 4 * ONE);
+    }
+
+    #[test]
+    fn cumulative_prices_skip_empty_reserves() {
+        let mut prices = CumulativePrices::default();
+        prices.update(0, 1_000, 10);
+        assert_eq!(prices.price_a_cumulative, 0);
+        assert_eq!(prices.price_b_cumulative, 0);
+        assert_eq!(prices.last_update_timestamp, 10);
+    }
+
+    #[test]
+    fn average_prices_across_wrap_around() {
+        let older = Observation {
+            timestamp: 1,
+            price_a_cumulative: u128::MAX - ONE + 1,
+            price_b_cumulative: 0,
+        };
+        let mut prices = CumulativePrices {
+            price_a_cumulative: older.price_a_cumulative,
+            price_b_cumulative: older.price_b_cumulative,
+            last_update_timestamp: older.timestamp,
+        };
+        prices.update(u64::MAX, u64::MAX, 3);
+        let (price_a, price_b) = older.average_prices(&prices.into()).unwrap();
+        assert_eq!(price_a, ONE);
+        assert_eq!(price_b, ONE);
+        assert_eq!(older.average_prices(&older), None);
+    }
+
+    #[test]
+    fn observations_ring_buffer() {
+        let mut observations = Observations {
+            token_swap: Pubkey::default(),
+            index: 0,
+            observations: [Observation::default(); OBSERVATIONS_LEN],
+        };
+        let mut prices = CumulativePrices::default();
+        for timestamp in 1..=(OBSERVATIONS_LEN as i64 + 10) {
+            prices.update(1, 1, timestamp);
+            assert!(observations.record(prices));
+            assert!(!observations.record(prices));
+        }
+        let latest = OBSERVATIONS_LEN as i64 + 10;
+        assert_eq!(observations.latest().timestamp, latest);
+        assert_eq!(
+            observations
+                .observation_before(latest - 5)
+                .unwrap()
+                .timestamp,
+            latest - 5
+        );
+        // overwritten observations are gone
+        assert!(observations.observation_before(10).is_none());
+        assert_eq!(observations.observation_before(11).unwrap().timestamp, 11);
+    }
+}
@@ -0,0 +1,31 @@
+use {crate::curves::CurveType, anchor_lang::prelude::*};
+
+/// Global account of the program, counting the swaps created so far
+#[account]
+#[derive(InitSpace)]
+pub struct Factory {
+    /// Number of swaps created, also the index of the next registry entry
+    pub pool_count: u64,
+}
+
+/// Registry entry of a swap, written by `initialize` at the index given by
+/// the pool count of the factory, so that swaps can be listed without
+/// scanning the program accounts
+#[account]
+#[derive(InitSpace)]
+pub struct PoolEntry {
+    /// Index of the entry in the registry
+    pub index: u64,
+    /// Swap account
+    pub token_swap: Pubkey,
+    /// Mint of token A
+    pub token_a_mint: Pubkey,
+    /// Mint of token B
+    pub token_b_mint: Pubkey,
+    /// Curve of the swap
+    pub curve_type: CurveType,
+    /// Trade fee in basis points when the swap was created
+    pub fee_tier: u16,
+    /// Slot the swap was created at
+    pub created_slot: u64,
+}
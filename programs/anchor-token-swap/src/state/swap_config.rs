@@ -0,0 +1,10 @@
+use {crate::SwapConstraints, anchor_lang::prelude::*};
+
+/// Program-level configuration, editable by the upgrade authority
+#[account]
+#[derive(InitSpace)]
+pub struct SwapConfig {
+    /// Constraints on the swaps created with the program, none to allow any
+    /// curve and fees
+    pub constraints: Option<SwapConstraints>,
+}
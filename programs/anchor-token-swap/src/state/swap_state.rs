@@ -1,6 +1,9 @@
 use {
-    super::Fees,
-    crate::curves::{CurveType, SwapCurve},
+    super::{CumulativePrices, Fees, FlashLoan, PoolStatus},
+    crate::{
+        curves::{CurveType, SwapCurve, TradeDirection},
+        SwapError,
+    },
     anchor_lang::prelude::*,
 };
 
@@ -26,17 +29,58 @@ pub trait SwapState {
     fn swap_curve(&self) -> SwapCurve;
 }
 
+/// Discriminator of the accounts written as `SwapV1`, the first 8 bytes of
+/// the sha256 of `account:SwapV1`
+pub const SWAP_V1_DISCRIMINATOR: [u8; 8] = [244, 254, 184, 97, 8, 187, 243, 179];
+
 /// All versions of SwapState
 // #[enum_dispatch(SwapState)]
 pub enum SwapVersion {
-    /// Latest version, used for all new swaps
+    /// First version, read as `LegacySwapV1`, must be migrated with `migrate_v1_to_v2` before the
+    /// swap can be used again
     SwapV1,
+    /// Latest version, used for all new swaps
+    SwapV2,
+}
+
+/// Layout of the swaps created before `SwapV2`, under the name and
+/// discriminator of `SwapV1`. It must never change so that those accounts can
+/// still be read by `migrate_v1_to_v2`.
+#[derive(InitSpace)]
+#[non_exhaustive]
+#[account(discriminator = &SWAP_V1_DISCRIMINATOR)]
+pub struct LegacySwapV1 {
+    /// Token A
+    pub token_a: Pubkey,
+    /// Token B
+    pub token_b: Pubkey,
+
+    /// Pool tokens are issued when A or B tokens are deposited.
+    /// Pool tokens can be withdrawn back to the original A or B token.
+    pub pool_mint: Pubkey,
+
+    /// Mint information for token A
+    pub token_a_mint: Pubkey,
+    /// Mint information for token B
+    pub token_b_mint: Pubkey,
+
+    /// Pool token account to receive trading and / or withdrawal fees
+    pub pool_fee_account: Pubkey,
+    // All fee information
+    pub fees: Fees,
+    // curve_type to construct CurveCalculator, which can be used by the SwapCurve, that
+    // calculates swaps, deposits, and withdrawals
+    pub curve_type: CurveType,
 }
 
 #[derive(InitSpace)]
 #[non_exhaustive]
 #[account]
-pub struct SwapV1 {
+pub struct SwapV2 {
+    /// Bump seed of the swap authority, stored so that it is not re-derived
+    /// by every instruction
+    pub bump_seed: u8,
+
     /// Token A
     pub token_a: Pubkey,
     /// Token B
@@ -51,6 +95,13 @@ pub struct SwapV1 {
     /// Mint information for token B
     pub token_b_mint: Pubkey,
 
+    /// Program owning the mint and swap account of token A
+    pub token_a_program: Pubkey,
+    /// Program owning the mint and swap account of token B
+    pub token_b_program: Pubkey,
+    /// Program owning the pool token mint
+    pub pool_token_program: Pubkey,
+
     /// Pool token account to receive trading and / or withdrawal fees
     pub pool_fee_account: Pubkey,
     // All fee information
@@ -58,9 +109,135 @@ pub struct SwapV1 {
     // curve_type to construct CurveCalculator, which can be used by the SwapCurve, that
     // calculates swaps, deposits, and withdrawals
     pub curve_type: CurveType,
+    /// Trade fee in basis points when the swap was created, part of its
+    /// address, and kept when the trade fee is updated. A migrated swap takes
+    /// the basis points of its trade fee, or `u16::MAX` when it is not a whole
+    /// number of basis points.
+    pub fee_tier: u16,
+
+    /// Admin allowed to update the fees of the swap, set to the payer of the
+    /// initialization, or to the owner of the pool fee account for a
+    /// migrated swap
+    pub admin: Pubkey,
+    /// Operations currently allowed on the swap
+    pub status: PoolStatus,
+    /// Flash loan currently lent out, every other operation on the swap is
+    /// rejected until it is repaid
+    pub flash_loan: Option<FlashLoan>,
+    /// Cumulative prices of the swap, updated before every change of its
+    /// reserves
+    pub cumulative_prices: CumulativePrices,
+
+    /// Amount of token A owned by the liquidity providers and used by the
+    /// curve, any excess held by the swap token A account is absorbed by
+    /// `sync` or returned by `skim`
+    pub reserve_a: u64,
+    /// Amount of token B owned by the liquidity providers and used by the
+    /// curve, any excess held by the swap token B account is absorbed by
+    /// `sync` or returned by `skim`
+    pub reserve_b: u64,
+    /// Index of the registry entry of the swap, closed along with it, none
+    /// for a migrated swap
+    pub pool_entry_index: Option<u64>,
+
+    /// Reserved for future fields, so that they can be added without a
+    /// migration. New fields must be taken out of it so that the size of the
+    /// account never changes.
+    pub reserved: [u8; 103],
 }
 
-impl SwapState for SwapV1 {
+impl SwapV2 {
+    /// Upgrade a first version swap, given the bump seed of its authority,
+    /// its admin, the programs owning its token A and B accounts and pool
+    /// mint, the balances of its token A and B accounts taken as reserves,
+    /// and the current unix timestamp from which prices accumulate.
+    ///
+    /// The swap starts active, without any flash loan nor registry entry.
+    pub fn from_v1(
+        swap_v1: LegacySwapV1,
+        bump_seed: u8,
+        admin: Pubkey,
+        (token_a_program, token_b_program, pool_token_program): (Pubkey, Pubkey, Pubkey),
+        (reserve_a, reserve_b): (u64, u64),
+        timestamp: i64,
+    ) -> Self {
+        Self {
+            bump_seed,
+            token_a: swap_v1.token_a,
+            token_b: swap_v1.token_b,
+            pool_mint: swap_v1.pool_mint,
+            token_a_mint: swap_v1.token_a_mint,
+            token_b_mint: swap_v1.token_b_mint,
+            token_a_program,
+            token_b_program,
+            pool_token_program,
+            pool_fee_account: swap_v1.pool_fee_account,
+            fee_tier: swap_v1.fees.trade_fee_bps().unwrap_or(u16::MAX),
+            fees: swap_v1.fees,
+            curve_type: swap_v1.curve_type,
+            admin,
+            status: PoolStatus::Active,
+            flash_loan: None,
+            cumulative_prices: CumulativePrices {
+                last_update_timestamp: timestamp,
+                ..Default::default()
+            },
+            reserve_a,
+            reserve_b,
+            pool_entry_index: None,
+            reserved: [0; 103],
+        }
+    }
+
+    /// Accumulate the prices given by the reserves up to the current time,
+    /// must be called before the reserves change
+    pub fn update_cumulative_prices(&mut self) -> Result<()> {
+        self.cumulative_prices
+            .update(self.reserve_a, self.reserve_b, Clock::get()?.unix_timestamp);
+        Ok(())
+    }
+
+    /// Reserves of the source and destination tokens of a trade
+    pub fn reserves(&self, trade_direction: TradeDirection) -> (u64, u64) {
+        match trade_direction {
+            TradeDirection::AtoB => (self.reserve_a, self.reserve_b),
+            TradeDirection::BtoA => (self.reserve_b, self.reserve_a),
+        }
+    }
+
+    /// Move the reserves by the change of the swap token account balances
+    /// made by an instruction, so that any excess held before it is left out
+    pub fn update_reserves(
+        &mut self,
+        (balance_a_before, balance_b_before): (u64, u64),
+        (balance_a_after, balance_b_after): (u64, u64),
+    ) -> Result<()> {
+        self.reserve_a = self
+            .reserve_a
+            .checked_add(balance_a_after)
+            .and_then(|amount| amount.checked_sub(balance_a_before))
+            .ok_or(SwapError::CalculationFailure)?;
+        self.reserve_b = self
+            .reserve_b
+            .checked_add(balance_b_after)
+            .and_then(|amount| amount.checked_sub(balance_b_before))
+            .ok_or(SwapError::CalculationFailure)?;
+        Ok(())
+    }
+
+    /// Program owning the given mint, if it is token A or B of the swap
+    pub fn token_program(&self, mint: &Pubkey) -> Option<&Pubkey> {
+        if *mint == self.token_a_mint {
+            Some(&self.token_a_program)
+        } else if *mint == self.token_b_mint {
+            Some(&self.token_b_program)
+        } else {
+            None
+        }
+    }
+}
+
+impl SwapState for LegacySwapV1 {
     #[inline]
     fn token_a_account(&self) -> &Pubkey {
         &self.token_a
@@ -101,3 +278,164 @@ impl SwapState for SwapV1 {
         SwapCurve::new(self.curve_type)
     }
 }
+
+impl SwapState for SwapV2 {
+    #[inline]
+    fn token_a_account(&self) -> &Pubkey {
+        &self.token_a
+    }
+
+    #[inline]
+    fn token_b_account(&self) -> &Pubkey {
+        &self.token_b
+    }
+
+    #[inline]
+    fn pool_mint(&self) -> &Pubkey {
+        &self.pool_mint
+    }
+
+    #[inline]
+    fn token_a_mint(&self) -> &Pubkey {
+        &self.token_a_mint
+    }
+
+    #[inline]
+    fn token_b_mint(&self) -> &Pubkey {
+        &self.token_b_mint
+    }
+
+    #[inline]
+    fn pool_fee_account(&self) -> &Pubkey {
+        &self.pool_fee_account
+    }
+
+    #[inline]
+    fn fees(&self) -> &Fees {
+        &self.fees
+    }
+
+    #[inline]
+    fn swap_curve(&self) -> SwapCurve {
+        SwapCurve::new(self.curve_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, anchor_lang::Discriminator};
+
+    /// `SwapV1` account as written before `SwapV2`: discriminator, six keys,
+    /// the fees and the curve type
+    fn baseline_swap_v1_data(
+        keys: &[Pubkey; 6],
+        (trade_fee_numerator, trade_fee_denominator): (u64, u64),
+        curve_type: &[u8],
+    ) -> Vec<u8> {
+        let mut data = SWAP_V1_DISCRIMINATOR.to_vec();
+        for key in keys {
+            data.extend_from_slice(key.as_ref());
+        }
+        for fee in [
+            trade_fee_numerator,
+            trade_fee_denominator,
+            5,
+            10_000,
+            1,
+            6,
+            20,
+            100,
+        ] {
+            data.extend_from_slice(&fee.to_le_bytes());
+        }
+        data.extend_from_slice(curve_type);
+        data
+    }
+
+    #[test]
+    fn legacy_swap_v1_keeps_the_swap_v1_discriminator() {
+        assert_eq!(
+            LegacySwapV1::DISCRIMINATOR,
+            &anchor_lang::solana_program::hash::hash(b"account:SwapV1").to_bytes()[..8]
+        );
+    }
+
+    #[test]
+    fn swap_v2_account_size_never_changes() {
+        assert_eq!(SwapV2::INIT_SPACE, 630);
+    }
+
+    #[test]
+    fn migrate_baseline_swap_v1() {
+        let keys = [(); 6].map(|_| Pubkey::new_unique());
+        let mut curve_type = vec![1];
+        curve_type.extend_from_slice(&7u64.to_le_bytes());
+        let data = baseline_swap_v1_data(&keys, (25, 10_000), &curve_type);
+        let swap_v1 = LegacySwapV1::try_deserialize(&mut &data[..]).unwrap();
+        assert_eq!(swap_v1.token_a, keys[0]);
+        assert_eq!(swap_v1.pool_fee_account, keys[5]);
+        assert!(swap_v1.curve_type == CurveType::ConstantPrice { token_b_price: 7 });
+
+        let admin = Pubkey::new_unique();
+        let programs = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let swap_v2 = SwapV2::from_v1(swap_v1, 254, admin, programs, (100, 200), 1_000);
+        assert_eq!(swap_v2.bump_seed, 254);
+        assert_eq!(swap_v2.token_a, keys[0]);
+        assert_eq!(swap_v2.token_b, keys[1]);
+        assert_eq!(swap_v2.pool_mint, keys[2]);
+        assert_eq!(swap_v2.token_a_mint, keys[3]);
+        assert_eq!(swap_v2.token_b_mint, keys[4]);
+        assert_eq!(swap_v2.pool_fee_account, keys[5]);
+        assert_eq!(
+            (
+                swap_v2.token_a_program,
+                swap_v2.token_b_program,
+                swap_v2.pool_token_program
+            ),
+            programs
+        );
+        assert_eq!(swap_v2.fees.trade_fee_numerator, 25);
+        assert_eq!(swap_v2.fees.host_fee_denominator, 100);
+        assert!(swap_v2.curve_type == CurveType::ConstantPrice { token_b_price: 7 });
+        assert_eq!(swap_v2.fee_tier, 25);
+        assert_eq!(swap_v2.admin, admin);
+        assert!(swap_v2.status == PoolStatus::Active);
+        assert!(swap_v2.flash_loan.is_none());
+        assert!(
+            swap_v2.cumulative_prices
+                == CumulativePrices {
+                    price_a_cumulative: 0,
+                    price_b_cumulative: 0,
+                    last_update_timestamp: 1_000,
+                }
+        );
+        assert_eq!((swap_v2.reserve_a, swap_v2.reserve_b), (100, 200));
+        assert_eq!(swap_v2.pool_entry_index, None);
+
+        let mut migrated = vec![];
+        swap_v2.try_serialize(&mut migrated).unwrap();
+        assert!(migrated.len() <= SwapV2::INIT_SPACE + 8);
+        let swap_v2 = SwapV2::try_deserialize(&mut &migrated[..]).unwrap();
+        assert_eq!(swap_v2.token_b_mint, keys[4]);
+    }
+
+    #[test]
+    fn migrate_baseline_swap_v1_with_fractional_trade_fee() {
+        let keys = [(); 6].map(|_| Pubkey::new_unique());
+        let data = baseline_swap_v1_data(&keys, (1, 3), &[0]);
+        let swap_v1 = LegacySwapV1::try_deserialize(&mut &data[..]).unwrap();
+        let swap_v2 = SwapV2::from_v1(
+            swap_v1,
+            255,
+            Pubkey::new_unique(),
+            (Pubkey::default(), Pubkey::default(), Pubkey::default()),
+            (0, 0),
+            0,
+        );
+        assert_eq!(swap_v2.fee_tier, u16::MAX);
+    }
+}
@@ -1,28 +1,28 @@
 use {
     crate::{curves::CurveType, state::Fees, SwapError},
     anchor_lang::prelude::*,
-    anchor_spl::{
-        token_2022::spl_token_2022::extension::mint_close_authority::MintCloseAuthority,
-        token_interface::Mint,
-    },
 };
 
+/// Maximum number of curve types allowed by the swap constraints
+pub const MAX_VALID_CURVE_TYPES: usize = 8;
+
 /// Encodes fee constraints, used in multihost environments where the program
 /// may be used by multiple frontends, to ensure that proper fees are being
 /// assessed.
-/// Since this struct needs to be created at compile-time, we only have access
-/// to const functions and constructors. Since SwapCurve contains a Arc, it
-/// cannot be used, so we have to split the curves based on their types.
-pub struct SwapConstraints<'a> {
+/// Stored in the config account of the program, so that the policy can be
+/// changed by the upgrade authority without redeploying the program.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct SwapConstraints {
     /// Owner of the program
     pub owner_key: Option<Pubkey>,
     /// Valid curve types
-    pub valid_curve_types: &'a [CurveType],
+    #[max_len(MAX_VALID_CURVE_TYPES)]
+    pub valid_curve_types: Vec<CurveType>,
     /// Valid fees
-    pub fees: &'a Fees,
+    pub fees: Fees,
 }
 
-impl<'a> SwapConstraints<'a> {
+impl SwapConstraints {
     pub fn validate_curve(&self, curve_type: &CurveType) -> Result<()> {
         if !self.valid_curve_types.contains(curve_type) {
             return err!(SwapError::UnsupportedCurveType);
@@ -46,34 +46,28 @@ impl<'a> SwapConstraints<'a> {
             Err(SwapError::InvalidFee.into())
         }
     }
+
+    /// Checks that the constraints themselves are valid
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.valid_curve_types.len() <= MAX_VALID_CURVE_TYPES,
+            SwapError::InvalidInput
+        );
+        self.fees.validate()
+    }
 }
+
 /// Fee structure defined by program creator in order to enforce certain
 /// fees when others use the program.  Adds checks on pool creation and
 /// swapping to ensure the correct fees and account owners are passed.
-/// Fees provided during production build currently are considered min
-/// fees that creator of the pool can specify. Host fee is a fixed
-/// percentage that host receives as a portion of owner fees
-pub const SWAP_CONSTRAINTS: Option<SwapConstraints> = {
-    // #[cfg(feature = "production")]
-    // {
-    //     Some(SwapConstraints {
-    //         owner_key: OWNER_KEY,
-    //         valid_curve_types: VALID_CURVE_TYPES,
-    //         fees: FEES,
-    //     })
-    // }
-    // #[cfg(not(feature = "production"))]
-    // {
-    //     None
-    // }
-    None
-};
-
+/// Fees provided in the constraints are considered min fees that creator of
+/// the pool can specify. Host fee is a fixed percentage that host receives as
+/// a portion of owner fees
 pub fn validate_swap_constraints(
     curve_type: &CurveType,
     fees: &Fees,
     fee_account_owner: Pubkey,
-    constraints: Option<SwapConstraints>,
+    constraints: &Option<SwapConstraints>,
 ) -> Result<()> {
     if let Some(constraints) = constraints {
         if let Some(owner_key) = constraints.owner_key {
@@ -84,16 +78,3 @@ pub fn validate_swap_constraints(
     }
     Ok(())
 }
-
-pub fn validate_mint_uncloseable(mint_account: &InterfaceAccount<Mint>) -> Result<()> {
-    match anchor_spl::token_interface::get_mint_extension_data::<MintCloseAuthority>(
-        &mint_account.to_account_info(),
-    ) {
-        Ok(MintCloseAuthority { close_authority })
-            if Option::<Pubkey>::from(close_authority).is_some() =>
-        {
-            err!(SwapError::InvalidCloseAuthority)
-        }
-        _ => Ok(()),
-    }
-}
@@ -0,0 +1,128 @@
+//! Transfer hook counting the transfers of a Token-2022 mint in a PDA listed
+//! as an extra account, so that the swap tests can check that the hook runs
+//! on every transfer of the swap and receives its extra accounts.
+use {
+    anchor_lang::prelude::*,
+    spl_discriminator::SplDiscriminate,
+    spl_tlv_account_resolution::{
+        account::ExtraAccountMeta, seeds::Seed, state::ExtraAccountMetaList,
+    },
+    spl_transfer_hook_interface::instruction::ExecuteInstruction,
+};
+
+declare_id!("6hnsMD6ugMrx3x8BBavNebJ7NCKAcHrr2HoftWqPdRRk");
+
+/// Seed of the counter of a mint, along with the mint
+pub const COUNTER_SEED: &[u8] = b"counter";
+/// Seed of the extra account metas account of a mint, along with the mint, as
+/// defined by the transfer hook interface
+pub const EXTRA_ACCOUNT_METAS_SEED: &[u8] = b"extra-account-metas";
+
+#[program]
+pub mod transfer_hook_counter {
+    use super::*;
+
+    /// Create the counter of the mint and list it as the only extra account
+    /// of the hook
+    pub fn initialize_extra_account_meta_list(
+        ctx: Context<InitializeExtraAccountMetaList>,
+    ) -> Result<()> {
+        ExtraAccountMetaList::init::<ExecuteInstruction>(
+            &mut ctx.accounts.extra_account_meta_list.try_borrow_mut_data()?,
+            &extra_account_metas()?,
+        )?;
+        Ok(())
+    }
+
+    /// Count a transfer of the mint and the amount transferred
+    #[instruction(discriminator = ExecuteInstruction::SPL_DISCRIMINATOR_SLICE)]
+    pub fn transfer_hook(ctx: Context<TransferHook>, amount: u64) -> Result<()> {
+        let counter = &mut ctx.accounts.counter;
+        counter.transfers = counter
+            .transfers
+            .checked_add(1)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        counter.amount = counter
+            .amount
+            .checked_add(amount)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        Ok(())
+    }
+}
+
+/// Extra accounts of the hook: the counter of the mint, the mint being the
+/// second account of the execute instruction
+fn extra_account_metas() -> Result<Vec<ExtraAccountMeta>> {
+    Ok(vec![ExtraAccountMeta::new_with_seeds(
+        &[
+            Seed::Literal {
+                bytes: COUNTER_SEED.to_vec(),
+            },
+            Seed::AccountKey { index: 1 },
+        ],
+        false,
+        true,
+    )?])
+}
+
+/// Transfers of a mint counted by the hook
+#[account]
+#[derive(InitSpace)]
+pub struct Counter {
+    /// Number of transfers
+    pub transfers: u64,
+    /// Total amount transferred
+    pub amount: u64,
+}
+
+#[derive(Accounts)]
+pub struct InitializeExtraAccountMetaList<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: list of the extra accounts of the hook, written by the handler
+    #[account(
+        init,
+        payer = payer,
+        space = ExtraAccountMetaList::size_of(extra_account_metas()?.len())?,
+        seeds = [EXTRA_ACCOUNT_METAS_SEED, mint.key().as_ref()],
+        bump,
+    )]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+    /// CHECK: mint whose transfers are counted
+    pub mint: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = Counter::INIT_SPACE + 8,
+        seeds = [COUNTER_SEED, mint.key().as_ref()],
+        bump,
+    )]
+    pub counter: Account<'info, Counter>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts of the execute instruction of the transfer hook interface,
+/// followed by the extra accounts of the hook
+#[derive(Accounts)]
+pub struct TransferHook<'info> {
+    /// CHECK: source token account of the transfer
+    pub source: UncheckedAccount<'info>,
+    /// CHECK: mint of the transfer
+    pub mint: UncheckedAccount<'info>,
+    /// CHECK: destination token account of the transfer
+    pub destination: UncheckedAccount<'info>,
+    /// CHECK: owner or delegate of the source token account
+    pub owner: UncheckedAccount<'info>,
+    /// CHECK: list of the extra accounts of the hook
+    #[account(
+        seeds = [EXTRA_ACCOUNT_METAS_SEED, mint.key().as_ref()],
+        bump,
+    )]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [COUNTER_SEED, mint.key().as_ref()],
+        bump,
+    )]
+    pub counter: Account<'info, Counter>,
+}
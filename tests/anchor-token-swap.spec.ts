@@ -1,24 +1,39 @@
 import * as anchor from "@coral-xyz/anchor";
 import { BN, Program } from "@coral-xyz/anchor";
 import { AnchorTokenSwap } from "../target/types/anchor_token_swap";
-import { TokenSwapTest } from "./token";
+import { TransferHookCounter } from "../target/types/transfer_hook_counter";
+import {
+  ACCOUNTS_PER_HOP,
+  CurveTypeArg,
+  expectAnchorError,
+  expectEvent,
+  expectTransactionError,
+  FEE_TIER,
+  TokenSwapTest,
+} from "./token";
 import {
   approve,
+  ASSOCIATED_TOKEN_PROGRAM_ID,
   createAccount,
   createApproveInstruction,
   createInitializeAccountInstruction,
   createMintToInstruction,
   getAccount,
   getAccountLenForMint,
+  getAssociatedTokenAddressSync,
   getMinimumBalanceForRentExemptAccount,
   getMint,
+  NATIVE_MINT,
   TOKEN_2022_PROGRAM_ID,
 } from "@solana/spl-token";
 import {
+  AccountMeta,
   Keypair,
+  PublicKey,
   sendAndConfirmTransaction,
   SystemProgram,
   Transaction,
+  TransactionInstruction,
 } from "@solana/web3.js";
 import { assert } from "chai";
 
@@ -31,9 +46,54 @@ const OWNER_WITHDRAW_FEE_NUMERATOR = 1;
 const OWNER_WITHDRAW_FEE_DENOMINATOR = 6;
 const HOST_FEE_NUMERATOR = 20;
 const HOST_FEE_DENOMINATOR = 100;
+const poolFees = () => ({
+  tradeFeeNumerator: new BN(TRADING_FEE_NUMERATOR),
+  tradeFeeDenominator: new BN(TRADING_FEE_DENOMINATOR),
+  ownerTradeFeeNumerator: new BN(OWNER_TRADING_FEE_NUMERATOR),
+  ownerTradeFeeDenominator: new BN(OWNER_TRADING_FEE_DENOMINATOR),
+  ownerWithdrawFeeNumerator: new BN(OWNER_WITHDRAW_FEE_NUMERATOR),
+  ownerWithdrawFeeDenominator: new BN(OWNER_WITHDRAW_FEE_DENOMINATOR),
+  hostFeeNumerator: new BN(HOST_FEE_NUMERATOR),
+  hostFeeDenominator: new BN(HOST_FEE_DENOMINATOR),
+});
+
+/**
+ * Accounts of `swap` from `sourceMint` to the other mint of the swap, the
+ * owner of the test being the user transfer authority
+ * @param userSource null to swap SOL of the owner
+ * @param userDestination null to receive into the associated token account
+ * of the owner, or SOL for wrapped SOL
+ */
+const swapAccounts = (
+  test: TokenSwapTest,
+  sourceMint: PublicKey,
+  userSource: PublicKey | null,
+  userDestination: PublicKey | null
+) => {
+  const destinationMint = test.otherMint(sourceMint);
+  return {
+    tokenSwap: test.tokenSwap,
+    authority: test.authority,
+    userTransferAuthority: test.owner.publicKey,
+    userSource,
+    swapSource: test.vault(sourceMint),
+    userDestination,
+    swapDestination: test.vault(destinationMint),
+    poolMint: test.poolMint,
+    hostFeeAccount: null,
+    poolFeeAccount: test.poolFeeAccount,
+    sourceTokenMint: sourceMint,
+    destinationTokenMint: destinationMint,
+    tokenSourceProgram: test.tokenProgram(sourceMint),
+    tokenDestinationProgram: test.tokenProgram(destinationMint),
+    tokenPoolProgram: TOKEN_2022_PROGRAM_ID,
+  };
+};
 
 // Pool token amount minted on init
 const DEFAULT_POOL_TOKEN_AMOUNT = 1000000000n;
+// Pool token amount locked forever on init
+const MINIMUM_LIQUIDITY = 1000n;
 // Pool token amount to withdraw / deposit, which is 1% of `DEFAULT_POOL_TOKEN_AMOUNT`
 const TEST_POOL_TOKEN_AMOUNT = 10000000n;
 const SWAP_AMOUNT_IN = 100000n;
@@ -56,33 +116,66 @@ describe("anchor-token-swap", () => {
     tokenSwapTest = await TokenSwapTest.init(connection, program.programId);
   });
 
+  const [config] = PublicKey.findProgramAddressSync(
+    [Buffer.from("config")],
+    program.programId
+  );
+  const [programData] = PublicKey.findProgramAddressSync(
+    [program.programId.toBuffer()],
+    new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+  );
+  const [factory] = PublicKey.findProgramAddressSync(
+    [Buffer.from("factory")],
+    program.programId
+  );
+  const [poolEntry] = PublicKey.findProgramAddressSync(
+    [Buffer.from("pool_entry"), new BN(0).toArrayLike(Buffer, "le", 8)],
+    program.programId
+  );
+
   it("It should initialized!", async () => {
-    await program.methods
+    const configSignature = await program.methods
+      .initializeConfig(null)
+      .accountsPartial({
+        config,
+        upgradeAuthority: provider.wallet.publicKey,
+        swapProgram: program.programId,
+        programData,
+      })
+      .rpc();
+    await program.methods.initializeFactory().rpc();
+    const signature = await program.methods
       .initialize(
         {
           constantProduct: {},
         },
-        {
-          tradeFeeNumerator: new BN(TRADING_FEE_NUMERATOR),
-          tradeFeeDenominator: new BN(TRADING_FEE_DENOMINATOR),
-          ownerTradeFeeNumerator: new BN(OWNER_TRADING_FEE_NUMERATOR),
-          ownerTradeFeeDenominator: new BN(OWNER_TRADING_FEE_DENOMINATOR),
-          ownerWithdrawFeeNumerator: new BN(OWNER_WITHDRAW_FEE_NUMERATOR),
-          ownerWithdrawFeeDenominator: new BN(OWNER_WITHDRAW_FEE_DENOMINATOR),
-          hostFeeNumerator: new BN(HOST_FEE_NUMERATOR),
-          hostFeeDenominator: new BN(HOST_FEE_DENOMINATOR),
-        }
+        poolFees(),
+        FEE_TIER,
+        new BN(tokenSwapTest.amountOfCurrentSwapToken.a.toString()),
+        new BN(tokenSwapTest.amountOfCurrentSwapToken.b.toString())
       )
-      .accounts({
-        swapV1: tokenSwapTest.tokenSwapAccount.publicKey,
+      .accountsPartial({
+        config,
+        tokenAMint: tokenSwapTest.mintA,
+        tokenBMint: tokenSwapTest.mintB,
+        tokenSwap: tokenSwapTest.tokenSwap,
         swapTokenA: tokenSwapTest.swapTokenA,
         swapTokenB: tokenSwapTest.swapTokenB,
         poolMint: tokenSwapTest.poolMint,
+        lockedLiquidity: tokenSwapTest.lockedLiquidity,
+        factory,
+        poolEntry,
+        userTransferAuthority: tokenSwapTest.owner.publicKey,
+        userTokenA: tokenSwapTest.userTokenA,
+        userTokenB: tokenSwapTest.userTokenB,
         userPoolToken: tokenSwapTest.userPoolTokenAccount,
+        poolFeeOwner: tokenSwapTest.poolFeeOwner,
         poolFeeAccount: tokenSwapTest.poolFeeAccount,
+        tokenAProgram: TOKEN_2022_PROGRAM_ID,
+        tokenBProgram: TOKEN_2022_PROGRAM_ID,
         tokenPoolProgram: TOKEN_2022_PROGRAM_ID,
       })
-      .signers([tokenSwapTest.tokenSwapAccount])
+      .signers([tokenSwapTest.owner])
       .rpc();
 
     const userPoolTokenReciever = await tokenSwapTest.getAccount(
@@ -92,15 +185,60 @@ describe("anchor-token-swap", () => {
 
     assert.equal(
       userPoolTokenReciever.amount,
-      BigInt(DEFAULT_POOL_TOKEN_AMOUNT)
+      DEFAULT_POOL_TOKEN_AMOUNT - MINIMUM_LIQUIDITY
+    );
+    const lockedLiquidity = await tokenSwapTest.getAccount(
+      connection,
+      tokenSwapTest.lockedLiquidity
     );
+    assert.equal(lockedLiquidity.amount, MINIMUM_LIQUIDITY);
     const poolMint = await tokenSwapTest.getPoolMint(connection);
     assert.equal(poolMint.supply, BigInt(DEFAULT_POOL_TOKEN_AMOUNT));
-    const swapV1 = await program.account.swapV1.fetch(
-      tokenSwapTest.tokenSwapAccount.publicKey
+    const swap = await program.account.swapV2.fetch(tokenSwapTest.tokenSwap);
+    assert.ok(swap.fees.tradeFeeNumerator.eq(new BN(TRADING_FEE_NUMERATOR)));
+    assert.ok(swap.tokenA.equals(tokenSwapTest.swapTokenA));
+    assert.equal(swap.bumpSeed, tokenSwapTest.authorityBumpSeed);
+    assert.ok(swap.tokenAProgram.equals(TOKEN_2022_PROGRAM_ID));
+    assert.ok(swap.poolTokenProgram.equals(TOKEN_2022_PROGRAM_ID));
+    assert.ok(
+      swap.reserveA.eq(new BN(tokenSwapTest.amountOfCurrentSwapToken.a.toString()))
+    );
+    assert.ok(
+      swap.reserveB.eq(new BN(tokenSwapTest.amountOfCurrentSwapToken.b.toString()))
+    );
+
+    const configUpdated = await expectEvent(
+      program,
+      configSignature,
+      "ConfigUpdated"
+    );
+    assert.isNull(configUpdated.constraints);
+    const poolInitialized = await expectEvent(
+      program,
+      signature,
+      "PoolInitialized"
     );
-    assert.ok(swapV1.fees.tradeFeeNumerator.eq(new BN(TRADING_FEE_NUMERATOR)));
-    assert.ok(swapV1.tokenA.equals(tokenSwapTest.swapTokenA));
+    assert.ok(poolInitialized.tokenSwap.equals(tokenSwapTest.tokenSwap));
+    assert.ok(poolInitialized.admin.equals(provider.wallet.publicKey));
+    assert.equal(poolInitialized.feeTier, FEE_TIER);
+    assert.equal(
+      poolInitialized.poolTokensMinted.toString(),
+      (DEFAULT_POOL_TOKEN_AMOUNT - MINIMUM_LIQUIDITY).toString()
+    );
+    assert.ok(poolInitialized.reserveA.eq(swap.reserveA));
+  });
+
+  it("It should listPools", async () => {
+    const page = await program.methods
+      .listPools(new BN(0))
+      .remainingAccounts([
+        { pubkey: poolEntry, isSigner: false, isWritable: false },
+      ])
+      .view();
+    assert.ok(page.poolCount.eq(new BN(1)));
+    assert.equal(page.entries.length, 1);
+    assert.ok(page.entries[0].tokenSwap.equals(tokenSwapTest.tokenSwap));
+    assert.equal(page.entries[0].feeTier, FEE_TIER);
   });
 
   it("It should depositAllTokenTypes", async () => {
@@ -156,15 +294,16 @@ describe("anchor-token-swap", () => {
       TOKEN_2022_PROGRAM_ID
     );
 
-    await program.methods
+    const signature = await program.methods
       .depositAllTokenTypes(
         new BN(TEST_POOL_TOKEN_AMOUNT.toString()),
         new BN(amountOftokenAToDeposit.toString()),
-        new BN(amountOftokenBToDeposit.toString())
+        new BN(amountOftokenBToDeposit.toString()),
+        null
       )
       .accounts({
         payer: tokenSwapTest.payer.publicKey,
-        swapV1: tokenSwapTest.tokenSwapAccount.publicKey,
+        tokenSwap: tokenSwapTest.tokenSwap,
         userTransferAuthority: userTransferAuthority.publicKey,
         sourceA: userAccountA,
         sourceB: userAccountB,
@@ -197,6 +336,21 @@ describe("anchor-token-swap", () => {
       userPoolToken
     );
     assert(userPoolTokenInfo.amount == TEST_POOL_TOKEN_AMOUNT);
+    const deposited = await expectEvent(program, signature, "Deposited");
+    assert.ok(deposited.tokenSwap.equals(tokenSwapTest.tokenSwap));
+    assert.ok(deposited.user.equals(userTransferAuthority.publicKey));
+    assert.equal(
+      deposited.tokenAAmount.toString(),
+      amountOftokenAToDeposit.toString()
+    );
+    assert.equal(
+      deposited.tokenBAmount.toString(),
+      amountOftokenBToDeposit.toString()
+    );
+    assert.equal(
+      deposited.poolTokensMinted.toString(),
+      TEST_POOL_TOKEN_AMOUNT.toString()
+    );
   });
 
   it("It should withdrawAllTokenTypes", async () => {
@@ -233,15 +387,16 @@ describe("anchor-token-swap", () => {
       TOKEN_2022_PROGRAM_ID
     );
 
-    await program.methods
+    const signature = await program.methods
       .withdrawAllTokenTypes(
         new BN(TEST_POOL_TOKEN_AMOUNT.toString()),
         new BN(expectedWithdrawAmountOftokenA.toString()),
-        new BN(expectedWithdrawAmountOftokenB.toString())
+        new BN(expectedWithdrawAmountOftokenB.toString()),
+        null
       )
       .accounts({
         payer: tokenSwapTest.payer.publicKey,
-        swapV1: tokenSwapTest.tokenSwapAccount.publicKey,
+        tokenSwap: tokenSwapTest.tokenSwap,
         userTransferAuthority: userTransferAuthority.publicKey,
         destinationA: userAccountA,
         destinationB: userAccountB,
@@ -276,6 +431,21 @@ describe("anchor-token-swap", () => {
       userAccountB
     );
     assert(userAccountBInfo.amount == expectedWithdrawAmountOftokenB);
+    const withdrawn = await expectEvent(program, signature, "Withdrawn");
+    assert.ok(withdrawn.tokenSwap.equals(tokenSwapTest.tokenSwap));
+    assert.equal(
+      withdrawn.tokenAAmount.toString(),
+      expectedWithdrawAmountOftokenA.toString()
+    );
+    assert.equal(
+      withdrawn.tokenBAmount.toString(),
+      expectedWithdrawAmountOftokenB.toString()
+    );
+    assert.equal(
+      withdrawn.poolTokensBurned.toString(),
+      TEST_POOL_TOKEN_AMOUNT.toString()
+    );
+    assert.equal(withdrawn.withdrawFee.toString(), "0");
   });
   it("It should create account & swap in a single tx", async () => {
     const sourceUserAccountA = Keypair.generate();
@@ -348,10 +518,11 @@ describe("anchor-token-swap", () => {
     let swapInstruction = await program.methods
       .swap(
         new BN(SWAP_AMOUNT_IN.toString()),
-        new BN(EXPECTED_SWAP_AMOUNT_OUT.toString())
+        new BN(EXPECTED_SWAP_AMOUNT_OUT.toString()),
+        null
       )
       .accounts({
-        tokenSwap: tokenSwapTest.tokenSwapAccount.publicKey,
+        tokenSwap: tokenSwapTest.tokenSwap,
         swapSource: tokenSwapTest.swapTokenA,
         userSource: sourceUserAccountA.publicKey,
         swapDestination: tokenSwapTest.swapTokenB,
@@ -442,11 +613,12 @@ describe("anchor-token-swap", () => {
     await program.methods
       .depositSingleTokenTypeExactAmountIn(
         new BN(depositAmount.toString()),
-        new BN(0)
+        new BN(0),
+        null
       )
       .accounts({
         payer: tokenSwapTest.payer.publicKey,
-        swapV1: tokenSwapTest.tokenSwapAccount.publicKey,
+        tokenSwap: tokenSwapTest.tokenSwap,
         userTransferAuthority: userTransferAuthority.publicKey,
         source: userAccountA,
         sourceTokenMint: tokenSwapTest.mintA,
@@ -477,11 +649,12 @@ describe("anchor-token-swap", () => {
     await program.methods
       .depositSingleTokenTypeExactAmountIn(
         new BN(depositAmount.toString()),
-        new BN(0)
+        new BN(0),
+        null
       )
       .accounts({
         payer: tokenSwapTest.payer.publicKey,
-        swapV1: tokenSwapTest.tokenSwapAccount.publicKey,
+        tokenSwap: tokenSwapTest.tokenSwap,
         userTransferAuthority: userTransferAuthority.publicKey,
         source: userAccountB,
         sourceTokenMint: tokenSwapTest.mintB,
@@ -511,6 +684,80 @@ describe("anchor-token-swap", () => {
     );
   });
 
+  it("It should depositSingleTokenTypeExactAmountOut", async () => {
+    // Pool token amount to receive, and the most token A to pay for it
+    const poolTokenAmount = 10000n;
+    const maximumSourceAmount = 100000n;
+
+    const userTransferAuthority = Keypair.generate();
+    const [userAccountA, userAccountB] = await tokenSwapTest.createTokenPair(
+      connection
+    );
+    await tokenSwapTest.mintToTokenPair(
+      connection,
+      userAccountA,
+      userAccountB,
+      maximumSourceAmount,
+      maximumSourceAmount
+    );
+    await tokenSwapTest.approveForPair(
+      connection,
+      userAccountA,
+      userAccountB,
+      userTransferAuthority.publicKey,
+      maximumSourceAmount,
+      maximumSourceAmount
+    );
+    const userPoolTokenBefore = await tokenSwapTest.getAccount(
+      connection,
+      tokenSwapTest.userPoolTokenAccount
+    );
+    await program.methods
+      .depositSingleTokenTypeExactAmountOut(
+        new BN(poolTokenAmount.toString()),
+        new BN(maximumSourceAmount.toString()),
+        null
+      )
+      .accounts({
+        payer: tokenSwapTest.payer.publicKey,
+        tokenSwap: tokenSwapTest.tokenSwap,
+        userTransferAuthority: userTransferAuthority.publicKey,
+        source: userAccountA,
+        sourceTokenMint: tokenSwapTest.mintA,
+        swapTokenA: tokenSwapTest.swapTokenA,
+        swapTokenB: tokenSwapTest.swapTokenB,
+        sourceTokenProgram: TOKEN_2022_PROGRAM_ID,
+        tokenPoolProgram: TOKEN_2022_PROGRAM_ID,
+        poolMint: tokenSwapTest.poolMint,
+        poolTokenDestination: tokenSwapTest.userPoolTokenAccount,
+      })
+      .signers([tokenSwapTest.payer, userTransferAuthority])
+      .rpc();
+    const userPoolTokenAfter = await tokenSwapTest.getAccount(
+      connection,
+      tokenSwapTest.userPoolTokenAccount
+    );
+    assert.equal(
+      userPoolTokenAfter.amount - userPoolTokenBefore.amount,
+      poolTokenAmount
+    );
+    const userAccountAInfo = await tokenSwapTest.getAccount(
+      connection,
+      userAccountA
+    );
+    const sourceAmount = maximumSourceAmount - userAccountAInfo.amount;
+    assert.ok(sourceAmount > 0n);
+    tokenSwapTest.amountOfCurrentSwapToken.a += sourceAmount;
+    const swapTokenAInfo = await tokenSwapTest.getAccount(
+      connection,
+      tokenSwapTest.swapTokenA
+    );
+    assert.equal(
+      swapTokenAInfo.amount,
+      tokenSwapTest.amountOfCurrentSwapToken.a
+    );
+  });
+
   it("It should withdrawSingleTokenTypeExactAmountIn", async () => {
     // Pool token amount to withdraw on one side
     const withdrawAmount = 50000n;
@@ -532,11 +779,12 @@ describe("anchor-token-swap", () => {
     await program.methods
       .withdrawSingleTokenTypeExactAmountOut(
         new BN(withdrawAmount.toString()),
-        new BN(adjustedPoolTokenB.toString())
+        new BN(adjustedPoolTokenB.toString()),
+        null
       )
       .accounts({
         payer: tokenSwapTest.payer.publicKey,
-        swapV1: tokenSwapTest.tokenSwapAccount.publicKey,
+        tokenSwap: tokenSwapTest.tokenSwap,
         poolTokenSource: tokenSwapTest.userPoolTokenAccount,
         userTransferAuthority: userTransferAuthority.publicKey,
         userTokenDestination: userAccountB,
@@ -558,4 +806,2307 @@ describe("anchor-token-swap", () => {
     );
     assert.equal(userAccountBInfo.amount, withdrawAmount);
   });
+
+  it("It should withdrawSingleTokenTypeExactAmountIn with exact pool tokens", async () => {
+    const poolTokenAmount = 100000n;
+    const userTransferAuthority = Keypair.generate();
+    const [userAccountA] = await tokenSwapTest.createTokenPair(connection);
+    await approve(
+      connection,
+      tokenSwapTest.payer,
+      tokenSwapTest.userPoolTokenAccount,
+      userTransferAuthority.publicKey,
+      tokenSwapTest.owner,
+      poolTokenAmount,
+      [],
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+    const userPoolTokenBefore = await tokenSwapTest.getAccount(
+      connection,
+      tokenSwapTest.userPoolTokenAccount
+    );
+    await program.methods
+      .withdrawSingleTokenTypeExactAmountIn(
+        new BN(poolTokenAmount.toString()),
+        new BN(1),
+        null
+      )
+      .accounts({
+        payer: tokenSwapTest.payer.publicKey,
+        tokenSwap: tokenSwapTest.tokenSwap,
+        poolTokenSource: tokenSwapTest.userPoolTokenAccount,
+        userTransferAuthority: userTransferAuthority.publicKey,
+        userTokenDestination: userAccountA,
+        swapTokenA: tokenSwapTest.swapTokenA,
+        swapTokenB: tokenSwapTest.swapTokenB,
+        poolMint: tokenSwapTest.poolMint,
+        tokenAMint: tokenSwapTest.mintA,
+        tokenBMint: tokenSwapTest.mintB,
+        tokenPoolProgram: TOKEN_2022_PROGRAM_ID,
+        destinationTokenMint: tokenSwapTest.mintA,
+        destinationTokenProgram: TOKEN_2022_PROGRAM_ID,
+        poolFeeAccount: null,
+      })
+      .signers([tokenSwapTest.payer, userTransferAuthority])
+      .rpc();
+    const userPoolTokenAfter = await tokenSwapTest.getAccount(
+      connection,
+      tokenSwapTest.userPoolTokenAccount
+    );
+    assert.equal(
+      userPoolTokenBefore.amount - userPoolTokenAfter.amount,
+      poolTokenAmount
+    );
+    const userAccountAInfo = await tokenSwapTest.getAccount(
+      connection,
+      userAccountA
+    );
+    assert.ok(userAccountAInfo.amount > 0n);
+  });
+
+  it("It should withdrawSingleTokenTypeExactAmountIn into a new associated token account", async () => {
+    const poolTokenAmount = 100000n;
+    const userTransferAuthority = Keypair.generate();
+    await approve(
+      connection,
+      tokenSwapTest.payer,
+      tokenSwapTest.userPoolTokenAccount,
+      userTransferAuthority.publicKey,
+      tokenSwapTest.owner,
+      poolTokenAmount,
+      [],
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+    const userTokenDestinationAta = getAssociatedTokenAddressSync(
+      tokenSwapTest.mintA,
+      userTransferAuthority.publicKey,
+      false,
+      TOKEN_2022_PROGRAM_ID
+    );
+    await program.methods
+      .withdrawSingleTokenTypeExactAmountIn(
+        new BN(poolTokenAmount.toString()),
+        new BN(1),
+        null
+      )
+      .accounts({
+        payer: tokenSwapTest.payer.publicKey,
+        tokenSwap: tokenSwapTest.tokenSwap,
+        poolTokenSource: tokenSwapTest.userPoolTokenAccount,
+        userTransferAuthority: userTransferAuthority.publicKey,
+        userTokenDestination: null,
+        swapTokenA: tokenSwapTest.swapTokenA,
+        swapTokenB: tokenSwapTest.swapTokenB,
+        poolMint: tokenSwapTest.poolMint,
+        tokenAMint: tokenSwapTest.mintA,
+        tokenBMint: tokenSwapTest.mintB,
+        tokenPoolProgram: TOKEN_2022_PROGRAM_ID,
+        destinationTokenMint: tokenSwapTest.mintA,
+        destinationTokenProgram: TOKEN_2022_PROGRAM_ID,
+        poolFeeAccount: null,
+        userTokenDestinationAta,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+      })
+      .signers([tokenSwapTest.payer, userTransferAuthority])
+      .rpc();
+    const userTokenDestinationInfo = await tokenSwapTest.getAccount(
+      connection,
+      userTokenDestinationAta
+    );
+    assert.ok(userTokenDestinationInfo.amount > 0n);
+  });
+
+  describe("admin", () => {
+    const admin = provider.wallet.publicKey;
+    const newFees = () => ({
+      ...poolFees(),
+      ownerTradeFeeNumerator: new BN(OWNER_TRADING_FEE_NUMERATOR + 1),
+    });
+
+    it("It should store the payer of initialize as admin", async () => {
+      const swap = await program.account.swapV2.fetch(tokenSwapTest.tokenSwap);
+      assert.ok(swap.admin.equals(admin));
+    });
+
+    it("It should reject updateFees from a non-admin", async () => {
+      const notAdmin = Keypair.generate();
+      await expectAnchorError(
+        program.methods
+          .updateFees(newFees())
+          .accountsPartial({
+            tokenSwap: tokenSwapTest.tokenSwap,
+            admin: notAdmin.publicKey,
+            config,
+            poolFeeAccount: tokenSwapTest.poolFeeAccount,
+          })
+          .signers([notAdmin])
+          .rpc(),
+        "InvalidAdmin"
+      );
+      const swap = await program.account.swapV2.fetch(tokenSwapTest.tokenSwap);
+      assert.ok(
+        swap.fees.ownerTradeFeeNumerator.eq(new BN(OWNER_TRADING_FEE_NUMERATOR))
+      );
+    });
+
+    it("It should updateFees by the admin", async () => {
+      const signature = await program.methods
+        .updateFees(newFees())
+        .accountsPartial({
+          tokenSwap: tokenSwapTest.tokenSwap,
+          admin,
+          config,
+          poolFeeAccount: tokenSwapTest.poolFeeAccount,
+        })
+        .rpc();
+      const swap = await program.account.swapV2.fetch(tokenSwapTest.tokenSwap);
+      assert.ok(
+        swap.fees.ownerTradeFeeNumerator.eq(
+          new BN(OWNER_TRADING_FEE_NUMERATOR + 1)
+        )
+      );
+      const feesUpdated = await expectEvent(program, signature, "FeesUpdated");
+      assert.ok(feesUpdated.tokenSwap.equals(tokenSwapTest.tokenSwap));
+      assert.ok(
+        feesUpdated.oldFees.ownerTradeFeeNumerator.eq(
+          new BN(OWNER_TRADING_FEE_NUMERATOR)
+        )
+      );
+      assert.ok(
+        feesUpdated.newFees.ownerTradeFeeNumerator.eq(
+          new BN(OWNER_TRADING_FEE_NUMERATOR + 1)
+        )
+      );
+    });
+
+    it("It should update the trade fee, keeping the fee tier", async () => {
+      await program.methods
+        .updateFees({
+          ...newFees(),
+          tradeFeeNumerator: new BN(TRADING_FEE_NUMERATOR + 5),
+        })
+        .accountsPartial({
+          tokenSwap: tokenSwapTest.tokenSwap,
+          admin,
+          config,
+          poolFeeAccount: tokenSwapTest.poolFeeAccount,
+        })
+        .rpc();
+      const swap = await program.account.swapV2.fetch(tokenSwapTest.tokenSwap);
+      assert.ok(
+        swap.fees.tradeFeeNumerator.eq(new BN(TRADING_FEE_NUMERATOR + 5))
+      );
+      // the fee tier is the seed the swap was created with
+      assert.equal(swap.feeTier, FEE_TIER);
+      await program.methods
+        .updateFees(newFees())
+        .accountsPartial({
+          tokenSwap: tokenSwapTest.tokenSwap,
+          admin,
+          config,
+          poolFeeAccount: tokenSwapTest.poolFeeAccount,
+        })
+        .rpc();
+    });
+
+    it("It should hand the admin over with setAdmin", async () => {
+      const newAdmin = Keypair.generate();
+      await expectAnchorError(
+        program.methods
+          .setAdmin(newAdmin.publicKey)
+          .accountsPartial({
+            tokenSwap: tokenSwapTest.tokenSwap,
+            admin: newAdmin.publicKey,
+          })
+          .signers([newAdmin])
+          .rpc(),
+        "InvalidAdmin"
+      );
+      const signature = await program.methods
+        .setAdmin(newAdmin.publicKey)
+        .accountsPartial({ tokenSwap: tokenSwapTest.tokenSwap, admin })
+        .rpc();
+      let swap = await program.account.swapV2.fetch(tokenSwapTest.tokenSwap);
+      assert.ok(swap.admin.equals(newAdmin.publicKey));
+      const adminUpdated = await expectEvent(program, signature, "AdminUpdated");
+      assert.ok(adminUpdated.tokenSwap.equals(tokenSwapTest.tokenSwap));
+      assert.ok(adminUpdated.oldAdmin.equals(admin));
+      assert.ok(adminUpdated.newAdmin.equals(newAdmin.publicKey));
+
+      // the previous admin lost its rights
+      await expectAnchorError(
+        program.methods
+          .updateFees(newFees())
+          .accountsPartial({
+            tokenSwap: tokenSwapTest.tokenSwap,
+            admin,
+            config,
+            poolFeeAccount: tokenSwapTest.poolFeeAccount,
+          })
+          .rpc(),
+        "InvalidAdmin"
+      );
+
+      // hand it back for the following tests
+      await program.methods
+        .setAdmin(admin)
+        .accountsPartial({
+          tokenSwap: tokenSwapTest.tokenSwap,
+          admin: newAdmin.publicKey,
+        })
+        .signers([newAdmin])
+        .rpc();
+      swap = await program.account.swapV2.fetch(tokenSwapTest.tokenSwap);
+      assert.ok(swap.admin.equals(admin));
+    });
+  });
+
+  describe("flash loans", () => {
+    const FLASH_LOAN_AMOUNT = 100000n;
+    // trading fee of `FLASH_LOAN_AMOUNT`
+    const FLASH_LOAN_FEE = 250n;
+    // pool whose mints charge a transfer fee of 1%
+    let transferFeeSwap: TokenSwapTest;
+
+    before(async () => {
+      transferFeeSwap = await TokenSwapTest.init(connection, program.programId, {
+        mints: [{ transferFeeBasisPoints: 100 }, { transferFeeBasisPoints: 100 }],
+      });
+      await transferFeeSwap.initialize(program, poolFees());
+    });
+
+    const flashBorrow = (
+      test: TokenSwapTest,
+      destination: PublicKey,
+      amount: bigint = FLASH_LOAN_AMOUNT
+    ) =>
+      program.methods
+        .flashBorrow(new BN(amount.toString()))
+        .accountsPartial({
+          tokenSwap: test.tokenSwap,
+          authority: test.authority,
+          borrower: test.owner.publicKey,
+          swapToken: test.swapTokenA,
+          destination,
+          tokenMint: test.mintA,
+          tokenProgram: test.tokenAProgram,
+        })
+        .instruction();
+    const flashRepay = (test: TokenSwapTest, source: PublicKey) =>
+      program.methods
+        .flashRepay()
+        .accountsPartial({
+          tokenSwap: test.tokenSwap,
+          userTransferAuthority: test.owner.publicKey,
+          swapToken: test.swapTokenA,
+          source,
+          tokenMint: test.mintA,
+          tokenProgram: test.tokenAProgram,
+        })
+        .instruction();
+    const send = (
+      test: TokenSwapTest,
+      ...instructions: TransactionInstruction[]
+    ) =>
+      sendAndConfirmTransaction(
+        connection,
+        new Transaction().add(...instructions),
+        [test.payer, test.owner],
+        { commitment: "confirmed" }
+      );
+
+    it("It should borrow and repay with the fee in one transaction", async () => {
+      const [userAccountA, userAccountB] =
+        await tokenSwapTest.createTokenPair(connection);
+      await tokenSwapTest.mintToTokenPair(
+        connection,
+        userAccountA,
+        userAccountB,
+        FLASH_LOAN_FEE,
+        0n
+      );
+      const before = await program.account.swapV2.fetch(tokenSwapTest.tokenSwap);
+      const [swapTokenABefore] = await tokenSwapTest.getSwapTokenAccounts(
+        connection
+      );
+
+      const signature = await send(
+        tokenSwapTest,
+        await flashBorrow(tokenSwapTest, userAccountA),
+        await flashRepay(tokenSwapTest, userAccountA)
+      );
+
+      const [swapTokenA] = await tokenSwapTest.getSwapTokenAccounts(connection);
+      assert.equal(swapTokenA.amount, swapTokenABefore.amount + FLASH_LOAN_FEE);
+      const userAccountAInfo = await tokenSwapTest.getAccount(
+        connection,
+        userAccountA
+      );
+      assert.equal(userAccountAInfo.amount, 0n);
+      const swap = await program.account.swapV2.fetch(tokenSwapTest.tokenSwap);
+      assert.isNull(swap.flashLoan);
+      assert.ok(
+        swap.reserveA.eq(before.reserveA.add(new BN(FLASH_LOAN_FEE.toString())))
+      );
+      assert.ok(
+        swap.cumulativePrices.lastUpdateTimestamp.gte(
+          before.cumulativePrices.lastUpdateTimestamp
+        )
+      );
+      const borrowed = await expectEvent(program, signature, "FlashBorrowed");
+      assert.ok(borrowed.tokenSwap.equals(tokenSwapTest.tokenSwap));
+      assert.ok(borrowed.mint.equals(tokenSwapTest.mintA));
+      assert.equal(borrowed.amount.toString(), FLASH_LOAN_AMOUNT.toString());
+      assert.equal(borrowed.fee.toString(), FLASH_LOAN_FEE.toString());
+      const repaid = await expectEvent(program, signature, "FlashRepaid");
+      assert.equal(
+        repaid.amountRepaid.toString(),
+        (FLASH_LOAN_AMOUNT + FLASH_LOAN_FEE).toString()
+      );
+      assert.equal(repaid.fee.toString(), FLASH_LOAN_FEE.toString());
+      assert.ok(repaid.reserve.eq(swap.reserveA));
+      tokenSwapTest.amountOfCurrentSwapToken.a = swapTokenA.amount;
+    });
+
+    it("It should reject a borrow without a repay", async () => {
+      const [userAccountA] = await tokenSwapTest.createTokenPair(connection);
+      await expectAnchorError(
+        send(tokenSwapTest, await flashBorrow(tokenSwapTest, userAccountA)),
+        "FlashLoanNotRepaid"
+      );
+    });
+
+    it("It should reject a repay of another swap", async () => {
+      const [userAccountA] = await tokenSwapTest.createTokenPair(connection);
+      const [otherAccountA] = await transferFeeSwap.createTokenPair(connection);
+      await expectAnchorError(
+        send(
+          tokenSwapTest,
+          await flashBorrow(tokenSwapTest, userAccountA),
+          await flashRepay(transferFeeSwap, otherAccountA)
+        ),
+        "FlashLoanNotRepaid"
+      );
+    });
+
+    it("It should reject a repay missing the fee", async () => {
+      // the borrowed amount alone can not cover the fee
+      const [userAccountA] = await tokenSwapTest.createTokenPair(connection);
+      await expectTransactionError(
+        send(
+          tokenSwapTest,
+          await flashBorrow(tokenSwapTest, userAccountA),
+          await flashRepay(tokenSwapTest, userAccountA)
+        ),
+        "insufficient funds"
+      );
+    });
+
+    it("It should reject a nested borrow", async () => {
+      const [userAccountA, userAccountB] =
+        await tokenSwapTest.createTokenPair(connection);
+      await tokenSwapTest.mintToTokenPair(
+        connection,
+        userAccountA,
+        userAccountB,
+        2n * FLASH_LOAN_FEE,
+        0n
+      );
+      await expectAnchorError(
+        send(
+          tokenSwapTest,
+          await flashBorrow(tokenSwapTest, userAccountA),
+          await flashBorrow(tokenSwapTest, userAccountA),
+          await flashRepay(tokenSwapTest, userAccountA)
+        ),
+        "FlashLoanInProgress"
+      );
+    });
+
+    it("It should repay the transfer fee of the mint", async () => {
+      const [userAccountA, userAccountB] =
+        await transferFeeSwap.createTokenPair(connection);
+      // covers the fee of the loan, and the transfer fee both ways
+      const funding = 5000n;
+      await transferFeeSwap.mintToTokenPair(
+        connection,
+        userAccountA,
+        userAccountB,
+        funding,
+        0n
+      );
+      const [swapTokenABefore] = await transferFeeSwap.getSwapTokenAccounts(
+        connection
+      );
+
+      await send(
+        transferFeeSwap,
+        await flashBorrow(transferFeeSwap, userAccountA),
+        await flashRepay(transferFeeSwap, userAccountA)
+      );
+
+      // the swap gets back exactly the loan and its fee
+      const [swapTokenA] = await transferFeeSwap.getSwapTokenAccounts(
+        connection
+      );
+      assert.equal(swapTokenA.amount, swapTokenABefore.amount + FLASH_LOAN_FEE);
+      const userAccountAInfo = await transferFeeSwap.getAccount(
+        connection,
+        userAccountA
+      );
+      assert.isBelow(Number(userAccountAInfo.amount), Number(funding));
+    });
+  });
+
+  describe("close pool", () => {
+    const admin = provider.wallet.publicKey;
+    // pool closed and created again by these tests
+    let closedSwap: TokenSwapTest;
+    let observations: PublicKey;
+    let poolEntryIndex: BN;
+
+    before(async () => {
+      closedSwap = await TokenSwapTest.init(connection, program.programId);
+      await closedSwap.initialize(program, poolFees());
+      const swap = await program.account.swapV2.fetch(closedSwap.tokenSwap);
+      poolEntryIndex = swap.poolEntryIndex;
+      [observations] = PublicKey.findProgramAddressSync(
+        [Buffer.from("observations"), closedSwap.tokenSwap.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .initializeObservations()
+        .accountsPartial({ tokenSwap: closedSwap.tokenSwap, observations })
+        .rpc();
+    });
+
+    const closePool = async (signer?: Keypair) => {
+      const [destinationA, destinationB] = await closedSwap.createTokenPair(
+        connection
+      );
+      return program.methods
+        .closePool()
+        .accountsPartial({
+          admin: signer?.publicKey ?? admin,
+          tokenSwap: closedSwap.tokenSwap,
+          authority: closedSwap.authority,
+          swapTokenA: closedSwap.swapTokenA,
+          swapTokenB: closedSwap.swapTokenB,
+          poolMint: closedSwap.poolMint,
+          lockedLiquidity: closedSwap.lockedLiquidity,
+          poolEntry: closedSwap.poolEntry,
+          observations,
+          tokenAMint: closedSwap.mintA,
+          tokenBMint: closedSwap.mintB,
+          destinationA,
+          destinationB,
+          tokenAProgram: closedSwap.tokenAProgram,
+          tokenBProgram: closedSwap.tokenBProgram,
+          tokenPoolProgram: TOKEN_2022_PROGRAM_ID,
+        })
+        .signers(signer ? [signer] : [])
+        .rpc();
+    };
+
+    it("It should reject closePool from a non-admin", async () => {
+      const notAdmin = Keypair.generate();
+      await expectAnchorError(closePool(notAdmin), "InvalidAdmin");
+    });
+
+    it("It should reject closePool with outstanding pool tokens", async () => {
+      await expectAnchorError(closePool(), "PoolNotDrained");
+    });
+
+    it("It should closePool once drained", async () => {
+      const userPoolToken = await closedSwap.getAccount(
+        connection,
+        closedSwap.userPoolTokenAccount
+      );
+      const [userAccountA, userAccountB] = await closedSwap.createTokenPair(
+        connection
+      );
+      await program.methods
+        .withdrawAllTokenTypes(
+          new BN(userPoolToken.amount.toString()),
+          new BN(0),
+          new BN(0),
+          null
+        )
+        .accountsPartial({
+          payer: closedSwap.payer.publicKey,
+          tokenSwap: closedSwap.tokenSwap,
+          userTransferAuthority: closedSwap.owner.publicKey,
+          destinationA: userAccountA,
+          destinationB: userAccountB,
+          userPoolTokenSource: closedSwap.userPoolTokenAccount,
+          swapTokenA: closedSwap.swapTokenA,
+          swapTokenB: closedSwap.swapTokenB,
+          tokenAMint: closedSwap.mintA,
+          tokenBMint: closedSwap.mintB,
+          poolMint: closedSwap.poolMint,
+          poolFeeAccount: null,
+          tokenPoolProgram: TOKEN_2022_PROGRAM_ID,
+          tokenAProgram: closedSwap.tokenAProgram,
+          tokenBProgram: closedSwap.tokenBProgram,
+        })
+        .signers([closedSwap.payer, closedSwap.owner])
+        .rpc();
+
+      const signature = await closePool();
+
+      const closed = await connection.getMultipleAccountsInfo(
+        [
+          closedSwap.tokenSwap,
+          closedSwap.swapTokenA,
+          closedSwap.swapTokenB,
+          closedSwap.lockedLiquidity,
+          closedSwap.poolEntry,
+          observations,
+        ],
+        "confirmed"
+      );
+      closed.forEach((account) => assert.isNull(account));
+      const poolMint = await closedSwap.getPoolMint(connection);
+      assert.equal(poolMint.supply, 0n);
+
+      // the registry leaves the closed swap out
+      const page = await program.methods
+        .listPools(poolEntryIndex)
+        .remainingAccounts([
+          { pubkey: closedSwap.poolEntry, isSigner: false, isWritable: false },
+        ])
+        .view();
+      assert.equal(page.entries.length, 0);
+      const poolClosed = await expectEvent(program, signature, "PoolClosed");
+      assert.ok(poolClosed.tokenSwap.equals(closedSwap.tokenSwap));
+      assert.ok(poolClosed.admin.equals(admin));
+    });
+
+    it("It should create the closed swap again", async () => {
+      const closedEntry = closedSwap.poolEntry;
+      await closedSwap.mintToTokenPair(
+        connection,
+        closedSwap.userTokenA,
+        closedSwap.userTokenB,
+        closedSwap.amountOfCurrentSwapToken.a,
+        closedSwap.amountOfCurrentSwapToken.b
+      );
+      await closedSwap.initialize(program, poolFees());
+      assert.notOk(closedSwap.poolEntry.equals(closedEntry));
+      const swap = await program.account.swapV2.fetch(closedSwap.tokenSwap);
+      assert.ok(swap.poolMint.equals(closedSwap.poolMint));
+      const poolMint = await closedSwap.getPoolMint(connection);
+      assert.equal(poolMint.supply, DEFAULT_POOL_TOKEN_AMOUNT);
+    });
+  });
+
+  describe("route swap", () => {
+    // swap of token B of the main swap for a new token, the second hop
+    let nextSwap: TokenSwapTest;
+    let userSource: PublicKey;
+    let userDestination: PublicKey;
+    // token of the second swap which is not token B of the main swap
+    let destinationMint: PublicKey;
+    const ROUTE_AMOUNT_IN = 10000n;
+
+    before(async () => {
+      nextSwap = await TokenSwapTest.init(connection, program.programId, {
+        owner: tokenSwapTest.owner,
+        mints: [{ mint: tokenSwapTest.mintB }, {}],
+      });
+      await nextSwap.initialize(program, poolFees());
+      destinationMint = nextSwap.mintA.equals(tokenSwapTest.mintB)
+        ? nextSwap.mintB
+        : nextSwap.mintA;
+      let userAccountB: PublicKey;
+      [userSource, userAccountB] = await tokenSwapTest.createTokenPair(
+        connection
+      );
+      await tokenSwapTest.mintToTokenPair(
+        connection,
+        userSource,
+        userAccountB,
+        ROUTE_AMOUNT_IN,
+        0n
+      );
+      userDestination = await createAccount(
+        connection,
+        nextSwap.payer,
+        destinationMint,
+        nextSwap.owner.publicKey,
+        Keypair.generate(),
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+    });
+
+    const vault = (test: TokenSwapTest, mint: PublicKey) =>
+      mint.equals(test.mintA) ? test.swapTokenA : test.swapTokenB;
+    // accounts of a hop from `sourceMint` to `hopDestinationMint`
+    const hopAccounts = (
+      test: TokenSwapTest,
+      sourceMint: PublicKey,
+      hopDestinationMint: PublicKey,
+      hostFeeAccount: PublicKey = program.programId
+    ) =>
+      [
+        test.tokenSwap,
+        test.authority,
+        vault(test, sourceMint),
+        vault(test, hopDestinationMint),
+        hopDestinationMint,
+        test.tokenProgram(hopDestinationMint),
+        test.poolMint,
+        test.poolFeeAccount,
+        TOKEN_2022_PROGRAM_ID,
+        hostFeeAccount,
+      ].map((pubkey, i) => ({
+        pubkey,
+        isSigner: false,
+        isWritable: [0, 2, 3, 6, 7, 9].includes(i),
+      }));
+    const quote = (
+      test: TokenSwapTest,
+      sourceMint: PublicKey,
+      hopDestinationMint: PublicKey,
+      amountIn: BN
+    ) =>
+      program.methods
+        .quoteSwap(amountIn)
+        .accountsPartial({
+          tokenSwap: test.tokenSwap,
+          swapSource: vault(test, sourceMint),
+          swapDestination: vault(test, hopDestinationMint),
+          sourceTokenMint: sourceMint,
+          destinationTokenMint: hopDestinationMint,
+        })
+        .view();
+    const routeSwap = (
+      minimumAmountOut: BN,
+      hops: { pubkey: PublicKey; isSigner: boolean; isWritable: boolean }[]
+    ) =>
+      program.methods
+        .routeSwap(new BN(ROUTE_AMOUNT_IN.toString()), minimumAmountOut, 2, null)
+        .accountsPartial({
+          userTransferAuthority: tokenSwapTest.owner.publicKey,
+          userSource,
+          userDestination,
+          sourceTokenMint: tokenSwapTest.mintA,
+          tokenSourceProgram: tokenSwapTest.tokenAProgram,
+        })
+        .remainingAccounts(hops)
+        .signers([tokenSwapTest.owner])
+        .rpc();
+    const route = (hostFeeAccount?: PublicKey) => [
+      ...hopAccounts(
+        tokenSwapTest,
+        tokenSwapTest.mintA,
+        tokenSwapTest.mintB,
+        hostFeeAccount
+      ),
+      ...hopAccounts(nextSwap, tokenSwapTest.mintB, destinationMint),
+    ];
+
+    it("It should reject a route below the minimum amount out", async () => {
+      const first = await quote(
+        tokenSwapTest,
+        tokenSwapTest.mintA,
+        tokenSwapTest.mintB,
+        new BN(ROUTE_AMOUNT_IN.toString())
+      );
+      const second = await quote(
+        nextSwap,
+        tokenSwapTest.mintB,
+        destinationMint,
+        first.amountOut
+      );
+      await expectAnchorError(
+        routeSwap(second.amountOut.addn(1), route()),
+        "ExceededSlippage"
+      );
+    });
+
+    it("It should reject a hop with the account of another swap", async () => {
+      const hops = route();
+      // swap source of the second hop taken from the first swap
+      hops[ACCOUNTS_PER_HOP + 2].pubkey = tokenSwapTest.swapTokenB;
+      await expectAnchorError(
+        routeSwap(new BN(0), hops),
+        "IncorrectSwapAccount"
+      );
+    });
+
+    it("It should routeSwap through two swaps", async () => {
+      const first = await quote(
+        tokenSwapTest,
+        tokenSwapTest.mintA,
+        tokenSwapTest.mintB,
+        new BN(ROUTE_AMOUNT_IN.toString())
+      );
+      const second = await quote(
+        nextSwap,
+        tokenSwapTest.mintB,
+        destinationMint,
+        first.amountOut
+      );
+      const [mainBefore, nextBefore] = await Promise.all([
+        program.account.swapV2.fetch(tokenSwapTest.tokenSwap),
+        program.account.swapV2.fetch(nextSwap.tokenSwap),
+      ]);
+      const hostFeeBefore = await tokenSwapTest.getAccount(
+        connection,
+        tokenSwapTest.userPoolTokenAccount
+      );
+
+      await routeSwap(
+        second.amountOut,
+        route(tokenSwapTest.userPoolTokenAccount)
+      );
+
+      const userSourceInfo = await tokenSwapTest.getAccount(
+        connection,
+        userSource
+      );
+      assert.equal(userSourceInfo.amount, 0n);
+      const userDestinationInfo = await nextSwap.getAccount(
+        connection,
+        userDestination
+      );
+      assert.equal(
+        userDestinationInfo.amount,
+        BigInt(second.amountOut.toString())
+      );
+      // the intermediate token moved between the vaults of the two swaps
+      const [main, next] = await Promise.all([
+        program.account.swapV2.fetch(tokenSwapTest.tokenSwap),
+        program.account.swapV2.fetch(nextSwap.tokenSwap),
+      ]);
+      assert.ok(main.reserveA.eq(mainBefore.reserveA.add(first.amountIn)));
+      assert.ok(main.reserveB.eq(mainBefore.reserveB.sub(first.amountOut)));
+      const nextSourceIsA = nextSwap.mintA.equals(tokenSwapTest.mintB);
+      assert.ok(
+        (nextSourceIsA ? next.reserveA : next.reserveB).eq(
+          (nextSourceIsA ? nextBefore.reserveA : nextBefore.reserveB).add(
+            first.amountOut
+          )
+        )
+      );
+      // the host fee of the first hop went to the host fee account
+      const hostFee = await tokenSwapTest.getAccount(
+        connection,
+        tokenSwapTest.userPoolTokenAccount
+      );
+      assert.isAbove(Number(hostFee.amount), Number(hostFeeBefore.amount));
+      const [swapTokenA, swapTokenB] =
+        await tokenSwapTest.getSwapTokenAccounts(connection);
+      tokenSwapTest.amountOfCurrentSwapToken = {
+        a: swapTokenA.amount,
+        b: swapTokenB.amount,
+      };
+    });
+  });
+
+  describe("transfer fee", () => {
+    // swap whose mints charge a transfer fee of 1%
+    let feeSwap: TokenSwapTest;
+
+    before(async () => {
+      feeSwap = await TokenSwapTest.init(connection, program.programId, {
+        mints: [{ transferFeeBasisPoints: 100 }, { transferFeeBasisPoints: 100 }],
+      });
+      await feeSwap.initialize(program, poolFees());
+    });
+
+    it("It should swap with the swap receiving the source amount swapped", async () => {
+      const [userAccountA, userAccountB] = await feeSwap.createTokenPair(
+        connection
+      );
+      await feeSwap.mintToTokenPair(
+        connection,
+        userAccountA,
+        userAccountB,
+        SWAP_AMOUNT_IN,
+        0n
+      );
+      const quote = await program.methods
+        .quoteSwap(new BN(SWAP_AMOUNT_IN.toString()))
+        .accountsPartial({
+          tokenSwap: feeSwap.tokenSwap,
+          swapSource: feeSwap.swapTokenA,
+          swapDestination: feeSwap.swapTokenB,
+          sourceTokenMint: feeSwap.mintA,
+          destinationTokenMint: feeSwap.mintB,
+        })
+        .view();
+      const before = await program.account.swapV2.fetch(feeSwap.tokenSwap);
+      const [swapTokenABefore] = await feeSwap.getSwapTokenAccounts(connection);
+
+      const signature = await program.methods
+        .swap(new BN(SWAP_AMOUNT_IN.toString()), quote.amountOut, null)
+        .accountsPartial(
+          swapAccounts(feeSwap, feeSwap.mintA, userAccountA, userAccountB)
+        )
+        .signers([feeSwap.owner])
+        .rpc();
+
+      const swap = await program.account.swapV2.fetch(feeSwap.tokenSwap);
+      const [swapTokenA] = await feeSwap.getSwapTokenAccounts(connection);
+      // the reserve grew by the source amount swapped used by the curve, all
+      // of which the swap received after the transfer fee
+      const sourceAmountSwapped = BigInt(
+        swap.reserveA.sub(before.reserveA).toString()
+      );
+      assert.equal(swapTokenA.amount - swapTokenABefore.amount, sourceAmountSwapped);
+      assert.isBelow(
+        Number(sourceAmountSwapped),
+        Number(quote.amountIn.toString())
+      );
+      const userAccountAInfo = await feeSwap.getAccount(connection, userAccountA);
+      assert.equal(
+        userAccountAInfo.amount,
+        SWAP_AMOUNT_IN - BigInt(quote.amountIn.toString())
+      );
+      const userAccountBInfo = await feeSwap.getAccount(connection, userAccountB);
+      assert.equal(userAccountBInfo.amount, BigInt(quote.amountOut.toString()));
+      const swapped = await expectEvent(program, signature, "Swapped");
+      assert.ok(swapped.tokenSwap.equals(feeSwap.tokenSwap));
+      assert.ok(swapped.user.equals(feeSwap.owner.publicKey));
+      assert.ok(swapped.sourceMint.equals(feeSwap.mintA));
+      assert.ok(swapped.amountIn.eq(quote.amountIn));
+      assert.ok(swapped.tradeFee.eq(quote.tradeFee));
+      assert.ok(swapped.reserveA.eq(swap.reserveA));
+      assert.ok(swapped.reserveB.eq(swap.reserveB));
+    });
+
+    it("It should swapExactAmountOut with the user receiving the exact amount out", async () => {
+      const amountOut = 10000n;
+      const [userAccountA, userAccountB] = await feeSwap.createTokenPair(
+        connection
+      );
+      await feeSwap.mintToTokenPair(
+        connection,
+        userAccountA,
+        userAccountB,
+        SWAP_AMOUNT_IN,
+        0n
+      );
+      const swapExactAmountOut = (maximumAmountIn: BN) =>
+        program.methods
+          .swapExactAmountOut(new BN(amountOut.toString()), maximumAmountIn, null)
+          .accountsPartial(
+            swapAccounts(feeSwap, feeSwap.mintA, userAccountA, userAccountB)
+          )
+          .signers([feeSwap.owner])
+          .rpc();
+      await expectAnchorError(swapExactAmountOut(new BN(1)), "ExceededSlippage");
+      const before = await program.account.swapV2.fetch(feeSwap.tokenSwap);
+      const [swapTokenABefore, swapTokenBBefore] =
+        await feeSwap.getSwapTokenAccounts(connection);
+
+      const signature = await swapExactAmountOut(
+        new BN(SWAP_AMOUNT_IN.toString())
+      );
+
+      const swap = await program.account.swapV2.fetch(feeSwap.tokenSwap);
+      const [swapTokenA, swapTokenB] = await feeSwap.getSwapTokenAccounts(
+        connection
+      );
+      const swapped = await expectEvent(program, signature, "Swapped");
+      // the user receives exactly the amount out, the swap sending out the
+      // transfer fee of token B on top of it
+      const userAccountBInfo = await feeSwap.getAccount(connection, userAccountB);
+      assert.equal(userAccountBInfo.amount, amountOut);
+      assert.isAbove(Number(swapped.amountOut.toString()), Number(amountOut));
+      assert.equal(
+        swapTokenBBefore.amount - swapTokenB.amount,
+        BigInt(swapped.amountOut.toString())
+      );
+      assert.ok(before.reserveB.sub(swap.reserveB).eq(swapped.amountOut));
+      // the user pays the source amount swapped plus the transfer fee of
+      // token A, the swap receiving the source amount swapped
+      const userAccountAInfo = await feeSwap.getAccount(connection, userAccountA);
+      assert.equal(
+        userAccountAInfo.amount,
+        SWAP_AMOUNT_IN - BigInt(swapped.amountIn.toString())
+      );
+      const sourceAmountSwapped = BigInt(
+        swap.reserveA.sub(before.reserveA).toString()
+      );
+      assert.equal(swapTokenA.amount - swapTokenABefore.amount, sourceAmountSwapped);
+      assert.isBelow(
+        Number(sourceAmountSwapped),
+        Number(swapped.amountIn.toString())
+      );
+      assert.ok(swapped.reserveA.eq(swap.reserveA));
+      assert.ok(swapped.reserveB.eq(swap.reserveB));
+    });
+  });
+
+  describe("transfer hook", () => {
+    // swap whose mints both have a transfer hook counting their transfers
+    const hookProgram = anchor.workspace
+      .TransferHookCounter as Program<TransferHookCounter>;
+    let hookSwap: TokenSwapTest;
+    let userAccountA: PublicKey;
+    let userAccountB: PublicKey;
+    const amount = 10000n;
+    const poolTokenAmount = 1000000n;
+
+    const hookPda = (seed: string, mint: PublicKey) =>
+      PublicKey.findProgramAddressSync(
+        [Buffer.from(seed), mint.toBuffer()],
+        hookProgram.programId
+      )[0];
+    /**
+     * Remaining accounts of the transfers of a mint: the hook program, the
+     * extra account metas of the mint, then its counter
+     */
+    const hookAccounts = (mint: PublicKey): AccountMeta[] => [
+      { pubkey: hookProgram.programId, isSigner: false, isWritable: false },
+      {
+        pubkey: hookPda("extra-account-metas", mint),
+        isSigner: false,
+        isWritable: false,
+      },
+      { pubkey: hookPda("counter", mint), isSigner: false, isWritable: true },
+    ];
+    // transfers of token A and token B counted by their hooks
+    const transfers = async () =>
+      Promise.all(
+        [hookSwap.mintA, hookSwap.mintB].map(async (mint) =>
+          (
+            await hookProgram.account.counter.fetch(hookPda("counter", mint))
+          ).transfers.toNumber()
+        )
+      );
+
+    before(async () => {
+      hookSwap = await TokenSwapTest.init(connection, program.programId, {
+        mints: [
+          { transferHookProgramId: hookProgram.programId },
+          { transferHookProgramId: hookProgram.programId },
+        ],
+      });
+      for (const mint of [hookSwap.mintA, hookSwap.mintB]) {
+        await hookProgram.methods
+          .initializeExtraAccountMetaList()
+          .accountsPartial({
+            payer: provider.wallet.publicKey,
+            extraAccountMetaList: hookPda("extra-account-metas", mint),
+            mint,
+            counter: hookPda("counter", mint),
+          })
+          .rpc();
+      }
+      await hookSwap.initialize(program, poolFees(), [
+        ...hookAccounts(hookSwap.mintA),
+        ...hookAccounts(hookSwap.mintB),
+      ]);
+      [userAccountA, userAccountB] = await hookSwap.createTokenPair(
+        connection
+      );
+      await hookSwap.mintToTokenPair(
+        connection,
+        userAccountA,
+        userAccountB,
+        100n * amount,
+        100n * amount
+      );
+    });
+
+    it("It should run the hooks of the initial liquidity", async () => {
+      assert.deepEqual(await transfers(), [1, 1]);
+    });
+
+    it("It should reject a swap without the hook accounts", async () => {
+      await expectAnchorError(
+        program.methods
+          .swap(new BN(amount.toString()), new BN(0), null)
+          .accountsPartial(
+            swapAccounts(hookSwap, hookSwap.mintA, userAccountA, userAccountB)
+          )
+          .remainingAccounts(hookAccounts(hookSwap.mintA))
+          .signers([hookSwap.owner])
+          .rpc(),
+        "InvalidTransferHookAccounts"
+      );
+    });
+
+    it("It should swap with the hooks of the source then destination mint", async () => {
+      const [transfersA, transfersB] = await transfers();
+      await program.methods
+        .swap(new BN(amount.toString()), new BN(0), null)
+        .accountsPartial(
+          swapAccounts(hookSwap, hookSwap.mintB, userAccountB, userAccountA)
+        )
+        .remainingAccounts([
+          ...hookAccounts(hookSwap.mintB),
+          ...hookAccounts(hookSwap.mintA),
+        ])
+        .signers([hookSwap.owner])
+        .rpc();
+      assert.deepEqual(await transfers(), [transfersA + 1, transfersB + 1]);
+    });
+
+    it("It should deposit and withdraw both token types with the hooks of both mints", async () => {
+      const [transfersA, transfersB] = await transfers();
+      await program.methods
+        .depositAllTokenTypes(
+          new BN(poolTokenAmount.toString()),
+          new BN((10n * amount).toString()),
+          new BN((10n * amount).toString()),
+          null
+        )
+        .accountsPartial({
+          payer: hookSwap.owner.publicKey,
+          tokenSwap: hookSwap.tokenSwap,
+          userTransferAuthority: hookSwap.owner.publicKey,
+          sourceA: userAccountA,
+          sourceB: userAccountB,
+          tokenA: hookSwap.swapTokenA,
+          tokenB: hookSwap.swapTokenB,
+          tokenAMint: hookSwap.mintA,
+          tokenBMint: hookSwap.mintB,
+          poolMint: hookSwap.poolMint,
+          destination: hookSwap.userPoolTokenAccount,
+          poolFeeAccount: hookSwap.poolFeeAccount,
+          lockedLiquidity: null,
+          tokenPoolProgram: TOKEN_2022_PROGRAM_ID,
+          tokenAProgram: hookSwap.tokenAProgram,
+          tokenBProgram: hookSwap.tokenBProgram,
+          nativeSolAccount: null,
+        })
+        .remainingAccounts([
+          ...hookAccounts(hookSwap.mintA),
+          ...hookAccounts(hookSwap.mintB),
+        ])
+        .signers([hookSwap.owner])
+        .rpc();
+      assert.deepEqual(await transfers(), [transfersA + 1, transfersB + 1]);
+
+      await program.methods
+        .withdrawAllTokenTypes(
+          new BN(poolTokenAmount.toString()),
+          new BN(0),
+          new BN(0),
+          null
+        )
+        .accountsPartial({
+          payer: hookSwap.owner.publicKey,
+          tokenSwap: hookSwap.tokenSwap,
+          userTransferAuthority: hookSwap.owner.publicKey,
+          destinationA: userAccountA,
+          destinationB: userAccountB,
+          userPoolTokenSource: hookSwap.userPoolTokenAccount,
+          swapTokenA: hookSwap.swapTokenA,
+          swapTokenB: hookSwap.swapTokenB,
+          tokenAMint: hookSwap.mintA,
+          tokenBMint: hookSwap.mintB,
+          poolMint: hookSwap.poolMint,
+          poolFeeAccount: null,
+          tokenPoolProgram: TOKEN_2022_PROGRAM_ID,
+          tokenAProgram: hookSwap.tokenAProgram,
+          tokenBProgram: hookSwap.tokenBProgram,
+          nativeSolAccount: null,
+          destinationAAta: null,
+          destinationBAta: null,
+          associatedTokenProgram: null,
+        })
+        .remainingAccounts([
+          ...hookAccounts(hookSwap.mintA),
+          ...hookAccounts(hookSwap.mintB),
+        ])
+        .signers([hookSwap.owner])
+        .rpc();
+      assert.deepEqual(await transfers(), [transfersA + 2, transfersB + 2]);
+    });
+
+    it("It should deposit and withdraw a single token type with the hook of its mint", async () => {
+      const [transfersA, transfersB] = await transfers();
+      await program.methods
+        .depositSingleTokenTypeExactAmountIn(
+          new BN(amount.toString()),
+          new BN(0),
+          null
+        )
+        .accountsPartial({
+          payer: hookSwap.owner.publicKey,
+          tokenSwap: hookSwap.tokenSwap,
+          userTransferAuthority: hookSwap.owner.publicKey,
+          source: userAccountA,
+          sourceTokenMint: hookSwap.mintA,
+          swapTokenA: hookSwap.swapTokenA,
+          swapTokenB: hookSwap.swapTokenB,
+          sourceTokenProgram: hookSwap.tokenAProgram,
+          tokenPoolProgram: TOKEN_2022_PROGRAM_ID,
+          poolMint: hookSwap.poolMint,
+          poolTokenDestination: hookSwap.userPoolTokenAccount,
+          lockedLiquidity: null,
+          nativeSolAccount: null,
+        })
+        .remainingAccounts(hookAccounts(hookSwap.mintA))
+        .signers([hookSwap.owner])
+        .rpc();
+      assert.deepEqual(await transfers(), [transfersA + 1, transfersB]);
+
+      await program.methods
+        .withdrawSingleTokenTypeExactAmountOut(
+          new BN(amount.toString()),
+          new BN(poolTokenAmount.toString()),
+          null
+        )
+        .accountsPartial({
+          payer: hookSwap.owner.publicKey,
+          tokenSwap: hookSwap.tokenSwap,
+          userTransferAuthority: hookSwap.owner.publicKey,
+          poolTokenSource: hookSwap.userPoolTokenAccount,
+          swapTokenA: hookSwap.swapTokenA,
+          swapTokenB: hookSwap.swapTokenB,
+          tokenAMint: hookSwap.mintA,
+          tokenBMint: hookSwap.mintB,
+          poolMint: hookSwap.poolMint,
+          poolFeeAccount: null,
+          userTokenDestination: userAccountB,
+          destinationTokenMint: hookSwap.mintB,
+          destinationTokenProgram: hookSwap.tokenBProgram,
+          tokenPoolProgram: TOKEN_2022_PROGRAM_ID,
+          nativeSolAccount: null,
+          userTokenDestinationAta: null,
+          associatedTokenProgram: null,
+        })
+        .remainingAccounts(hookAccounts(hookSwap.mintB))
+        .signers([hookSwap.owner])
+        .rpc();
+      assert.deepEqual(await transfers(), [transfersA + 1, transfersB + 1]);
+    });
+  });
+
+  describe("native SOL", () => {
+    // swap of wrapped SOL and a Token-2022 mint
+    let solSwap: TokenSwapTest;
+    let tokenMint: PublicKey;
+    // token account of the owner for `tokenMint`
+    let userToken: PublicKey;
+    // temporary wrapped SOL account of the owner
+    let nativeSolAccount: PublicKey;
+    const amountIn = 10000n;
+
+    before(async () => {
+      solSwap = await TokenSwapTest.init(connection, program.programId, {
+        mints: [{ native: true }, {}],
+      });
+      await solSwap.initialize(program, poolFees());
+      tokenMint = solSwap.otherMint(NATIVE_MINT);
+      const solIsA = solSwap.mintA.equals(NATIVE_MINT);
+      const [userAccountA, userAccountB] = await solSwap.createTokenPair(
+        connection
+      );
+      userToken = solIsA ? userAccountB : userAccountA;
+      await solSwap.mintToTokenPair(
+        connection,
+        userAccountA,
+        userAccountB,
+        solIsA ? 0n : amountIn,
+        solIsA ? amountIn : 0n
+      );
+      [nativeSolAccount] = PublicKey.findProgramAddressSync(
+        [Buffer.from("native-sol"), solSwap.owner.publicKey.toBuffer()],
+        program.programId
+      );
+    });
+
+    // lamports of the owner, which only pays for what is wrapped, the
+    // provider paying the transaction fees
+    const ownerLamports = () =>
+      connection.getBalance(solSwap.owner.publicKey, "confirmed");
+
+    const quote = (sourceMint: PublicKey, amount: bigint) =>
+      program.methods
+        .quoteSwap(new BN(amount.toString()))
+        .accountsPartial({
+          tokenSwap: solSwap.tokenSwap,
+          swapSource: solSwap.vault(sourceMint),
+          swapDestination: solSwap.vault(solSwap.otherMint(sourceMint)),
+          sourceTokenMint: sourceMint,
+          destinationTokenMint: solSwap.otherMint(sourceMint),
+        })
+        .view();
+
+    // swap with the temporary wrapped SOL account of the owner, which the
+    // owner funds as the payer
+    const swapSol = (
+      sourceMint: PublicKey,
+      amount: bigint,
+      minimumAmountOut: BN
+    ) => {
+      const fromSol = sourceMint.equals(NATIVE_MINT);
+      return program.methods
+        .swap(new BN(amount.toString()), minimumAmountOut, null)
+        .accountsPartial({
+          ...swapAccounts(
+            solSwap,
+            sourceMint,
+            fromSol ? null : userToken,
+            fromSol ? userToken : null
+          ),
+          nativeSolAccount,
+          payer: solSwap.owner.publicKey,
+          userDestinationAta: null,
+          associatedTokenProgram: null,
+        })
+        .signers([solSwap.owner])
+        .rpc();
+    };
+
+    it("It should swap SOL of the user transfer authority", async () => {
+      const { amountIn: quotedIn, amountOut } = await quote(
+        NATIVE_MINT,
+        amountIn
+      );
+      const lamportsBefore = await ownerLamports();
+      const userTokenBefore = await solSwap.getAccount(connection, userToken);
+
+      await swapSol(NATIVE_MINT, amountIn, amountOut);
+
+      assert.equal(
+        await ownerLamports(),
+        lamportsBefore - quotedIn.toNumber()
+      );
+      const userTokenInfo = await solSwap.getAccount(connection, userToken);
+      assert.equal(
+        userTokenInfo.amount - userTokenBefore.amount,
+        BigInt(amountOut.toString())
+      );
+      // the temporary account is closed back to lamports
+      assert.isNull(await connection.getAccountInfo(nativeSolAccount));
+    });
+
+    it("It should swap into SOL of the user transfer authority", async () => {
+      const { amountIn: quotedIn, amountOut } = await quote(tokenMint, amountIn);
+      const lamportsBefore = await ownerLamports();
+      const userTokenBefore = await solSwap.getAccount(connection, userToken);
+
+      await swapSol(tokenMint, amountIn, amountOut);
+
+      assert.equal(
+        await ownerLamports(),
+        lamportsBefore + amountOut.toNumber()
+      );
+      const userTokenInfo = await solSwap.getAccount(connection, userToken);
+      assert.equal(
+        userTokenBefore.amount - userTokenInfo.amount,
+        BigInt(quotedIn.toString())
+      );
+      assert.isNull(await connection.getAccountInfo(nativeSolAccount));
+    });
+
+    it("It should swap SOL with lamports already sent to the native SOL account", async () => {
+      // lamports sent to the address beforehand, short of the rent
+      const prefunded = 1000000;
+      await sendAndConfirmTransaction(
+        connection,
+        new Transaction().add(
+          SystemProgram.transfer({
+            fromPubkey: solSwap.payer.publicKey,
+            toPubkey: nativeSolAccount,
+            lamports: prefunded,
+          })
+        ),
+        [solSwap.payer]
+      );
+      const { amountIn: quotedIn, amountOut } = await quote(
+        NATIVE_MINT,
+        amountIn
+      );
+      const lamportsBefore = await ownerLamports();
+      const userTokenBefore = await solSwap.getAccount(connection, userToken);
+
+      await swapSol(NATIVE_MINT, amountIn, amountOut);
+
+      // the owner only tops the account up, and gets the prefunded
+      // lamports back when it is closed
+      assert.equal(
+        await ownerLamports(),
+        lamportsBefore - quotedIn.toNumber() + prefunded
+      );
+      const userTokenInfo = await solSwap.getAccount(connection, userToken);
+      assert.equal(
+        userTokenInfo.amount - userTokenBefore.amount,
+        BigInt(amountOut.toString())
+      );
+      assert.isNull(await connection.getAccountInfo(nativeSolAccount));
+    });
+
+    it("It should depositSingleTokenTypeExactAmountIn SOL", async () => {
+      const lamportsBefore = await ownerLamports();
+      const poolTokenBefore = await solSwap.getAccount(
+        connection,
+        solSwap.userPoolTokenAccount
+      );
+      const vaultBefore = await solSwap.getAccount(
+        connection,
+        solSwap.vault(NATIVE_MINT)
+      );
+
+      await program.methods
+        .depositSingleTokenTypeExactAmountIn(
+          new BN(amountIn.toString()),
+          new BN(1),
+          null
+        )
+        .accountsPartial({
+          payer: solSwap.owner.publicKey,
+          tokenSwap: solSwap.tokenSwap,
+          userTransferAuthority: solSwap.owner.publicKey,
+          source: null,
+          sourceTokenMint: NATIVE_MINT,
+          swapTokenA: solSwap.swapTokenA,
+          swapTokenB: solSwap.swapTokenB,
+          sourceTokenProgram: solSwap.tokenProgram(NATIVE_MINT),
+          tokenPoolProgram: TOKEN_2022_PROGRAM_ID,
+          poolMint: solSwap.poolMint,
+          poolTokenDestination: solSwap.userPoolTokenAccount,
+          lockedLiquidity: null,
+          nativeSolAccount,
+        })
+        .signers([solSwap.owner])
+        .rpc();
+
+      assert.equal(await ownerLamports(), lamportsBefore - Number(amountIn));
+      const vault = await solSwap.getAccount(
+        connection,
+        solSwap.vault(NATIVE_MINT)
+      );
+      assert.equal(vault.amount - vaultBefore.amount, amountIn);
+      const poolToken = await solSwap.getAccount(
+        connection,
+        solSwap.userPoolTokenAccount
+      );
+      assert.isAbove(Number(poolToken.amount - poolTokenBefore.amount), 0);
+      assert.isNull(await connection.getAccountInfo(nativeSolAccount));
+    });
+
+    it("It should withdrawAllTokenTypes into SOL", async () => {
+      const poolTokenAmount = TEST_POOL_TOKEN_AMOUNT;
+      const { supply } = await solSwap.getPoolMint(connection);
+      const [swapTokenA, swapTokenB] = await solSwap.getSwapTokenAccounts(
+        connection
+      );
+      const expectedA = (swapTokenA.amount * poolTokenAmount) / supply;
+      const expectedB = (swapTokenB.amount * poolTokenAmount) / supply;
+      const expectedSol = solSwap.mintA.equals(NATIVE_MINT)
+        ? expectedA
+        : expectedB;
+      const lamportsBefore = await ownerLamports();
+      const userTokenBefore = await solSwap.getAccount(connection, userToken);
+
+      await program.methods
+        .withdrawAllTokenTypes(
+          new BN(poolTokenAmount.toString()),
+          new BN(expectedA.toString()),
+          new BN(expectedB.toString()),
+          null
+        )
+        .accountsPartial({
+          payer: solSwap.owner.publicKey,
+          tokenSwap: solSwap.tokenSwap,
+          userTransferAuthority: solSwap.owner.publicKey,
+          destinationA: solSwap.mintA.equals(NATIVE_MINT) ? null : userToken,
+          destinationB: solSwap.mintB.equals(NATIVE_MINT) ? null : userToken,
+          userPoolTokenSource: solSwap.userPoolTokenAccount,
+          swapTokenA: solSwap.swapTokenA,
+          swapTokenB: solSwap.swapTokenB,
+          tokenAMint: solSwap.mintA,
+          tokenBMint: solSwap.mintB,
+          poolMint: solSwap.poolMint,
+          poolFeeAccount: null,
+          tokenPoolProgram: TOKEN_2022_PROGRAM_ID,
+          tokenAProgram: solSwap.tokenAProgram,
+          tokenBProgram: solSwap.tokenBProgram,
+          nativeSolAccount,
+          destinationAAta: null,
+          destinationBAta: null,
+          associatedTokenProgram: null,
+        })
+        .signers([solSwap.owner])
+        .rpc();
+
+      assert.equal(
+        await ownerLamports(),
+        lamportsBefore + Number(expectedSol)
+      );
+      const userTokenInfo = await solSwap.getAccount(connection, userToken);
+      assert.equal(
+        userTokenInfo.amount - userTokenBefore.amount,
+        solSwap.mintA.equals(NATIVE_MINT) ? expectedB : expectedA
+      );
+      assert.isNull(await connection.getAccountInfo(nativeSolAccount));
+    });
+  });
+
+  describe("quotes", () => {
+    // swap whose mints charge a transfer fee of 1%, so that quotes account
+    // for it
+    let quoteSwap: TokenSwapTest;
+    let userAccountA: PublicKey;
+    let userAccountB: PublicKey;
+    const amount = 10000n;
+
+    before(async () => {
+      quoteSwap = await TokenSwapTest.init(connection, program.programId, {
+        mints: [{ transferFeeBasisPoints: 100 }, { transferFeeBasisPoints: 100 }],
+      });
+      await quoteSwap.initialize(program, poolFees());
+      [userAccountA, userAccountB] = await quoteSwap.createTokenPair(
+        connection
+      );
+      await quoteSwap.mintToTokenPair(
+        connection,
+        userAccountA,
+        userAccountB,
+        10n * amount,
+        0n
+      );
+    });
+
+    const tokenAmount = async (account: PublicKey) =>
+      (await quoteSwap.getAccount(connection, account)).amount;
+
+    it("It should swap the amounts of quoteSwap", async () => {
+      const quote = await program.methods
+        .quoteSwap(new BN(amount.toString()))
+        .accountsPartial({
+          tokenSwap: quoteSwap.tokenSwap,
+          swapSource: quoteSwap.swapTokenA,
+          swapDestination: quoteSwap.swapTokenB,
+          sourceTokenMint: quoteSwap.mintA,
+          destinationTokenMint: quoteSwap.mintB,
+        })
+        .view();
+      const [sourceBefore, destinationBefore] = await Promise.all([
+        tokenAmount(userAccountA),
+        tokenAmount(userAccountB),
+      ]);
+
+      await program.methods
+        .swap(new BN(amount.toString()), quote.amountOut, null)
+        .accountsPartial(
+          swapAccounts(quoteSwap, quoteSwap.mintA, userAccountA, userAccountB)
+        )
+        .signers([quoteSwap.owner])
+        .rpc();
+
+      assert.equal(
+        sourceBefore - (await tokenAmount(userAccountA)),
+        BigInt(quote.amountIn.toString())
+      );
+      assert.equal(
+        (await tokenAmount(userAccountB)) - destinationBefore,
+        BigInt(quote.amountOut.toString())
+      );
+    });
+
+    it("It should deposit the amounts of quoteDepositSingle", async () => {
+      const quote = await program.methods
+        .quoteDepositSingle(new BN(amount.toString()))
+        .accountsPartial({
+          tokenSwap: quoteSwap.tokenSwap,
+          poolMint: quoteSwap.poolMint,
+          sourceTokenMint: quoteSwap.mintA,
+        })
+        .view();
+      const poolTokenBefore = await tokenAmount(quoteSwap.userPoolTokenAccount);
+
+      await program.methods
+        .depositSingleTokenTypeExactAmountIn(
+          new BN(amount.toString()),
+          quote.poolTokenAmount,
+          null
+        )
+        .accountsPartial({
+          payer: quoteSwap.owner.publicKey,
+          tokenSwap: quoteSwap.tokenSwap,
+          userTransferAuthority: quoteSwap.owner.publicKey,
+          source: userAccountA,
+          sourceTokenMint: quoteSwap.mintA,
+          swapTokenA: quoteSwap.swapTokenA,
+          swapTokenB: quoteSwap.swapTokenB,
+          sourceTokenProgram: quoteSwap.tokenAProgram,
+          tokenPoolProgram: TOKEN_2022_PROGRAM_ID,
+          poolMint: quoteSwap.poolMint,
+          poolTokenDestination: quoteSwap.userPoolTokenAccount,
+          lockedLiquidity: null,
+          nativeSolAccount: null,
+        })
+        .signers([quoteSwap.owner])
+        .rpc();
+
+      assert.equal(
+        (await tokenAmount(quoteSwap.userPoolTokenAccount)) - poolTokenBefore,
+        BigInt(quote.poolTokenAmount.toString())
+      );
+    });
+
+    it("It should withdraw the amounts of quoteWithdrawSingle", async () => {
+      const quote = await program.methods
+        .quoteWithdrawSingle(new BN(amount.toString()))
+        .accountsPartial({
+          tokenSwap: quoteSwap.tokenSwap,
+          poolMint: quoteSwap.poolMint,
+          poolFeeAccount: quoteSwap.poolFeeAccount,
+          destinationTokenMint: quoteSwap.mintB,
+        })
+        .view();
+      const [poolTokenBefore, poolFeeBefore, destinationBefore] =
+        await Promise.all([
+          tokenAmount(quoteSwap.userPoolTokenAccount),
+          tokenAmount(quoteSwap.poolFeeAccount),
+          tokenAmount(userAccountB),
+        ]);
+
+      await program.methods
+        .withdrawSingleTokenTypeExactAmountOut(
+          new BN(amount.toString()),
+          quote.poolTokenAmount,
+          null
+        )
+        .accountsPartial({
+          payer: quoteSwap.owner.publicKey,
+          tokenSwap: quoteSwap.tokenSwap,
+          userTransferAuthority: quoteSwap.owner.publicKey,
+          poolTokenSource: quoteSwap.userPoolTokenAccount,
+          swapTokenA: quoteSwap.swapTokenA,
+          swapTokenB: quoteSwap.swapTokenB,
+          tokenAMint: quoteSwap.mintA,
+          tokenBMint: quoteSwap.mintB,
+          poolMint: quoteSwap.poolMint,
+          poolFeeAccount: quoteSwap.poolFeeAccount,
+          userTokenDestination: userAccountB,
+          destinationTokenMint: quoteSwap.mintB,
+          destinationTokenProgram: quoteSwap.tokenBProgram,
+          tokenPoolProgram: TOKEN_2022_PROGRAM_ID,
+          nativeSolAccount: null,
+          userTokenDestinationAta: null,
+          associatedTokenProgram: null,
+        })
+        .signers([quoteSwap.owner])
+        .rpc();
+
+      assert.equal(
+        poolTokenBefore - (await tokenAmount(quoteSwap.userPoolTokenAccount)),
+        BigInt(quote.poolTokenAmount.toString())
+      );
+      assert.equal(
+        (await tokenAmount(quoteSwap.poolFeeAccount)) - poolFeeBefore,
+        BigInt(quote.withdrawFee.toString())
+      );
+      assert.equal(
+        (await tokenAmount(userAccountB)) - destinationBefore,
+        BigInt(quote.amountOut.toString())
+      );
+    });
+  });
+
+  describe("curves", () => {
+    // curves going through square roots and fractional powers, whose compute
+    // cost must stay within the default budget of an instruction
+    const COMPUTE_UNIT_LIMIT = 200000;
+    const curveTypes: [string, CurveTypeArg][] = [
+      ["weighted", { weighted: { weightA: new BN(80), weightB: new BN(20) } }],
+      ["stable", { stable: { amp: new BN(100) } }],
+    ];
+    const amount = 10000n;
+
+    for (const [name, curveType] of curveTypes) {
+      describe(name, () => {
+        let curveSwap: TokenSwapTest;
+        let userAccountA: PublicKey;
+        let userAccountB: PublicKey;
+
+        before(async () => {
+          curveSwap = await TokenSwapTest.init(connection, program.programId, {
+            curveType,
+          });
+          await curveSwap.initialize(program, poolFees());
+          [userAccountA, userAccountB] = await curveSwap.createTokenPair(
+            connection
+          );
+          await curveSwap.mintToTokenPair(
+            connection,
+            userAccountA,
+            userAccountB,
+            10n * amount,
+            10n * amount
+          );
+        });
+
+        const tokenAmount = async (account: PublicKey) =>
+          (await curveSwap.getAccount(connection, account)).amount;
+
+        /**
+         * Compute units consumed by the transaction, simulated
+         */
+        const computeUnits = async (tx: Transaction) => {
+          const { value } = await connection.simulateTransaction(tx, [
+            curveSwap.owner,
+          ]);
+          assert.isNull(value.err, value.logs?.join("\n"));
+          console.log(`    ${name}: ${value.unitsConsumed} compute units`);
+          return value.unitsConsumed;
+        };
+
+        it("It should swap the amount of quoteSwap within the compute budget", async () => {
+          const quote = await program.methods
+            .quoteSwap(new BN(amount.toString()))
+            .accountsPartial({
+              tokenSwap: curveSwap.tokenSwap,
+              swapSource: curveSwap.swapTokenA,
+              swapDestination: curveSwap.swapTokenB,
+              sourceTokenMint: curveSwap.mintA,
+              destinationTokenMint: curveSwap.mintB,
+            })
+            .view();
+          assert.isTrue(quote.amountOut.gtn(0));
+          const swap = program.methods
+            .swap(new BN(amount.toString()), quote.amountOut, null)
+            .accountsPartial(
+              swapAccounts(curveSwap, curveSwap.mintA, userAccountA, userAccountB)
+            )
+            .signers([curveSwap.owner]);
+          assert.isBelow(
+            await computeUnits(await swap.transaction()),
+            COMPUTE_UNIT_LIMIT
+          );
+          const destinationBefore = await tokenAmount(userAccountB);
+
+          await swap.rpc();
+
+          assert.equal(
+            (await tokenAmount(userAccountB)) - destinationBefore,
+            BigInt(quote.amountOut.toString())
+          );
+        });
+
+        it("It should deposit and withdraw a single token type within the compute budget", async () => {
+          const deposit = program.methods
+            .depositSingleTokenTypeExactAmountIn(
+              new BN(amount.toString()),
+              new BN(1),
+              null
+            )
+            .accountsPartial({
+              payer: curveSwap.owner.publicKey,
+              tokenSwap: curveSwap.tokenSwap,
+              userTransferAuthority: curveSwap.owner.publicKey,
+              source: userAccountB,
+              sourceTokenMint: curveSwap.mintB,
+              swapTokenA: curveSwap.swapTokenA,
+              swapTokenB: curveSwap.swapTokenB,
+              sourceTokenProgram: curveSwap.tokenBProgram,
+              tokenPoolProgram: TOKEN_2022_PROGRAM_ID,
+              poolMint: curveSwap.poolMint,
+              poolTokenDestination: curveSwap.userPoolTokenAccount,
+              lockedLiquidity: null,
+              nativeSolAccount: null,
+            })
+            .signers([curveSwap.owner]);
+          assert.isBelow(
+            await computeUnits(await deposit.transaction()),
+            COMPUTE_UNIT_LIMIT
+          );
+          const poolTokenBefore = await tokenAmount(
+            curveSwap.userPoolTokenAccount
+          );
+          await deposit.rpc();
+          const poolTokenAmount =
+            (await tokenAmount(curveSwap.userPoolTokenAccount)) -
+            poolTokenBefore;
+          assert.isTrue(poolTokenAmount > 0n);
+
+          // withdraw half of the deposit back, costing less than the pool
+          // tokens minted for it even with the withdraw fee
+          const withdraw = program.methods
+            .withdrawSingleTokenTypeExactAmountOut(
+              new BN((amount / 2n).toString()),
+              new BN(poolTokenAmount.toString()),
+              null
+            )
+            .accountsPartial({
+              payer: curveSwap.owner.publicKey,
+              tokenSwap: curveSwap.tokenSwap,
+              userTransferAuthority: curveSwap.owner.publicKey,
+              poolTokenSource: curveSwap.userPoolTokenAccount,
+              swapTokenA: curveSwap.swapTokenA,
+              swapTokenB: curveSwap.swapTokenB,
+              tokenAMint: curveSwap.mintA,
+              tokenBMint: curveSwap.mintB,
+              poolMint: curveSwap.poolMint,
+              poolFeeAccount: curveSwap.poolFeeAccount,
+              userTokenDestination: userAccountB,
+              destinationTokenMint: curveSwap.mintB,
+              destinationTokenProgram: curveSwap.tokenBProgram,
+              tokenPoolProgram: TOKEN_2022_PROGRAM_ID,
+              nativeSolAccount: null,
+              userTokenDestinationAta: null,
+              associatedTokenProgram: null,
+            })
+            .signers([curveSwap.owner]);
+          assert.isBelow(
+            await computeUnits(await withdraw.transaction()),
+            COMPUTE_UNIT_LIMIT
+          );
+          const destinationBefore = await tokenAmount(userAccountB);
+
+          await withdraw.rpc();
+
+          assert.equal(
+            (await tokenAmount(userAccountB)) - destinationBefore,
+            amount / 2n
+          );
+        });
+      });
+    }
+  });
+
+  describe("pool status", () => {
+    let statusSwap: TokenSwapTest;
+    let userAccountA: PublicKey;
+    let userAccountB: PublicKey;
+    // token amount of each operation, and pool token amount of deposits
+    // and withdrawals of both token types
+    const amount = 1000n;
+    const poolTokenAmount = 1000000n;
+
+    before(async () => {
+      statusSwap = await TokenSwapTest.init(connection, program.programId);
+      await statusSwap.initialize(program, poolFees());
+      [userAccountA, userAccountB] = await statusSwap.createTokenPair(
+        connection
+      );
+      await statusSwap.mintToTokenPair(
+        connection,
+        userAccountA,
+        userAccountB,
+        100n * amount,
+        100n * amount
+      );
+    });
+
+    const setPoolStatus = (
+      status: Parameters<Program<AnchorTokenSwap>["methods"]["setPoolStatus"]>[0]
+    ) =>
+      program.methods
+        .setPoolStatus(status)
+        .accountsPartial({
+          tokenSwap: statusSwap.tokenSwap,
+          admin: provider.wallet.publicKey,
+        })
+        .rpc();
+
+    type Deadline = Parameters<Program<AnchorTokenSwap>["methods"]["swap"]>[2];
+
+    // every user operation on the swap, by the owner of the user accounts
+    const operations: Record<
+      string,
+      (deadline?: Deadline) => Promise<string>
+    > = {
+      swap: (deadline = null) =>
+        program.methods
+          .swap(new BN(amount.toString()), new BN(0), deadline)
+          .accountsPartial(
+            swapAccounts(statusSwap, statusSwap.mintA, userAccountA, userAccountB)
+          )
+          .signers([statusSwap.owner])
+          .rpc(),
+      depositAllTokenTypes: (deadline = null) =>
+        program.methods
+          .depositAllTokenTypes(
+            new BN(poolTokenAmount.toString()),
+            new BN((10n * amount).toString()),
+            new BN((10n * amount).toString()),
+            deadline
+          )
+          .accountsPartial({
+            payer: statusSwap.owner.publicKey,
+            tokenSwap: statusSwap.tokenSwap,
+            userTransferAuthority: statusSwap.owner.publicKey,
+            sourceA: userAccountA,
+            sourceB: userAccountB,
+            tokenA: statusSwap.swapTokenA,
+            tokenB: statusSwap.swapTokenB,
+            tokenAMint: statusSwap.mintA,
+            tokenBMint: statusSwap.mintB,
+            poolMint: statusSwap.poolMint,
+            destination: statusSwap.userPoolTokenAccount,
+            poolFeeAccount: statusSwap.poolFeeAccount,
+            lockedLiquidity: null,
+            tokenPoolProgram: TOKEN_2022_PROGRAM_ID,
+            tokenAProgram: statusSwap.tokenAProgram,
+            tokenBProgram: statusSwap.tokenBProgram,
+            nativeSolAccount: null,
+          })
+          .signers([statusSwap.owner])
+          .rpc(),
+      depositSingleTokenTypeExactAmountIn: (deadline = null) =>
+        program.methods
+          .depositSingleTokenTypeExactAmountIn(
+            new BN(amount.toString()),
+            new BN(0),
+            deadline
+          )
+          .accountsPartial({
+            payer: statusSwap.owner.publicKey,
+            tokenSwap: statusSwap.tokenSwap,
+            userTransferAuthority: statusSwap.owner.publicKey,
+            source: userAccountA,
+            sourceTokenMint: statusSwap.mintA,
+            swapTokenA: statusSwap.swapTokenA,
+            swapTokenB: statusSwap.swapTokenB,
+            sourceTokenProgram: statusSwap.tokenAProgram,
+            tokenPoolProgram: TOKEN_2022_PROGRAM_ID,
+            poolMint: statusSwap.poolMint,
+            poolTokenDestination: statusSwap.userPoolTokenAccount,
+            lockedLiquidity: null,
+            nativeSolAccount: null,
+          })
+          .signers([statusSwap.owner])
+          .rpc(),
+      withdrawAllTokenTypes: (deadline = null) =>
+        program.methods
+          .withdrawAllTokenTypes(
+            new BN(poolTokenAmount.toString()),
+            new BN(0),
+            new BN(0),
+            deadline
+          )
+          .accountsPartial({
+            payer: statusSwap.owner.publicKey,
+            tokenSwap: statusSwap.tokenSwap,
+            userTransferAuthority: statusSwap.owner.publicKey,
+            destinationA: userAccountA,
+            destinationB: userAccountB,
+            userPoolTokenSource: statusSwap.userPoolTokenAccount,
+            swapTokenA: statusSwap.swapTokenA,
+            swapTokenB: statusSwap.swapTokenB,
+            tokenAMint: statusSwap.mintA,
+            tokenBMint: statusSwap.mintB,
+            poolMint: statusSwap.poolMint,
+            poolFeeAccount: null,
+            tokenPoolProgram: TOKEN_2022_PROGRAM_ID,
+            tokenAProgram: statusSwap.tokenAProgram,
+            tokenBProgram: statusSwap.tokenBProgram,
+            nativeSolAccount: null,
+            destinationAAta: null,
+            destinationBAta: null,
+            associatedTokenProgram: null,
+          })
+          .signers([statusSwap.owner])
+          .rpc(),
+      withdrawSingleTokenTypeExactAmountOut: (deadline = null) =>
+        program.methods
+          .withdrawSingleTokenTypeExactAmountOut(
+            new BN(amount.toString()),
+            new BN(poolTokenAmount.toString()),
+            deadline
+          )
+          .accountsPartial({
+            payer: statusSwap.owner.publicKey,
+            tokenSwap: statusSwap.tokenSwap,
+            userTransferAuthority: statusSwap.owner.publicKey,
+            poolTokenSource: statusSwap.userPoolTokenAccount,
+            swapTokenA: statusSwap.swapTokenA,
+            swapTokenB: statusSwap.swapTokenB,
+            tokenAMint: statusSwap.mintA,
+            tokenBMint: statusSwap.mintB,
+            poolMint: statusSwap.poolMint,
+            poolFeeAccount: null,
+            userTokenDestination: userAccountB,
+            destinationTokenMint: statusSwap.mintB,
+            destinationTokenProgram: statusSwap.tokenBProgram,
+            tokenPoolProgram: TOKEN_2022_PROGRAM_ID,
+            nativeSolAccount: null,
+            userTokenDestinationAta: null,
+            associatedTokenProgram: null,
+          })
+          .signers([statusSwap.owner])
+          .rpc(),
+    };
+
+    /**
+     * Run every operation, asserting that exactly the allowed ones succeed
+     * and all others fail with `OperationPaused`
+     */
+    const expectAllowed = async (allowed: string[]) => {
+      for (const [name, operation] of Object.entries(operations)) {
+        if (allowed.includes(name)) {
+          await operation();
+        } else {
+          await expectAnchorError(operation(), "OperationPaused");
+        }
+      }
+    };
+
+    it("It should reject setPoolStatus from a non-admin", async () => {
+      await expectAnchorError(
+        program.methods
+          .setPoolStatus({ withdrawOnly: {} })
+          .accountsPartial({
+            tokenSwap: statusSwap.tokenSwap,
+            admin: statusSwap.owner.publicKey,
+          })
+          .signers([statusSwap.owner])
+          .rpc(),
+        "InvalidAdmin"
+      );
+    });
+
+    it("It should only block swaps and single-sided operations when swaps are paused", async () => {
+      const signature = await setPoolStatus({ swapsPaused: {} });
+      const { status } = await program.account.swapV2.fetch(
+        statusSwap.tokenSwap
+      );
+      assert.deepEqual(status, { swapsPaused: {} });
+      const statusUpdated = await expectEvent(
+        program,
+        signature,
+        "PoolStatusUpdated"
+      );
+      assert.ok(statusUpdated.tokenSwap.equals(statusSwap.tokenSwap));
+      assert.deepEqual(statusUpdated.oldStatus, { active: {} });
+      assert.deepEqual(statusUpdated.newStatus, { swapsPaused: {} });
+      await expectAllowed(["depositAllTokenTypes", "withdrawAllTokenTypes"]);
+    });
+
+    it("It should only block deposits when deposits are paused", async () => {
+      await setPoolStatus({ depositsPaused: {} });
+      await expectAllowed([
+        "swap",
+        "withdrawAllTokenTypes",
+        "withdrawSingleTokenTypeExactAmountOut",
+      ]);
+    });
+
+    it("It should only allow withdrawals of both token types when withdraw only", async () => {
+      await setPoolStatus({ withdrawOnly: {} });
+      await expectAllowed(["withdrawAllTokenTypes"]);
+    });
+
+    it("It should allow every operation once active again", async () => {
+      await setPoolStatus({ active: {} });
+      await expectAllowed(Object.keys(operations));
+    });
+
+    it("It should reject every operation past its deadline", async () => {
+      const slot = await connection.getSlot();
+      const timestamp = Math.floor(Date.now() / 1000);
+      const expired: Deadline[] = [
+        { unixTimestamp: [new BN(timestamp - 3600)] },
+        { slot: [new BN(slot - 1)] },
+      ];
+      for (const deadline of expired) {
+        for (const operation of Object.values(operations)) {
+          await expectAnchorError(operation(deadline), "Expired");
+        }
+      }
+      // deadlines still ahead let every operation through
+      for (const operation of Object.values(operations)) {
+        await operation({ unixTimestamp: [new BN(timestamp + 3600)] });
+      }
+      for (const operation of Object.values(operations)) {
+        await operation({ slot: [new BN(slot + 1000)] });
+      }
+    });
+  });
+
+  describe("sync and skim", () => {
+    let donatedSwap: TokenSwapTest;
+    // tokens sent to the swap token accounts outside of the program
+    const donation = 1000n;
+
+    before(async () => {
+      donatedSwap = await TokenSwapTest.init(connection, program.programId);
+      await donatedSwap.initialize(program, poolFees());
+    });
+
+    const donate = () =>
+      donatedSwap.mintToTokenPair(
+        connection,
+        donatedSwap.swapTokenA,
+        donatedSwap.swapTokenB,
+        donation,
+        0n
+      );
+
+    it("It should skim the tokens above the reserves", async () => {
+      await donate();
+      const [destinationA, destinationB] = await donatedSwap.createTokenPair(
+        connection
+      );
+
+      const signature = await program.methods
+        .skim()
+        .accountsPartial({
+          tokenSwap: donatedSwap.tokenSwap,
+          authority: donatedSwap.authority,
+          swapTokenA: donatedSwap.swapTokenA,
+          swapTokenB: donatedSwap.swapTokenB,
+          tokenAMint: donatedSwap.mintA,
+          tokenBMint: donatedSwap.mintB,
+          destinationA,
+          destinationB,
+          tokenAProgram: donatedSwap.tokenAProgram,
+          tokenBProgram: donatedSwap.tokenBProgram,
+        })
+        .rpc();
+
+      const destinationAInfo = await donatedSwap.getAccount(
+        connection,
+        destinationA
+      );
+      assert.equal(destinationAInfo.amount, donation);
+      const skimmed = await expectEvent(program, signature, "Skimmed");
+      assert.ok(skimmed.tokenSwap.equals(donatedSwap.tokenSwap));
+      assert.equal(skimmed.tokenAAmount.toString(), donation.toString());
+      assert.equal(skimmed.tokenBAmount.toString(), "0");
+    });
+
+    it("It should sync the reserves to the balances", async () => {
+      await donate();
+      const before = await program.account.swapV2.fetch(donatedSwap.tokenSwap);
+
+      const signature = await program.methods
+        .sync()
+        .accountsPartial({
+          tokenSwap: donatedSwap.tokenSwap,
+          swapTokenA: donatedSwap.swapTokenA,
+          swapTokenB: donatedSwap.swapTokenB,
+        })
+        .rpc();
+
+      const swap = await program.account.swapV2.fetch(donatedSwap.tokenSwap);
+      assert.ok(
+        swap.reserveA.eq(before.reserveA.add(new BN(donation.toString())))
+      );
+      const synced = await expectEvent(program, signature, "ReservesSynced");
+      assert.ok(synced.tokenSwap.equals(donatedSwap.tokenSwap));
+      assert.ok(synced.reserveA.eq(swap.reserveA));
+      assert.ok(synced.reserveB.eq(swap.reserveB));
+    });
+  });
+
+  describe("price observations", () => {
+    // Number of observations kept by the ring buffer
+    const OBSERVATIONS_LEN = 64;
+    let observedSwap: TokenSwapTest;
+    let observations: PublicKey;
+
+    before(async () => {
+      observedSwap = await TokenSwapTest.init(connection, program.programId);
+      await observedSwap.initialize(program, poolFees());
+      [observations] = PublicKey.findProgramAddressSync(
+        [Buffer.from("observations"), observedSwap.tokenSwap.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .initializeObservations()
+        .accountsPartial({ tokenSwap: observedSwap.tokenSwap, observations })
+        .rpc();
+    });
+
+    const recordObservation = () =>
+      program.methods
+        .recordObservation()
+        .accountsPartial({ tokenSwap: observedSwap.tokenSwap, observations });
+
+    // observations are recorded at most once per second of the cluster clock
+    const waitForNextSecond = async () => {
+      const { observations: recorded, index } =
+        await program.account.observations.fetch(observations);
+      const latest = recorded[index].timestamp.toNumber();
+      const now = async () =>
+        connection.getBlockTime(await connection.getSlot());
+      while ((await now()) <= latest) {
+        await new Promise((resolve) => setTimeout(resolve, 200));
+      }
+    };
+
+    it("It should start the ring buffer with the prices of the swap", async () => {
+      const account = await program.account.observations.fetch(observations);
+      const swap = await program.account.swapV2.fetch(observedSwap.tokenSwap);
+      assert.ok(account.tokenSwap.equals(observedSwap.tokenSwap));
+      assert.equal(account.index, 0);
+      assert.equal(account.observations.length, OBSERVATIONS_LEN);
+      assert.ok(
+        account.observations[0].timestamp.eq(
+          swap.cumulativePrices.lastUpdateTimestamp
+        )
+      );
+    });
+
+    it("It should reject a second observation within the same second", async () => {
+      await waitForNextSecond();
+      // both instructions of a transaction see the same clock
+      await expectAnchorError(
+        recordObservation()
+          .preInstructions([await recordObservation().instruction()])
+          .rpc(),
+        "ObservationTooRecent"
+      );
+    });
+
+    it("It should record observations until the ring buffer wraps around", async () => {
+      const first = (await program.account.observations.fetch(observations))
+        .observations[0];
+
+      for (let i = 1; i <= OBSERVATIONS_LEN; i++) {
+        await waitForNextSecond();
+        await recordObservation().rpc();
+        const account = await program.account.observations.fetch(observations);
+        assert.equal(account.index, i % OBSERVATIONS_LEN);
+      }
+
+      // every slot holds its own observation, the first one being overwritten
+      // by the latest
+      const account = await program.account.observations.fetch(observations);
+      const timestamps = account.observations.map(({ timestamp }) =>
+        timestamp.toNumber()
+      );
+      for (let i = 2; i < OBSERVATIONS_LEN; i++) {
+        assert.isAbove(timestamps[i], timestamps[i - 1]);
+      }
+      assert.isAbove(timestamps[0], timestamps[OBSERVATIONS_LEN - 1]);
+      assert.isAbove(timestamps[1], first.timestamp.toNumber());
+      const swap = await program.account.swapV2.fetch(observedSwap.tokenSwap);
+      assert.ok(
+        account.observations[0].priceACumulative.eq(
+          swap.cumulativePrices.priceACumulative
+        )
+      );
+    });
+  });
+
+  describe("config", () => {
+    const constraints = () => ({
+      ownerKey: null,
+      validCurveTypes: [{ constantProduct: {} }],
+      fees: poolFees(),
+    });
+    // pool created within the constraints
+    let constrainedSwap: TokenSwapTest;
+
+    const updateConfig = (
+      newConstraints: ReturnType<typeof constraints> | null,
+      signer?: Keypair
+    ) =>
+      program.methods
+        .updateConfig(newConstraints)
+        .accountsPartial({
+          config,
+          upgradeAuthority: signer?.publicKey ?? provider.wallet.publicKey,
+          swapProgram: program.programId,
+          programData,
+        })
+        .signers(signer ? [signer] : [])
+        .rpc();
+
+    after(async () => {
+      // the other tests create pools without constraints
+      await updateConfig(null);
+    });
+
+    it("It should reject updateConfig from a non-upgrade authority", async () => {
+      const notAuthority = Keypair.generate();
+      await expectAnchorError(
+        updateConfig(constraints(), notAuthority),
+        "InvalidUpgradeAuthority"
+      );
+      const account = await program.account.swapConfig.fetch(config);
+      assert.isNull(account.constraints);
+    });
+
+    it("It should updateConfig by the upgrade authority", async () => {
+      const signature = await updateConfig(constraints());
+      const account = await program.account.swapConfig.fetch(config);
+      assert.isNotNull(account.constraints);
+      assert.deepEqual(account.constraints.validCurveTypes, [
+        { constantProduct: {} },
+      ]);
+      assert.ok(
+        account.constraints.fees.tradeFeeNumerator.eq(
+          new BN(TRADING_FEE_NUMERATOR)
+        )
+      );
+      const configUpdated = await expectEvent(
+        program,
+        signature,
+        "ConfigUpdated"
+      );
+      assert.isNotNull(configUpdated.constraints);
+    });
+
+    it("It should reject initialize with a curve type outside the constraints", async () => {
+      const stableSwap = await TokenSwapTest.init(
+        connection,
+        program.programId,
+        { curveType: { stable: { amp: new BN(100) } } }
+      );
+      await expectAnchorError(
+        stableSwap.initialize(program, poolFees()),
+        "UnsupportedCurveType"
+      );
+    });
+
+    it("It should reject initialize with fees below the constraints", async () => {
+      const cheapSwap = await TokenSwapTest.init(connection, program.programId);
+      await expectAnchorError(
+        cheapSwap.initialize(program, {
+          ...poolFees(),
+          tradeFeeNumerator: new BN(TRADING_FEE_NUMERATOR - 1),
+        }),
+        "InvalidFee"
+      );
+    });
+
+    it("It should initialize within the constraints", async () => {
+      constrainedSwap = await TokenSwapTest.init(connection, program.programId);
+      await constrainedSwap.initialize(program, {
+        ...poolFees(),
+        tradeFeeNumerator: new BN(TRADING_FEE_NUMERATOR + 5),
+      });
+      const swap = await program.account.swapV2.fetch(
+        constrainedSwap.tokenSwap
+      );
+      assert.ok(
+        swap.fees.tradeFeeNumerator.eq(new BN(TRADING_FEE_NUMERATOR + 5))
+      );
+    });
+
+    it("It should reject updateFees below the constraints", async () => {
+      await expectAnchorError(
+        program.methods
+          .updateFees({
+            ...poolFees(),
+            ownerTradeFeeNumerator: new BN(OWNER_TRADING_FEE_NUMERATOR - 1),
+          })
+          .accountsPartial({
+            tokenSwap: constrainedSwap.tokenSwap,
+            admin: provider.wallet.publicKey,
+            config,
+            poolFeeAccount: constrainedSwap.poolFeeAccount,
+          })
+          .rpc(),
+        "InvalidFee"
+      );
+      const swap = await program.account.swapV2.fetch(
+        constrainedSwap.tokenSwap
+      );
+      assert.ok(
+        swap.fees.ownerTradeFeeNumerator.eq(
+          new BN(OWNER_TRADING_FEE_NUMERATOR)
+        )
+      );
+    });
+  });
 });
@@ -1,26 +1,76 @@
 import * as anchor from "@coral-xyz/anchor";
+import { BN, Program } from "@coral-xyz/anchor";
 import {
   approve,
   createAccount,
+  createInitializeMintInstruction,
+  createInitializeTransferFeeConfigInstruction,
+  createInitializeTransferHookInstruction,
   createMint,
-  getAccount,
+  createSyncNativeInstruction,
+  ExtensionType,
+  getAssociatedTokenAddressSync,
   getMint,
+  getMintLen,
   Mint,
   mintTo,
+  NATIVE_MINT,
   Account as TokenAccount,
+  TOKEN_PROGRAM_ID,
+  unpackAccount,
 } from "@solana/spl-token";
 import {
+  AccountMeta,
   PublicKey,
   LAMPORTS_PER_SOL,
   Keypair,
   Connection,
+  sendAndConfirmTransaction,
+  SystemProgram,
+  Transaction,
 } from "@solana/web3.js";
 import { TOKEN_2022_PROGRAM_ID } from "@solana/spl-token";
+import { assert } from "chai";
+import { AnchorTokenSwap } from "../target/types/anchor_token_swap";
 
 // Initial amount in each swap token
 let amountOfCurrentSwapTokenA = 1000000n;
 let amountOfCurrentSwapTokenB = 1000000n;
 
+// Trade fee of the swap in basis points, part of its address
+export const FEE_TIER = 25;
+// Remaining accounts of each hop of `routeSwap`, before the transfer hook
+// accounts of its destination mint
+export const ACCOUNTS_PER_HOP = 10;
+
+// Curve type argument of `initialize`, e.g. `{ constantProduct: {} }`
+export type CurveTypeArg = Parameters<
+  Program<AnchorTokenSwap>["methods"]["initialize"]
+>[0];
+// Fees argument of `initialize`
+export type FeesArg = Parameters<
+  Program<AnchorTokenSwap>["methods"]["initialize"]
+>[1];
+
+// Variants of the curve type, in the order of their borsh discriminant
+const CURVE_TYPES = [
+  "constantProduct",
+  "constantPrice",
+  "offset",
+  "stable",
+  "weighted",
+];
+
+/**
+ * Seed of a curve type in the swap address, the discriminant of the curve
+ * regardless of its parameters
+ * @param curveType Curve type
+ */
+export function curveTypeSeed(curveType: CurveTypeArg): Buffer {
+  const [variant] = Object.keys(curveType);
+  return Buffer.from([CURVE_TYPES.indexOf(variant)]);
+}
+
 /**
  * Airdrop and confirm the transaction completes
  * @param key The public key to airdrop to, underneath is can create an account on the pubkey is doesn't exist
@@ -39,8 +89,228 @@ export async function airdrop_and_confirm(
   });
 }
 
+/**
+ * Assert that the promise is rejected with the given error of the program
+ * @param promise Sent transaction, from `rpc()` or `sendAndConfirmTransaction`
+ * @param code Name of the error, e.g. "InvalidAdmin"
+ */
+export async function expectAnchorError(
+  promise: Promise<unknown>,
+  code: string
+) {
+  let error: any;
+  try {
+    await promise;
+  } catch (e) {
+    error = e;
+  }
+  assert.ok(error, `expected the transaction to fail with ${code}`);
+  const anchorError =
+    error instanceof anchor.AnchorError
+      ? error
+      : anchor.AnchorError.parse(error.logs ?? error.transactionLogs ?? []);
+  assert.ok(anchorError, `expected ${code}, got ${error}`);
+  assert.equal(anchorError.error.errorCode.code, code);
+}
+
+/**
+ * Assert that the promise is rejected with a log containing the given text,
+ * for errors raised outside of the program, e.g. by the token program
+ * @param promise Sent transaction
+ * @param log Part of a log of the failed transaction
+ */
+export async function expectTransactionError(
+  promise: Promise<unknown>,
+  log: string
+) {
+  let error: any;
+  try {
+    await promise;
+  } catch (e) {
+    error = e;
+  }
+  assert.ok(error, `expected the transaction to fail with "${log}"`);
+  const logs: string[] = error.logs ?? error.transactionLogs ?? [];
+  assert.ok(
+    logs.some((line) => line.includes(log)),
+    `expected "${log}" in ${logs.join("\n")}`
+  );
+}
+
+// First bytes of the data of the instruction by which the program emits an
+// event to itself with the `event-cpi` feature
+const EVENT_IX_TAG = Buffer.from("e445a52e51cb9a1d", "hex");
+
+/**
+ * Events emitted by the program in a transaction, read from its logs with
+ * `emit!`, or from the instructions of the program to itself with the
+ * `event-cpi` feature
+ * @param program
+ * @param signature Signature of the transaction
+ */
+export async function emittedEvents(
+  program: Program<AnchorTokenSwap>,
+  signature: string
+): Promise<anchor.Event[]> {
+  const connection = program.provider.connection;
+  await connection.confirmTransaction(
+    { signature, ...(await connection.getLatestBlockhash()) },
+    "confirmed"
+  );
+  const tx = await connection.getTransaction(signature, {
+    commitment: "confirmed",
+    maxSupportedTransactionVersion: 0,
+  });
+  assert.ok(tx, `transaction ${signature} not found`);
+  const parser = new anchor.EventParser(program.programId, program.coder);
+  const events = [...parser.parseLogs(tx.meta.logMessages ?? [])];
+  const accountKeys = tx.transaction.message.getAccountKeys({
+    accountKeysFromLookups: tx.meta.loadedAddresses,
+  });
+  for (const { instructions } of tx.meta.innerInstructions ?? []) {
+    for (const instruction of instructions) {
+      const data = Buffer.from(anchor.utils.bytes.bs58.decode(instruction.data));
+      if (
+        accountKeys.get(instruction.programIdIndex)?.equals(program.programId) &&
+        data.subarray(0, 8).equals(EVENT_IX_TAG)
+      ) {
+        const event = program.coder.events.decode(
+          data.subarray(8).toString("base64")
+        );
+        if (event) {
+          events.push(event);
+        }
+      }
+    }
+  }
+  return events;
+}
+
+/**
+ * Data of the first event of the given type emitted in a transaction
+ * @param program
+ * @param signature Signature of the transaction
+ * @param name Name of the event, e.g. "Swapped"
+ */
+export async function expectEvent(
+  program: Program<AnchorTokenSwap>,
+  signature: string,
+  name: string
+): Promise<any> {
+  const events = await emittedEvents(program, signature);
+  const event = events.find(
+    (event) => event.name.toLowerCase() === name.toLowerCase()
+  );
+  assert.ok(
+    event,
+    `expected a ${name} event, got ${events.map((event) => event.name)}`
+  );
+  return event.data;
+}
+
+// Options of a mint of a swap
+export interface MintOptions {
+  // Token-2022 transfer fee of the mint, in basis points
+  transferFeeBasisPoints?: number;
+  // Program of the Token-2022 transfer hook of the mint
+  transferHookProgramId?: PublicKey;
+  // Wrapped SOL of the token program instead of a new mint
+  native?: boolean;
+  // Existing Token-2022 mint, whose authority is the owner of the swap
+  mint?: PublicKey;
+}
+
+// Options of a swap, all defaulting to the swap of the main tests
+export interface SwapOptions {
+  mints?: [MintOptions, MintOptions];
+  curveType?: CurveTypeArg;
+  feeTier?: number;
+  // Owner of the user accounts, and authority of the mints it creates
+  owner?: Keypair;
+}
+
+/**
+ * Create a Token-2022 mint, with a transfer fee and a transfer hook if
+ * requested
+ */
+async function createTestMint(
+  connection: Connection,
+  payer: Keypair,
+  mintAuthority: PublicKey,
+  options: MintOptions
+): Promise<PublicKey> {
+  if (options.native) {
+    return NATIVE_MINT;
+  }
+  if (options.mint) {
+    return options.mint;
+  }
+  const mint = Keypair.generate();
+  const extensions = [
+    ...(options.transferFeeBasisPoints === undefined
+      ? []
+      : [ExtensionType.TransferFeeConfig]),
+    ...(options.transferHookProgramId ? [ExtensionType.TransferHook] : []),
+  ];
+  if (extensions.length === 0) {
+    return createMint(
+      connection,
+      payer,
+      mintAuthority,
+      null,
+      2,
+      mint,
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+  }
+  const space = getMintLen(extensions);
+  const tx = new Transaction().add(
+    SystemProgram.createAccount({
+      fromPubkey: payer.publicKey,
+      newAccountPubkey: mint.publicKey,
+      space,
+      lamports: await connection.getMinimumBalanceForRentExemption(space),
+      programId: TOKEN_2022_PROGRAM_ID,
+    })
+  );
+  if (options.transferFeeBasisPoints !== undefined) {
+    tx.add(
+      createInitializeTransferFeeConfigInstruction(
+        mint.publicKey,
+        mintAuthority,
+        mintAuthority,
+        options.transferFeeBasisPoints,
+        BigInt(Number.MAX_SAFE_INTEGER),
+        TOKEN_2022_PROGRAM_ID
+      )
+    );
+  }
+  if (options.transferHookProgramId) {
+    tx.add(
+      createInitializeTransferHookInstruction(
+        mint.publicKey,
+        mintAuthority,
+        options.transferHookProgramId,
+        TOKEN_2022_PROGRAM_ID
+      )
+    );
+  }
+  tx.add(
+    createInitializeMintInstruction(
+      mint.publicKey,
+      2,
+      mintAuthority,
+      null,
+      TOKEN_2022_PROGRAM_ID
+    )
+  );
+  await sendAndConfirmTransaction(connection, tx, [payer, mint]);
+  return mint.publicKey;
+}
+
 export class TokenSwapTest {
-  tokenSwapAccount: Keypair;
+  tokenSwap: PublicKey;
   authority: PublicKey;
   authorityBumpSeed: number;
   provider: anchor.AnchorProvider;
@@ -50,18 +320,37 @@ export class TokenSwapTest {
   swapTokenB: PublicKey;
   mintA: PublicKey;
   mintB: PublicKey;
+  // programs owning mintA and mintB
+  tokenAProgram: PublicKey;
+  tokenBProgram: PublicKey;
+  curveType: CurveTypeArg;
+  feeTier: number;
   // payer for transactions
   payer: Keypair;
   poolMint: PublicKey;
+  // holds the minimum liquidity locked on init
+  lockedLiquidity: PublicKey;
+  // registry entry written by `initialize`
+  poolEntry: PublicKey;
+  // funding the initial liquidity of the swap
+  userTokenA: PublicKey;
+  userTokenB: PublicKey;
   //only use for receiver of the pool token during init
   userPoolTokenAccount: PublicKey;
+  poolFeeOwner: PublicKey;
   poolFeeAccount: PublicKey;
   amountOfCurrentSwapToken: { a: bigint; b: bigint };
   constructor() {}
-  public static async init(connection: Connection, programId: PublicKey) {
+  public static async init(
+    connection: Connection,
+    programId: PublicKey,
+    options: SwapOptions = {}
+  ) {
     let test = new TokenSwapTest();
-    test.owner = Keypair.generate();
+    test.owner = options.owner ?? Keypair.generate();
     test.payer = Keypair.generate();
+    test.curveType = options.curveType ?? { constantProduct: {} };
+    test.feeTier = options.feeTier ?? FEE_TIER;
     test.amountOfCurrentSwapToken = {
       a: amountOfCurrentSwapTokenA,
       b: amountOfCurrentSwapTokenB,
@@ -76,127 +365,199 @@ export class TokenSwapTest {
       ),
     ]);
 
-    test.tokenSwapAccount = Keypair.generate();
-    [test.authority, test.authorityBumpSeed] = PublicKey.findProgramAddressSync(
-      [test.tokenSwapAccount.publicKey.toBuffer()],
-      programId
-    );
-
-    // Batch 1: Create all mints
-    const [poolMint, mintA, mintB] = await Promise.all([
-      createMint(
-        connection,
-        test.payer,
-        test.authority,
-        null,
-        2,
-        Keypair.generate(),
-        undefined,
-        TOKEN_2022_PROGRAM_ID
-      ),
-      createMint(
+    // Batch 1: Create the token mints, sorted as the swap expects them
+    const [mintOptionsA, mintOptionsB] = options.mints ?? [{}, {}];
+    let [mintA, mintB] = await Promise.all([
+      createTestMint(
         connection,
         test.payer,
         test.owner.publicKey,
-        null,
-        2,
-        Keypair.generate(),
-        undefined,
-        TOKEN_2022_PROGRAM_ID
+        mintOptionsA
       ),
-      createMint(
+      createTestMint(
         connection,
         test.payer,
         test.owner.publicKey,
-        null,
-        2,
-        Keypair.generate(),
-        undefined,
-        TOKEN_2022_PROGRAM_ID
+        mintOptionsB
       ),
     ]);
-    test.poolMint = poolMint;
+    if (Buffer.compare(mintA.toBuffer(), mintB.toBuffer()) > 0) {
+      [mintA, mintB] = [mintB, mintA];
+    }
     test.mintA = mintA;
     test.mintB = mintB;
+    test.tokenAProgram = mintA.equals(NATIVE_MINT)
+      ? TOKEN_PROGRAM_ID
+      : TOKEN_2022_PROGRAM_ID;
+    test.tokenBProgram = mintB.equals(NATIVE_MINT)
+      ? TOKEN_PROGRAM_ID
+      : TOKEN_2022_PROGRAM_ID;
 
-    // Batch 2: Create all accounts
-    const [userPoolTokenReciever, poolFeeAccount, swapTokenA, swapTokenB] =
-      await Promise.all([
-        createAccount(
-          connection,
-          test.payer,
-          test.poolMint,
-          test.owner.publicKey,
-          Keypair.generate(),
-          undefined,
-          TOKEN_2022_PROGRAM_ID
-        ),
-        createAccount(
-          connection,
-          test.payer,
-          test.poolMint,
-          test.owner.publicKey,
-          Keypair.generate(),
-          undefined,
-          TOKEN_2022_PROGRAM_ID
-        ),
-        createAccount(
-          connection,
-          test.payer,
-          test.mintA,
-          test.authority,
-          Keypair.generate(),
-          undefined,
-          TOKEN_2022_PROGRAM_ID
-        ),
-        createAccount(
-          connection,
-          test.payer,
-          test.mintB,
-          test.authority,
-          Keypair.generate(),
-          undefined,
-          TOKEN_2022_PROGRAM_ID
-        ),
-      ]);
-    test.userPoolTokenAccount = userPoolTokenReciever;
-    test.poolFeeAccount = poolFeeAccount;
-    test.swapTokenA = swapTokenA;
-    test.swapTokenB = swapTokenB;
+    // The swap, its authority, token accounts and pool mint are all PDAs
+    const feeTier = Buffer.alloc(2);
+    feeTier.writeUInt16LE(test.feeTier);
+    [test.tokenSwap] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("swap"),
+        test.mintA.toBuffer(),
+        test.mintB.toBuffer(),
+        curveTypeSeed(test.curveType),
+        feeTier,
+      ],
+      programId
+    );
+    [test.authority, test.authorityBumpSeed] = PublicKey.findProgramAddressSync(
+      [test.tokenSwap.toBuffer()],
+      programId
+    );
+    [test.swapTokenA] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("vault"),
+        test.tokenSwap.toBuffer(),
+        test.mintA.toBuffer(),
+      ],
+      programId
+    );
+    [test.swapTokenB] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("vault"),
+        test.tokenSwap.toBuffer(),
+        test.mintB.toBuffer(),
+      ],
+      programId
+    );
+    [test.poolMint] = PublicKey.findProgramAddressSync(
+      [Buffer.from("pool_mint"), test.tokenSwap.toBuffer()],
+      programId
+    );
+    [test.lockedLiquidity] = PublicKey.findProgramAddressSync(
+      [Buffer.from("locked_liquidity"), test.tokenSwap.toBuffer()],
+      programId
+    );
+    test.userPoolTokenAccount = getAssociatedTokenAddressSync(
+      test.poolMint,
+      test.owner.publicKey,
+      false,
+      TOKEN_2022_PROGRAM_ID
+    );
+    test.poolFeeOwner = Keypair.generate().publicKey;
+    test.poolFeeAccount = getAssociatedTokenAddressSync(
+      test.poolMint,
+      test.poolFeeOwner,
+      false,
+      TOKEN_2022_PROGRAM_ID
+    );
 
-    // Batch 3: Mint tokens
-    await Promise.all([
-      mintTo(
-        connection,
-        test.payer,
-        test.mintA,
-        test.swapTokenA,
-        test.owner,
-        test.amountOfCurrentSwapToken.a,
-        [],
-        undefined,
-        TOKEN_2022_PROGRAM_ID
-      ),
-      mintTo(
-        connection,
-        test.payer,
-        test.mintB,
-        test.swapTokenB,
-        test.owner,
-        test.amountOfCurrentSwapToken.b,
-        [],
-        undefined,
-        TOKEN_2022_PROGRAM_ID
-      ),
-    ]);
+    // Batch 2: Create and fund the accounts providing the initial liquidity
+    [test.userTokenA, test.userTokenB] = await test.createTokenPair(
+      connection
+    );
+    await test.mintToTokenPair(
+      connection,
+      test.userTokenA,
+      test.userTokenB,
+      test.amountOfCurrentSwapToken.a,
+      test.amountOfCurrentSwapToken.b
+    );
 
     return test;
   }
+
+  /**
+   * Initialize the swap with the initial liquidity, registering it in the
+   * factory, the payer of the provider being its admin
+   * @param program
+   * @param fees
+   * @param remainingAccounts transfer hook accounts of token A then token B
+   * @returns signature of the transaction
+   */
+  public async initialize(
+    program: Program<AnchorTokenSwap>,
+    fees: FeesArg,
+    remainingAccounts: AccountMeta[] = []
+  ): Promise<string> {
+    const [config] = PublicKey.findProgramAddressSync(
+      [Buffer.from("config")],
+      program.programId
+    );
+    const [factory] = PublicKey.findProgramAddressSync(
+      [Buffer.from("factory")],
+      program.programId
+    );
+    const { poolCount } = await program.account.factory.fetch(factory);
+    [this.poolEntry] = PublicKey.findProgramAddressSync(
+      [Buffer.from("pool_entry"), poolCount.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const signature = await program.methods
+      .initialize(
+        this.curveType,
+        fees,
+        this.feeTier,
+        new BN(this.amountOfCurrentSwapToken.a.toString()),
+        new BN(this.amountOfCurrentSwapToken.b.toString())
+      )
+      .accountsPartial({
+        config,
+        tokenAMint: this.mintA,
+        tokenBMint: this.mintB,
+        tokenSwap: this.tokenSwap,
+        swapTokenA: this.swapTokenA,
+        swapTokenB: this.swapTokenB,
+        poolMint: this.poolMint,
+        lockedLiquidity: this.lockedLiquidity,
+        factory,
+        poolEntry: this.poolEntry,
+        userTransferAuthority: this.owner.publicKey,
+        userTokenA: this.userTokenA,
+        userTokenB: this.userTokenB,
+        userPoolToken: this.userPoolTokenAccount,
+        poolFeeOwner: this.poolFeeOwner,
+        poolFeeAccount: this.poolFeeAccount,
+        tokenAProgram: this.tokenAProgram,
+        tokenBProgram: this.tokenBProgram,
+        tokenPoolProgram: TOKEN_2022_PROGRAM_ID,
+      })
+      .remainingAccounts(remainingAccounts)
+      .signers([this.owner])
+      .rpc();
+    const [swapTokenA, swapTokenB] = await this.getSwapTokenAccounts(
+      program.provider.connection
+    );
+    this.amountOfCurrentSwapToken = {
+      a: swapTokenA.amount,
+      b: swapTokenB.amount,
+    };
+    return signature;
+  }
+
+  /**
+   * Program owning the given mint of the swap
+   * @param mint mintA or mintB
+   */
+  public tokenProgram(mint: PublicKey): PublicKey {
+    return mint.equals(this.mintA) ? this.tokenAProgram : this.tokenBProgram;
+  }
+  /**
+   * Swap token account of the given mint of the swap
+   * @param mint mintA or mintB
+   */
+  public vault(mint: PublicKey): PublicKey {
+    return mint.equals(this.mintA) ? this.swapTokenA : this.swapTokenB;
+  }
+  /**
+   * The mint of the swap other than the given one
+   * @param mint mintA or mintB
+   */
+  public otherMint(mint: PublicKey): PublicKey {
+    return mint.equals(this.mintA) ? this.mintB : this.mintA;
+  }
   public async getAccount(
     connection: Connection,
     key: PublicKey
   ): Promise<TokenAccount> {
-    return getAccount(connection, key, undefined, TOKEN_2022_PROGRAM_ID);
+    const info = await connection.getAccountInfo(key, "confirmed");
+    return unpackAccount(key, info, info?.owner ?? TOKEN_2022_PROGRAM_ID);
   }
   private async createToken(
     connection: Connection,
@@ -210,6 +571,37 @@ export class TokenSwapTest {
       this.owner.publicKey,
       key,
       undefined,
+      this.tokenProgram(mint)
+    );
+  }
+  private async mintToToken(
+    connection: Connection,
+    mint: PublicKey,
+    tokenAccount: PublicKey,
+    amount: bigint
+  ): Promise<void> {
+    if (mint.equals(NATIVE_MINT)) {
+      // wrap SOL of the payer
+      const tx = new Transaction().add(
+        SystemProgram.transfer({
+          fromPubkey: this.payer.publicKey,
+          toPubkey: tokenAccount,
+          lamports: amount,
+        }),
+        createSyncNativeInstruction(tokenAccount, TOKEN_PROGRAM_ID)
+      );
+      await sendAndConfirmTransaction(connection, tx, [this.payer]);
+      return;
+    }
+    await mintTo(
+      connection,
+      this.payer,
+      mint,
+      tokenAccount,
+      this.owner,
+      amount,
+      [],
+      undefined,
       TOKEN_2022_PROGRAM_ID
     );
   }
@@ -256,28 +648,8 @@ export class TokenSwapTest {
     amountB: bigint
   ): Promise<void> {
     await Promise.all([
-      mintTo(
-        connection,
-        this.payer,
-        this.mintA,
-        tokenAccountA,
-        this.owner,
-        amountA,
-        [],
-        undefined,
-        TOKEN_2022_PROGRAM_ID
-      ),
-      mintTo(
-        connection,
-        this.payer,
-        this.mintB,
-        tokenAccountB,
-        this.owner,
-        amountB,
-        [],
-        undefined,
-        TOKEN_2022_PROGRAM_ID
-      ),
+      this.mintToToken(connection, this.mintA, tokenAccountA, amountA),
+      this.mintToToken(connection, this.mintB, tokenAccountB, amountB),
     ]);
   }
   public async approveForPair(
@@ -298,7 +670,7 @@ export class TokenSwapTest {
         amountA,
         [],
         undefined,
-        TOKEN_2022_PROGRAM_ID
+        this.tokenAProgram
       ),
       approve(
         connection,
@@ -309,7 +681,7 @@ export class TokenSwapTest {
         amountB,
         [],
         undefined,
-        TOKEN_2022_PROGRAM_ID
+        this.tokenBProgram
       ),
     ]);
   }
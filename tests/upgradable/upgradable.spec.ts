@@ -1,8 +1,16 @@
 import * as anchor from "@coral-xyz/anchor";
-import { Program } from "@coral-xyz/anchor";
+import { BN, Program } from "@coral-xyz/anchor";
 import { AnchorTokenSwap } from "../../target/types/anchor_token_swap";
-import { PublicKey, SystemProgram } from "@solana/web3.js";
+import { Keypair, PublicKey, SystemProgram } from "@solana/web3.js";
 import { SYSTEM_PROGRAM_ID } from "@coral-xyz/anchor/dist/cjs/native/system";
+import {
+  TOKEN_PROGRAM_ID,
+  createAccount,
+  createMint,
+  mintTo,
+} from "@solana/spl-token";
+import { assert } from "chai";
+import { expectAnchorError } from "../token";
 
 describe("anchor-token-swap", () => {
   const provider = anchor.AnchorProvider.env();
@@ -27,4 +35,104 @@ describe("anchor-token-swap", () => {
       })
       .rpc();
   });
+
+  it("It should migrate a SwapV1 account once", async () => {
+    const payer = (provider.wallet as anchor.Wallet).payer;
+    const tokenSwap = Keypair.generate();
+    const [authority, bumpSeed] = PublicKey.findProgramAddressSync(
+      [tokenSwap.publicKey.toBuffer()],
+      program.programId
+    );
+    // the swap token accounts and pool mint of the first version swap,
+    // owned by its authority
+    const [mintA, mintB, poolMint] = await Promise.all(
+      [payer.publicKey, payer.publicKey, authority].map((mintAuthority) =>
+        createMint(connection, payer, mintAuthority, null, 2)
+      )
+    );
+    const [swapTokenA, swapTokenB] = await Promise.all(
+      [mintA, mintB].map((mint) =>
+        createAccount(connection, payer, mint, authority, Keypair.generate())
+      )
+    );
+    const poolFeeAccount = await createAccount(
+      connection,
+      payer,
+      poolMint,
+      payer.publicKey,
+      Keypair.generate()
+    );
+    await mintTo(connection, payer, mintA, swapTokenA, payer, 1000000);
+    await mintTo(connection, payer, mintB, swapTokenB, payer, 2000000);
+    const fees = {
+      tradeFeeNumerator: new BN(25),
+      tradeFeeDenominator: new BN(10000),
+      ownerTradeFeeNumerator: new BN(5),
+      ownerTradeFeeDenominator: new BN(10000),
+      ownerWithdrawFeeNumerator: new BN(0),
+      ownerWithdrawFeeDenominator: new BN(0),
+      hostFeeNumerator: new BN(20),
+      hostFeeDenominator: new BN(100),
+    };
+    await program.methods
+      .createSwapV1(fees, { constantProduct: {} })
+      .accountsPartial({
+        payer: payer.publicKey,
+        tokenSwap: tokenSwap.publicKey,
+        swapTokenA,
+        swapTokenB,
+        poolMint,
+        poolFeeAccount,
+      })
+      .signers([tokenSwap])
+      .rpc();
+    const swapV1 = await connection.getAccountInfo(tokenSwap.publicKey);
+    const swapV1Size = swapV1.data.length;
+
+    const migrate = () =>
+      program.methods
+        .migrateV1ToV2()
+        .accountsPartial({
+          payer: payer.publicKey,
+          tokenSwap: tokenSwap.publicKey,
+          swapTokenA,
+          swapTokenB,
+          poolMint,
+          poolFeeAccount,
+        })
+        .rpc();
+    await migrate();
+
+    // the account grew to the size of a SwapV2, rent exempt at that size
+    const swapV2Account = await connection.getAccountInfo(tokenSwap.publicKey);
+    assert.isAbove(swapV2Account.data.length, swapV1Size);
+    assert.ok(swapV2Account.owner.equals(program.programId));
+    assert.isAtLeast(
+      swapV2Account.lamports,
+      await connection.getMinimumBalanceForRentExemption(
+        swapV2Account.data.length
+      )
+    );
+    const swap = await program.account.swapV2.fetch(tokenSwap.publicKey);
+    assert.ok(swap.tokenA.equals(swapTokenA));
+    assert.ok(swap.tokenB.equals(swapTokenB));
+    assert.ok(swap.poolMint.equals(poolMint));
+    assert.ok(swap.tokenAMint.equals(mintA));
+    assert.ok(swap.tokenBMint.equals(mintB));
+    assert.ok(swap.poolFeeAccount.equals(poolFeeAccount));
+    assert.ok(swap.fees.tradeFeeNumerator.eq(fees.tradeFeeNumerator));
+    assert.deepEqual(swap.curveType, { constantProduct: {} });
+    assert.equal(swap.bumpSeed, bumpSeed);
+    assert.equal(swap.feeTier, 25);
+    // the admin is the owner of the pool fee account, the reserves the
+    // balances of the swap token accounts
+    assert.ok(swap.admin.equals(payer.publicKey));
+    assert.ok(swap.tokenAProgram.equals(TOKEN_PROGRAM_ID));
+    assert.ok(swap.reserveA.eq(new BN(1000000)));
+    assert.ok(swap.reserveB.eq(new BN(2000000)));
+    assert.deepEqual(swap.status, { active: {} });
+
+    // a migrated swap is no longer a SwapV1
+    await expectAnchorError(migrate(), "AccountDiscriminatorMismatch");
+  });
 });